@@ -0,0 +1,160 @@
+//! 交互示例：`cargo run -p custom_widget --example candlestick`
+//!
+//! `t` 切换绘制方式，`r` 切换画布/单元格绘制，`l` 切换图例位置，
+//! `[`/`]` 调整 K 线宽度，`q` 退出。
+
+use crossterm::{
+    event::{self, Event, KeyCode},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use custom_widget::{
+    Annotation, Axis, ChartType, Dataset, KLine, KlineData, LegendPosition, Renderer,
+};
+use std::{error::Error, io};
+use tui::{
+    backend::{Backend, CrosstermBackend},
+    style::{Color, Style},
+    symbols,
+    text::Span,
+    widgets::{Block, Borders},
+    Terminal,
+};
+
+/// 按固定的伪随机序列生成 `len` 根日 k 线，最早的在前
+fn generate(len: usize) -> Vec<KlineData<'static>> {
+    let mut seed: u64 = 20230407;
+    let mut next = move || {
+        seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (seed >> 33) as f64 / (1u64 << 31) as f64 - 0.5
+    };
+    let mut close = 10.0;
+    (0..len)
+        .map(|i| {
+            let start = close;
+            close = (start * (1.0 + next() * 0.06)).max(1.0);
+            let max = start.max(close) * (1.0 + next().abs() * 0.02);
+            let min = start.min(close) * (1.0 - next().abs() * 0.02);
+            KlineData {
+                date: format!("D{:03}", i + 1).into(),
+                start,
+                end: close,
+                min,
+                max,
+                ..KlineData::default()
+            }
+        })
+        .collect()
+}
+
+struct State {
+    datas: Vec<KlineData<'static>>,
+    chart_type: ChartType,
+    renderer: Renderer,
+    legend: Option<LegendPosition>,
+    candle_width: f64,
+}
+
+impl State {
+    fn next_legend(&mut self) {
+        self.legend = match self.legend {
+            Some(LegendPosition::TopRight) => Some(LegendPosition::TopLeft),
+            Some(LegendPosition::TopLeft) => Some(LegendPosition::BottomLeft),
+            Some(LegendPosition::BottomLeft) => Some(LegendPosition::BottomRight),
+            Some(LegendPosition::BottomRight) => None,
+            None => Some(LegendPosition::TopRight),
+        };
+    }
+}
+
+fn run<B: Backend>(terminal: &mut Terminal<B>, state: &mut State) -> io::Result<()> {
+    loop {
+        terminal.draw(|f| {
+            let datas = &state.datas;
+            let [min, max] = datas.iter().fold([f64::MAX, f64::MIN], |[min, max], data| {
+                [min.min(data.min), max.max(data.max)]
+            });
+            let last = datas.last().map_or(0.0, |data| data.end);
+            let dataset = Dataset::default()
+                .name(state.chart_type.name())
+                .marker(symbols::Marker::Braille)
+                .chart_type(state.chart_type)
+                .style(Style::default().fg(Color::Yellow))
+                .candle_width(state.candle_width)
+                .data(datas);
+            let title = format!(
+                "{:?}  宽度 {:.1}  t 绘制方式 r 渲染 l 图例 [ ] 宽度 q 退出",
+                state.renderer, state.candle_width
+            );
+            let kline = KLine::new(vec![dataset])
+                .block(Block::default().title(title).borders(Borders::ALL))
+                .renderer(state.renderer)
+                .legend_position(state.legend)
+                .annotations(vec![Annotation::Level {
+                    price: last,
+                    color: Color::Cyan,
+                }])
+                .x_axis(
+                    Axis::default()
+                        .style(Style::default().fg(Color::Gray))
+                        .bounds([-0.5, datas.len() as f64 - 0.5])
+                        .labels(vec![
+                            Span::raw(datas[0].date.as_ref()),
+                            Span::raw(datas[datas.len() - 1].date.as_ref()),
+                        ]),
+                )
+                .y_axis(
+                    Axis::default()
+                        .style(Style::default().fg(Color::Gray))
+                        .bounds([min, max])
+                        .labels(vec![
+                            Span::raw(format!("{:.2}", min)),
+                            Span::raw(format!("{:.2}", (min + max) / 2.0)),
+                            Span::raw(format!("{:.2}", max)),
+                        ]),
+                );
+            f.render_widget(kline, f.size());
+        })?;
+
+        if let Event::Key(key) = event::read()? {
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Char('t') => state.chart_type = state.chart_type.next(),
+                KeyCode::Char('r') => {
+                    state.renderer = match state.renderer {
+                        Renderer::Canvas => Renderer::Cell,
+                        Renderer::Cell => Renderer::Canvas,
+                    }
+                }
+                KeyCode::Char('l') => state.next_legend(),
+                KeyCode::Char('[') => state.candle_width = (state.candle_width - 0.1).max(0.0),
+                KeyCode::Char(']') => state.candle_width = (state.candle_width + 0.1).min(1.0),
+                _ => {}
+            }
+        }
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+
+    let mut state = State {
+        datas: generate(60),
+        chart_type: ChartType::Candlestick,
+        renderer: Renderer::Cell,
+        legend: Some(LegendPosition::TopRight),
+        candle_width: 0.6,
+    };
+    let result = run(&mut terminal, &mut state);
+
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+    result?;
+    Ok(())
+}
@@ -0,0 +1,70 @@
+//! 不占用终端的示例：把 K 线图绘制到缓冲区后逐行打印，
+//! `cargo run -p custom_widget --example print`
+
+use custom_widget::{Axis, Dataset, KLine, KlineData, Renderer};
+use tui::{
+    buffer::Buffer,
+    layout::Rect,
+    text::Span,
+    widgets::{Block, Borders, Widget},
+};
+use unicode_width::UnicodeWidthStr;
+
+fn main() {
+    // 日期、开盘、收盘、最低、最高，最早的在前
+    let bars = [
+        ("03-27", 0.894, 0.884, 0.880, 0.895),
+        ("03-28", 0.886, 0.903, 0.886, 0.906),
+        ("03-29", 0.908, 0.912, 0.898, 0.914),
+        ("03-30", 0.911, 0.924, 0.905, 0.926),
+        ("03-31", 0.927, 0.920, 0.919, 0.939),
+        ("04-03", 0.919, 0.918, 0.908, 0.926),
+        ("04-04", 0.917, 0.925, 0.908, 0.927),
+        ("04-06", 0.919, 0.904, 0.901, 0.924),
+        ("04-07", 0.902, 0.900, 0.894, 0.912),
+    ];
+    let datas: Vec<KlineData> = bars
+        .iter()
+        .map(|&(date, start, end, min, max)| KlineData {
+            date: date.into(),
+            start,
+            end,
+            min,
+            max,
+            ..KlineData::default()
+        })
+        .collect();
+
+    let kline = KLine::new(vec![Dataset::default().name("512690").data(&datas)])
+        .renderer(Renderer::Cell)
+        .block(Block::default().title("酒ETF").borders(Borders::ALL))
+        .x_axis(
+            Axis::default()
+                .bounds([-0.5, datas.len() as f64 - 0.5])
+                .labels(vec![
+                    Span::raw("03-27"),
+                    Span::raw("03-31"),
+                    Span::raw("04-07"),
+                ]),
+        )
+        .y_axis(
+            Axis::default()
+                .bounds([0.880, 0.939])
+                .labels(vec![Span::raw("0.880"), Span::raw("0.939")]),
+        );
+
+    let area = Rect::new(0, 0, 50, 16);
+    let mut buf = Buffer::empty(area);
+    kline.render(area, &mut buf);
+    for y in 0..area.height {
+        let mut line = String::new();
+        let mut x = 0;
+        while x < area.width {
+            let symbol = &buf.get(x, y).symbol;
+            line.push_str(symbol);
+            // 宽字符占两列，跳过被占用的单元格
+            x += symbol.width().max(1) as u16;
+        }
+        println!("{}", line);
+    }
+}
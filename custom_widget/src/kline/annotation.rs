@@ -0,0 +1,193 @@
+use std::borrow::Cow;
+use tui::style::{Color, Style};
+use tui::text::Span;
+use tui::widgets::canvas::{Context, Line};
+
+/// 斐波那契回撤的比例
+pub const FIBONACCI_RATIOS: [f64; 7] = [0.0, 0.236, 0.382, 0.5, 0.618, 0.786, 1.0];
+
+/// 叠加在 K 线图上的标注，坐标与 `Dataset` 的数据使用同一坐标系：
+/// x 为 K 线的下标，y 为价格
+#[derive(Debug, Clone)]
+pub enum Annotation<'a> {
+    /// 水平价位线，横跨整个图表
+    Level { price: f64, color: Color },
+    /// 两点趋势线
+    TrendLine {
+        x1: f64,
+        y1: f64,
+        x2: f64,
+        y2: f64,
+        color: Color,
+    },
+    /// 两点之间的斐波那契回撤，从较早的点向右延伸
+    Fibonacci {
+        x1: f64,
+        y1: f64,
+        x2: f64,
+        y2: f64,
+        color: Color,
+    },
+    /// 文字备注
+    Note {
+        x: f64,
+        y: f64,
+        text: Cow<'a, str>,
+        color: Color,
+    },
+    /// 十字光标
+    Crosshair { x: f64, y: f64, color: Color },
+}
+
+/// 把线段裁剪到边界之内，完全在边界外时返回 `None`
+///
+/// 画布的 `Line` 只要有一个端点在边界外就不会绘制，所以锚定在可视范围外的
+/// 趋势线需要先裁剪
+fn clip(
+    (x1, y1, x2, y2): (f64, f64, f64, f64),
+    x_bounds: [f64; 2],
+    y_bounds: [f64; 2],
+) -> Option<(f64, f64, f64, f64)> {
+    let (dx, dy) = (x2 - x1, y2 - y1);
+    let mut t0: f64 = 0.0;
+    let mut t1: f64 = 1.0;
+    let edges = [
+        (-dx, x1 - x_bounds[0]),
+        (dx, x_bounds[1] - x1),
+        (-dy, y1 - y_bounds[0]),
+        (dy, y_bounds[1] - y1),
+    ];
+    for (p, q) in edges {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+            continue;
+        }
+        let t = q / p;
+        if p < 0.0 {
+            t0 = t0.max(t);
+        } else {
+            t1 = t1.min(t);
+        }
+        if t0 > t1 {
+            return None;
+        }
+    }
+    Some((x1 + t0 * dx, y1 + t0 * dy, x1 + t1 * dx, y1 + t1 * dy))
+}
+
+fn draw_segment(
+    ctx: &mut Context,
+    segment: (f64, f64, f64, f64),
+    color: Color,
+    x_bounds: [f64; 2],
+    y_bounds: [f64; 2],
+) {
+    if let Some((x1, y1, x2, y2)) = clip(segment, x_bounds, y_bounds) {
+        ctx.draw(&Line {
+            x1,
+            y1,
+            x2,
+            y2,
+            color,
+        });
+    }
+}
+
+fn print(
+    ctx: &mut Context,
+    x: f64,
+    y: f64,
+    text: String,
+    color: Color,
+    x_bounds: [f64; 2],
+    y_bounds: [f64; 2],
+) {
+    let inside = |v: f64, bounds: [f64; 2]| bounds[0] <= v && v <= bounds[1];
+    if inside(x, x_bounds) && inside(y, y_bounds) {
+        ctx.print(x, y, Span::styled(text, Style::default().fg(color)));
+    }
+}
+
+impl<'a> Annotation<'a> {
+    pub fn draw(&self, ctx: &mut Context, x_bounds: [f64; 2], y_bounds: [f64; 2]) {
+        match self {
+            Annotation::Level { price, color } => {
+                draw_segment(
+                    ctx,
+                    (x_bounds[0], *price, x_bounds[1], *price),
+                    *color,
+                    x_bounds,
+                    y_bounds,
+                );
+                print(
+                    ctx,
+                    x_bounds[0],
+                    *price,
+                    format!("{:.3}", price),
+                    *color,
+                    x_bounds,
+                    y_bounds,
+                );
+            }
+            Annotation::TrendLine {
+                x1,
+                y1,
+                x2,
+                y2,
+                color,
+            } => {
+                draw_segment(ctx, (*x1, *y1, *x2, *y2), *color, x_bounds, y_bounds);
+            }
+            Annotation::Fibonacci {
+                x1,
+                y1,
+                x2,
+                y2,
+                color,
+            } => {
+                let left = x1.min(*x2);
+                for ratio in FIBONACCI_RATIOS {
+                    let price = y2 + (y1 - y2) * ratio;
+                    draw_segment(
+                        ctx,
+                        (left, price, x_bounds[1], price),
+                        *color,
+                        x_bounds,
+                        y_bounds,
+                    );
+                    let label = format!("{:.1}% {:.3}", ratio * 100.0, price);
+                    print(
+                        ctx,
+                        left.max(x_bounds[0]),
+                        price,
+                        label,
+                        *color,
+                        x_bounds,
+                        y_bounds,
+                    );
+                }
+            }
+            Annotation::Note { x, y, text, color } => {
+                print(ctx, *x, *y, text.to_string(), *color, x_bounds, y_bounds);
+            }
+            Annotation::Crosshair { x, y, color } => {
+                draw_segment(
+                    ctx,
+                    (*x, y_bounds[0], *x, y_bounds[1]),
+                    *color,
+                    x_bounds,
+                    y_bounds,
+                );
+                draw_segment(
+                    ctx,
+                    (x_bounds[0], *y, x_bounds[1], *y),
+                    *color,
+                    x_bounds,
+                    y_bounds,
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use crate::kline::klinedata::{heikin_ashi, ChartType, KlineData};
+use tui::buffer::Buffer;
+use tui::layout::Rect;
+use tui::style::Color;
+use tui::widgets::Widget;
+
+/// 直接写入 `Buffer` 的 K 线，每根 K 线占一列（放大时占多列），
+/// 每个单元格按上下两半计算，得到两倍的纵向分辨率。
+///
+/// ```text
+///    │
+///   ╽    │
+///   █   ▄█▄
+///   ╿   ▀█▀
+///   │    ╵
+/// ```
+///
+/// 只支持 `ChartType::Candlestick` 与 `ChartType::HeikinAshi`，
+/// 其余绘制方式由 `KLine` 回退到画布绘制。
+pub struct CellCandles<'a> {
+    pub coords: &'a [KlineData<'a>],
+    pub chart_type: ChartType,
+    pub x_bounds: [f64; 2],
+    pub y_bounds: [f64; 2],
+    pub up_color: Color,
+    pub down_color: Color,
+    /// 实体占相邻 K 线间距的比例，`None` 时实体尽量宽并在 K 线之间留出一列
+    pub candle_width: Option<f64>,
+}
+
+impl<'a> CellCandles<'a> {
+    pub fn supports(chart_type: ChartType) -> bool {
+        matches!(chart_type, ChartType::Candlestick | ChartType::HeikinAshi)
+    }
+
+    /// 价格换算为从底部开始计数的半格序号
+    fn half_row(&self, price: f64, half_rows: u16) -> i32 {
+        let [min, max] = self.y_bounds;
+        let height = max - min;
+        if height <= 0.0 {
+            return (half_rows / 2) as i32;
+        }
+        ((price - min) / height * (half_rows - 1) as f64).round() as i32
+    }
+
+    /// 实体占用的列数，保持奇数以便影线居中
+    fn body_width(&self, columns_per_bar: u16) -> u16 {
+        let width = match self.candle_width {
+            Some(ratio) => (columns_per_bar as f64 * ratio).round() as u16,
+            None => columns_per_bar.saturating_sub(1),
+        };
+        match width.min(columns_per_bar) {
+            0 => 1,
+            w if w % 2 == 0 => w - 1,
+            w => w,
+        }
+    }
+
+    fn symbol(body: (bool, bool), wick: (bool, bool), center: bool) -> Option<&'static str> {
+        let (body_lower, body_upper) = body;
+        let (wick_lower, wick_upper) = if center { wick } else { (false, false) };
+        let symbol = match (body_lower, body_upper) {
+            (true, true) => "█",
+            (false, true) if wick_lower => "╿",
+            (false, true) => "▀",
+            (true, false) if wick_upper => "╽",
+            (true, false) => "▄",
+            (false, false) => match (wick_lower, wick_upper) {
+                (true, true) => "│",
+                (false, true) => "╵",
+                (true, false) => "╷",
+                (false, false) => return None,
+            },
+        };
+        Some(symbol)
+    }
+
+    fn render_candle(
+        &self,
+        k_line_data: &KlineData,
+        columns: (u16, u16, u16),
+        area: Rect,
+        buf: &mut Buffer,
+    ) {
+        let (first, center, last) = columns;
+        let half_rows = area.height * 2;
+        let color = if k_line_data.start > k_line_data.end {
+            self.down_color
+        } else {
+            self.up_color
+        };
+        let wick = (
+            self.half_row(k_line_data.min, half_rows),
+            self.half_row(k_line_data.max, half_rows),
+        );
+        let body = (
+            self.half_row(k_line_data.start.min(k_line_data.end), half_rows),
+            self.half_row(k_line_data.start.max(k_line_data.end), half_rows),
+        );
+        let contains =
+            |range: (i32, i32), half_row: i32| range.0 <= half_row && half_row <= range.1;
+        for row in 0..area.height {
+            let lower = row as i32 * 2;
+            let upper = lower + 1;
+            let y = area.bottom() - 1 - row;
+            for x in first..=last {
+                let symbol = Self::symbol(
+                    (contains(body, lower), contains(body, upper)),
+                    (contains(wick, lower), contains(wick, upper)),
+                    x == center,
+                );
+                if let Some(symbol) = symbol {
+                    buf.get_mut(x, y).set_symbol(symbol).set_fg(color);
+                }
+            }
+        }
+    }
+}
+
+impl<'a> Widget for CellCandles<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let [x_min, x_max] = self.x_bounds;
+        let x_range = x_max - x_min;
+        if area.area() == 0 || x_range <= 0.0 {
+            return;
+        }
+        let heikin_ashi_datas;
+        let coords = match self.chart_type {
+            ChartType::HeikinAshi => {
+                heikin_ashi_datas = heikin_ashi(self.coords);
+                &heikin_ashi_datas[..]
+            }
+            _ => self.coords,
+        };
+        let columns_per_bar = (area.width as f64 / x_range).floor().max(1.0) as u16;
+        let body_width = self.body_width(columns_per_bar);
+        for (x, k_line_data) in coords.iter().enumerate() {
+            let offset = (x as f64 - x_min) / x_range * area.width as f64;
+            if offset < 0.0 || offset >= area.width as f64 {
+                continue;
+            }
+            let center = area.left() + offset as u16;
+            let first = center.saturating_sub(body_width / 2).max(area.left());
+            let last = (center + body_width / 2).min(area.right() - 1);
+            self.render_candle(k_line_data, (first, center, last), area, buf);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candles(candle_width: Option<f64>) -> CellCandles<'static> {
+        CellCandles {
+            coords: &[],
+            chart_type: ChartType::Candlestick,
+            x_bounds: [-0.5, 9.5],
+            y_bounds: [0.0, 1.0],
+            up_color: Color::Red,
+            down_color: Color::Green,
+            candle_width,
+        }
+    }
+
+    #[test]
+    fn body_width_leaves_a_gap_by_default() {
+        assert_eq!(candles(None).body_width(1), 1);
+        assert_eq!(candles(None).body_width(4), 3);
+        assert_eq!(candles(None).body_width(6), 5);
+    }
+
+    #[test]
+    fn body_width_follows_ratio_and_stays_odd() {
+        assert_eq!(candles(Some(0.5)).body_width(6), 3);
+        assert_eq!(candles(Some(0.5)).body_width(8), 3);
+        assert_eq!(candles(Some(1.0)).body_width(7), 7);
+        assert_eq!(candles(Some(0.0)).body_width(7), 1);
+    }
+}
@@ -1,4 +1,6 @@
-use crate::kline::klinedata::{KlineData, KlineDatas};
+use crate::kline::annotation::Annotation;
+use crate::kline::cell::CellCandles;
+use crate::kline::klinedata::{ChartType, KlineData, KlineDatas, DEFAULT_CANDLE_WIDTH};
 use std::borrow::Cow;
 use std::cmp::max;
 use tui::buffer::Buffer;
@@ -6,7 +8,7 @@ use tui::layout::{Alignment, Constraint, Rect};
 use tui::style::{Color, Style};
 use tui::symbols;
 use tui::text::{Span, Spans};
-use tui::widgets::canvas::{Canvas};
+use tui::widgets::canvas::Canvas;
 use tui::widgets::{Block, Borders, Widget};
 use unicode_width::UnicodeWidthStr;
 
@@ -38,6 +40,7 @@ impl<'a> Default for Axis<'a> {
 }
 
 impl<'a> Axis<'a> {
+    /// 坐标轴标题，x 轴的标题显示在 K 线区域的右下角
     pub fn title<T>(mut self, title: T) -> Axis<'a>
     where
         T: Into<Spans<'a>>,
@@ -54,28 +57,60 @@ impl<'a> Axis<'a> {
         self
     }
 
-    pub fn bounds(mut self, bounds: [f64; 2]) ->Axis<'a> {
+    /// 坐标轴的范围，x 轴以 K 线的下标为坐标，y 轴以价格为坐标
+    pub fn bounds(mut self, bounds: [f64; 2]) -> Axis<'a> {
         self.bounds = bounds;
         self
     }
 
+    /// 沿坐标轴均匀分布的标签，第一个在起点、最后一个在终点
     pub fn labels(mut self, labels: Vec<Span<'a>>) -> Axis<'a> {
         self.labels = Some(labels);
         self
     }
 
+    /// 坐标轴线的样式
     pub fn style(mut self, style: Style) -> Axis<'a> {
         self.style = style;
         self
     }
+
+    /// 标签的对齐方式：x 轴只影响第一个标签相对 y 轴的位置，
+    /// y 轴决定标签在 y 轴左侧区域中的对齐，默认左对齐
+    pub fn labels_alignment(mut self, alignment: Alignment) -> Axis<'a> {
+        self.labels_alignment = alignment;
+        self
+    }
+}
+
+/// 最小值不小于最大值时（如价格不变、只有一个点）以两者中点为中心向两侧各扩展 1%，
+/// 中点为 0 时扩展 1，使绘制的数据位于中间
+pub fn padded_bounds(bounds: [f64; 2]) -> [f64; 2] {
+    let [min, max] = bounds;
+    if max > min {
+        return bounds;
+    }
+    let center = (min + max) / 2.0;
+    let center = if center.is_finite() { center } else { 0.0 };
+    let pad = if center == 0.0 {
+        1.0
+    } else {
+        center.abs() * 0.01
+    };
+    [center - pad, center + pad]
 }
 
+/// 一组 K 线数据及其绘制方式，数据的下标即 x 坐标
 #[derive(Debug, Clone)]
 pub struct Dataset<'a> {
     name: Cow<'a, str>,
     data: &'a [KlineData<'a>],
     marker: symbols::Marker,
     style: Style,
+    chart_type: ChartType,
+    up_color: Color,
+    down_color: Color,
+    candle_width: Option<f64>,
 }
 
 impl<'a> Default for Dataset<'a> {
@@ -85,11 +120,16 @@ impl<'a> Default for Dataset<'a> {
             data: &[],
             marker: symbols::Marker::Dot,
             style: Style::default(),
+            chart_type: ChartType::default(),
+            up_color: Color::Red,
+            down_color: Color::Green,
+            candle_width: None,
         }
     }
 }
 
 impl<'a> Dataset<'a> {
+    /// 显示在图例中的名称，为空时不显示图例
     pub fn name<S>(mut self, name: S) -> Dataset<'a>
     where
         S: Into<Cow<'a, str>>,
@@ -98,15 +138,44 @@ impl<'a> Dataset<'a> {
         self
     }
 
+    /// 要绘制的数据，第 i 个数据画在 x = i 处
     pub fn data(mut self, data: &'a [KlineData]) -> Dataset<'a> {
         self.data = data;
         self
     }
 
+    /// 画布绘制时使用的点，`Marker::Braille` 精度最高
     pub fn marker(mut self, marker: symbols::Marker) -> Dataset<'a> {
         self.marker = marker;
         self
     }
+
+    /// 数据集的样式，折线与面积图使用其前景色，图例也以此样式显示名称
+    pub fn style(mut self, style: Style) -> Dataset<'a> {
+        self.style = style;
+        self
+    }
+
+    /// 绘制方式，默认为空心 K 线
+    pub fn chart_type(mut self, chart_type: ChartType) -> Dataset<'a> {
+        self.chart_type = chart_type;
+        self
+    }
+
+    /// 上涨与下跌 K 线的颜色，默认红涨绿跌
+    pub fn candle_colors(mut self, up: Color, down: Color) -> Dataset<'a> {
+        self.up_color = up;
+        self.down_color = down;
+        self
+    }
+
+    /// K 线实体占相邻 K 线间距的比例，取值 0 到 1。
+    ///
+    /// 未设置时画布绘制为 `DEFAULT_CANDLE_WIDTH`，单元格绘制时实体尽量宽并在 K 线之间留出一列
+    pub fn candle_width(mut self, ratio: f64) -> Dataset<'a> {
+        self.candle_width = Some(ratio.clamp(0.0, 1.0));
+        self
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Default)]
@@ -129,6 +198,27 @@ struct ChartLayout {
     graph_area: Rect,
 }
 
+/// 图例在 K 线区域中的位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LegendPosition {
+    #[default]
+    TopRight,
+    TopLeft,
+    BottomRight,
+    BottomLeft,
+}
+
+/// K 线的绘制方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Renderer {
+    /// 通过 `Canvas` 绘制，精度取决于 `Dataset` 的 marker
+    #[default]
+    Canvas,
+    /// 使用方块字符直接写入单元格，实体为实心
+    Cell,
+}
+
+/// K 线图控件：坐标轴、标签、图例，以及按 `Dataset` 绘制的 K 线、折线与标注
 pub struct KLine<'a> {
     block: Option<Block<'a>>,
     x_axis: Axis<'a>,
@@ -136,6 +226,11 @@ pub struct KLine<'a> {
     datasets: Vec<Dataset<'a>>,
     style: Style,
     hidden_legend_constraints: (Constraint, Constraint),
+    legend_position: Option<LegendPosition>,
+    renderer: Renderer,
+    annotations: Vec<Annotation<'a>>,
+    /// 所有数据集都没有数据时居中显示的文字
+    no_data: Cow<'a, str>,
 }
 
 impl<'a> KLine<'a> {
@@ -147,6 +242,10 @@ impl<'a> KLine<'a> {
             style: Default::default(),
             datasets,
             hidden_legend_constraints: (Constraint::Ratio(1, 4), Constraint::Ratio(1, 4)),
+            legend_position: Some(LegendPosition::default()),
+            renderer: Renderer::default(),
+            annotations: Vec::new(),
+            no_data: Cow::from("暂无数据"),
         }
     }
 
@@ -155,11 +254,19 @@ impl<'a> KLine<'a> {
         self
     }
 
+    /// 整个控件的样式，其背景色同时作为画布的背景
     pub fn style(mut self, style: Style) -> KLine<'a> {
         self.style = style;
         self
     }
 
+    /// 背景色，等同于设置 `style` 的背景
+    pub fn background(mut self, color: Color) -> KLine<'a> {
+        self.style = self.style.bg(color);
+        self
+    }
+
+    /// x 轴，范围一般为 `[-0.5, 数据个数 - 0.5]`，使两端的 K 线完整显示
     pub fn x_axis(mut self, axis: Axis<'a>) -> KLine<'a> {
         self.x_axis = axis;
         self
@@ -170,11 +277,48 @@ impl<'a> KLine<'a> {
         self
     }
 
-    fn hidden_legend_constraints(mut self, constraints: (Constraint, Constraint)) -> KLine<'a> {
+    /// K 线的绘制方式，默认通过画布绘制
+    pub fn renderer(mut self, renderer: Renderer) -> KLine<'a> {
+        self.renderer = renderer;
+        self
+    }
+
+    /// 叠加在 K 线之上的标注，如价位线、趋势线、备注
+    pub fn annotations(mut self, annotations: Vec<Annotation<'a>>) -> KLine<'a> {
+        self.annotations = annotations;
+        self
+    }
+
+    /// 没有数据时代替 K 线显示的文字，默认为“暂无数据”
+    pub fn no_data<S>(mut self, text: S) -> KLine<'a>
+    where
+        S: Into<Cow<'a, str>>,
+    {
+        self.no_data = text.into();
+        self
+    }
+
+    /// 计算在 `area` 中绘制时 K 线区域的位置，可用于鼠标坐标换算
+    pub fn graph_area(&self, area: Rect) -> Rect {
+        let chart_area = match self.block {
+            Some(ref b) => b.inner(area),
+            None => area,
+        };
+        self.layout(chart_area).graph_area
+    }
+
+    /// 图例宽高超过 K 线区域宽高的这个比例时不显示图例，默认均为 1/4
+    pub fn hidden_legend_constraints(mut self, constraints: (Constraint, Constraint)) -> KLine<'a> {
         self.hidden_legend_constraints = constraints;
         self
     }
 
+    /// 图例的位置，`None` 时不显示图例，默认在右上角
+    pub fn legend_position(mut self, position: Option<LegendPosition>) -> KLine<'a> {
+        self.legend_position = position;
+        self
+    }
+
     fn layout(&self, area: Rect) -> ChartLayout {
         let mut layout = ChartLayout::default();
         if area.width == 0 || area.height == 0 {
@@ -223,16 +367,26 @@ impl<'a> KLine<'a> {
                 .hidden_legend_constraints
                 .1
                 .apply(layout.graph_area.height);
-            if inner_width > 0
-                && legend_width < max_legend_width
-                && legend_height < max_legend_height
-            {
-                layout.legend_area = Some(Rect::new(
-                    layout.graph_area.right() - legend_width,
-                    layout.graph_area.top(),
-                    legend_width,
-                    legend_height,
-                ));
+            if let (Some(position), true) = (
+                self.legend_position,
+                inner_width > 0
+                    && legend_width < max_legend_width
+                    && legend_height < max_legend_height,
+            ) {
+                let graph_area = layout.graph_area;
+                let x = match position {
+                    LegendPosition::TopLeft | LegendPosition::BottomLeft => graph_area.left(),
+                    LegendPosition::TopRight | LegendPosition::BottomRight => {
+                        graph_area.right() - legend_width
+                    }
+                };
+                let y = match position {
+                    LegendPosition::TopLeft | LegendPosition::TopRight => graph_area.top(),
+                    LegendPosition::BottomLeft | LegendPosition::BottomRight => {
+                        graph_area.bottom() - legend_height
+                    }
+                };
+                layout.legend_area = Some(Rect::new(x, y, legend_width, legend_height));
             }
         }
         layout
@@ -247,7 +401,12 @@ impl<'a> KLine<'a> {
             .map(|l| l.iter().map(Span::width).max().unwrap_or_default() as u16)
             .unwrap_or_default();
 
-        if let Some(first_x_label) = self.x_axis.labels.as_ref().and_then(|labels| labels.get(0)) {
+        if let Some(first_x_label) = self
+            .x_axis
+            .labels
+            .as_ref()
+            .and_then(|labels| labels.first())
+        {
             let first_labels_width = first_x_label.content.width() as u16;
             let width_left_of_y_axis = match self.x_axis.labels_alignment {
                 Alignment::Left => {
@@ -296,17 +455,18 @@ impl<'a> KLine<'a> {
             None => return,
         };
 
-        let labels = self.x_axis.labels.as_ref().unwrap();
+        let labels = match self.x_axis.labels.as_ref() {
+            Some(labels) if !labels.is_empty() => labels,
+            _ => return,
+        };
         let labels_len = labels.len() as u16;
-        if labels_len < 2 {
-            return;
-        }
+        let first = &labels[0];
 
         let width_between_ticks = graph_area.width / labels_len;
 
         let label_area = self.first_x_label_area(
             y,
-            labels.first().unwrap().width() as u16,
+            first.width() as u16,
             width_between_ticks,
             chart_area,
             graph_area,
@@ -318,7 +478,10 @@ impl<'a> KLine<'a> {
             Alignment::Right => Alignment::Left,
         };
 
-        Self::render_label(buf, labels.first().unwrap(), label_area, label_alignment);
+        Self::render_label(buf, first, label_area, label_alignment);
+        if labels_len < 2 {
+            return;
+        }
 
         for (i, label) in labels[1..labels.len() - 1].iter().enumerate() {
             let x = graph_area.left() + (i + 1) as u16 * width_between_ticks + 1;
@@ -344,10 +507,17 @@ impl<'a> KLine<'a> {
             None => return,
         };
 
-        let labels = self.y_axis.labels.as_ref().unwrap();
+        let labels = match self.y_axis.labels.as_ref() {
+            Some(labels) => labels,
+            None => return,
+        };
         let labels_len = labels.len() as u16;
         for (i, label) in labels.iter().enumerate() {
-            let dy = i as u16 * (graph_area.height - 1) / (labels_len - 1);
+            // 只有一个标签时放在最下方
+            let dy = match labels_len {
+                1 => 0,
+                _ => i as u16 * (graph_area.height - 1) / (labels_len - 1),
+            };
             if dy < graph_area.bottom() {
                 let label_area = Rect::new(
                     x,
@@ -360,6 +530,17 @@ impl<'a> KLine<'a> {
         }
     }
 
+    /// 在区域中间显示没有数据的提示
+    fn render_no_data(&self, area: Rect, buf: &mut Buffer) {
+        if area.area() == 0 {
+            return;
+        }
+        let width = (self.no_data.width() as u16).min(area.width);
+        let x = area.left() + (area.width - width) / 2;
+        let y = area.top() + (area.height - 1) / 2;
+        buf.set_stringn(x, y, &self.no_data, width as usize, self.style);
+    }
+
     fn render_label(buf: &mut Buffer, label: &Span, label_area: Rect, alignment: Alignment) {
         let label_width = label.width() as u16;
         let bounded_label_width = label_area.width.min(label_width);
@@ -391,11 +572,18 @@ impl<'a> Widget for KLine<'a> {
             None => area,
         };
 
+        if self.datasets.iter().all(|dataset| dataset.data.is_empty()) {
+            self.render_no_data(chart_area, buf);
+            return;
+        }
+
         let layout = self.layout(chart_area);
         let graph_area = layout.graph_area;
         if graph_area.width < 1 || graph_area.height < 1 {
             return;
         }
+        self.x_axis.bounds = padded_bounds(self.x_axis.bounds);
+        self.y_axis.bounds = padded_bounds(self.y_axis.bounds);
 
         self.render_x_labels(buf, &layout, chart_area, graph_area);
         self.render_y_labels(buf, &layout, chart_area, graph_area);
@@ -425,6 +613,19 @@ impl<'a> Widget for KLine<'a> {
         }
 
         for dataset in &self.datasets {
+            if self.renderer == Renderer::Cell && CellCandles::supports(dataset.chart_type) {
+                CellCandles {
+                    coords: dataset.data,
+                    chart_type: dataset.chart_type,
+                    x_bounds: self.x_axis.bounds,
+                    y_bounds: self.y_axis.bounds,
+                    up_color: dataset.up_color,
+                    down_color: dataset.down_color,
+                    candle_width: dataset.candle_width,
+                }
+                .render(graph_area, buf);
+                continue;
+            }
             Canvas::default()
                 .background_color(self.style.bg.unwrap_or(Color::Reset))
                 .x_bounds(self.x_axis.bounds)
@@ -433,11 +634,32 @@ impl<'a> Widget for KLine<'a> {
                 .paint(|ctx| {
                     ctx.draw(&KlineDatas {
                         coords: dataset.data,
+                        chart_type: dataset.chart_type,
+                        color: dataset.style.fg.unwrap_or(Color::Yellow),
+                        baseline: self.y_axis.bounds[0],
+                        up_color: dataset.up_color,
+                        down_color: dataset.down_color,
+                        candle_width: dataset.candle_width.unwrap_or(DEFAULT_CANDLE_WIDTH),
                     });
                 })
                 .render(graph_area, buf);
         }
 
+        if !self.annotations.is_empty() {
+            let (x_bounds, y_bounds) = (self.x_axis.bounds, self.y_axis.bounds);
+            let annotations = &self.annotations;
+            Canvas::default()
+                .background_color(self.style.bg.unwrap_or(Color::Reset))
+                .x_bounds(x_bounds)
+                .y_bounds(y_bounds)
+                .paint(|ctx| {
+                    for annotation in annotations {
+                        annotation.draw(ctx, x_bounds, y_bounds);
+                    }
+                })
+                .render(graph_area, buf);
+        }
+
         if let Some(legend_area) = layout.legend_area {
             buf.set_style(legend_area, original_style);
             Block::default()
@@ -484,3 +706,103 @@ impl<'a> Widget for KLine<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATAS: [KlineData; 1] = [KlineData {
+        date: Cow::Borrowed("2023-04-07"),
+        pre_close: 0.904,
+        start: 0.902,
+        end: 0.9,
+        min: 0.894,
+        max: 0.912,
+        quantity: 0,
+        amount: 0,
+        up_rate: 0.0,
+        cr: 0.0,
+        avg5: 0.0,
+        avg10: 0.0,
+        avg20: 0.0,
+        avg30: 0.0,
+        avg_q5: 0.0,
+        avg_q10: 0.0,
+    }];
+
+    fn kline(position: Option<LegendPosition>) -> KLine<'static> {
+        KLine::new(vec![Dataset::default().name("K").data(&DATAS)]).legend_position(position)
+    }
+
+    #[test]
+    fn padded_bounds_keeps_valid_ranges() {
+        assert_eq!(padded_bounds([0.5, 1.0]), [0.5, 1.0]);
+        assert_eq!(padded_bounds([1.0, 1.0]), [0.99, 1.01]);
+        assert_eq!(padded_bounds([0.0, 0.0]), [-1.0, 1.0]);
+        assert_eq!(padded_bounds([f64::MAX, f64::MIN]), [-1.0, 1.0]);
+    }
+
+    #[test]
+    fn legend_positions() {
+        let area = Rect::new(0, 0, 40, 20);
+        let legend = |position| kline(Some(position)).layout(area).legend_area;
+        assert_eq!(
+            legend(LegendPosition::TopRight),
+            Some(Rect::new(37, 0, 3, 3))
+        );
+        assert_eq!(legend(LegendPosition::TopLeft), Some(Rect::new(0, 0, 3, 3)));
+        assert_eq!(
+            legend(LegendPosition::BottomRight),
+            Some(Rect::new(37, 17, 3, 3))
+        );
+        assert_eq!(
+            legend(LegendPosition::BottomLeft),
+            Some(Rect::new(0, 17, 3, 3))
+        );
+        assert_eq!(kline(None).layout(area).legend_area, None);
+    }
+
+    #[test]
+    fn legend_hidden_when_too_large() {
+        let area = Rect::new(0, 0, 40, 20);
+        let layout = kline(Some(LegendPosition::TopRight))
+            .hidden_legend_constraints((Constraint::Length(3), Constraint::Length(3)))
+            .layout(area);
+        assert_eq!(layout.legend_area, None);
+    }
+
+    #[test]
+    fn x_labels_alignment_moves_y_axis() {
+        let area = Rect::new(0, 0, 40, 10);
+        let axis_y = |alignment| {
+            KLine::new(vec![])
+                .x_axis(
+                    Axis::default()
+                        .labels(vec![Span::raw("2023-04-07"), Span::raw("2023-04-10")])
+                        .labels_alignment(alignment),
+                )
+                .y_axis(Axis::default().labels(vec![Span::raw("0.9")]))
+                .layout(area)
+                .axis_y
+        };
+        assert_eq!(axis_y(Alignment::Left), Some(9));
+        assert_eq!(axis_y(Alignment::Center), Some(5));
+        assert_eq!(axis_y(Alignment::Right), Some(3));
+    }
+
+    #[test]
+    fn background_fills_area() {
+        let area = Rect::new(0, 0, 20, 6);
+        let mut buf = Buffer::empty(area);
+        kline(None)
+            .background(Color::Blue)
+            .x_axis(Axis::default().bounds([-0.5, 0.5]))
+            .y_axis(Axis::default().bounds([0.894, 0.912]))
+            .render(area, &mut buf);
+        for x in 0..area.width {
+            for y in 0..area.height {
+                assert_eq!(buf.get(x, y).bg, Color::Blue);
+            }
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use std::borrow::Cow;
 use tui::{
     style::Color,
     widgets::canvas::{Line, Painter, Shape},
@@ -20,6 +21,8 @@ use tui::{
 #[derive(Debug, Clone)]
 pub struct Kblock {
     pub x: f64,
+    /// 实体宽度，以相邻两根 K 线的间距为 1
+    pub width: f64,
     pub min: f64,
     pub block_bottom: f64,
     pub block_top: f64,
@@ -27,8 +30,12 @@ pub struct Kblock {
     pub color: Color,
 }
 
+/// 画布绘制时 K 线实体默认占相邻 K 线间距的比例
+pub const DEFAULT_CANDLE_WIDTH: f64 = 0.2;
+
 impl Shape for Kblock {
     fn draw(&self, painter: &mut Painter) {
+        let (left, right) = (self.x - self.width / 2.0, self.x + self.width / 2.0);
         let lines: [Line; 6] = [
             // 下影线
             Line {
@@ -39,30 +46,30 @@ impl Shape for Kblock {
                 color: self.color,
             },
             Line {
-                x1: self.x - 0.1,
+                x1: left,
                 y1: self.block_bottom,
-                x2: self.x + 0.1,
+                x2: right,
                 y2: self.block_bottom,
                 color: self.color,
             },
             Line {
-                x1: self.x - 0.1,
+                x1: left,
                 y1: self.block_bottom,
-                x2: self.x - 0.1,
+                x2: left,
                 y2: self.block_top,
                 color: self.color,
             },
             Line {
-                x1: self.x + 0.1,
+                x1: right,
                 y1: self.block_bottom,
-                x2: self.x + 0.1,
+                x2: right,
                 y2: self.block_top,
                 color: self.color,
             },
             Line {
-                x1: self.x - 0.1,
+                x1: left,
                 y1: self.block_top,
-                x2: self.x + 0.1,
+                x2: right,
                 y2: self.block_top,
                 color: self.color,
             },
@@ -80,10 +87,10 @@ impl Shape for Kblock {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct KlineData<'a> {
     // "2023-04-07"
-    pub date: &'a str,
+    pub date: Cow<'a, str>,
     // 0.904
     pub pre_close: f64,
     // 0.902
@@ -116,30 +123,198 @@ pub struct KlineData<'a> {
     pub avg_q10: f64,
 }
 
+/// 价格图的绘制方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChartType {
+    /// 空心 K 线（默认）
+    #[default]
+    Candlestick,
+    /// 平均 K 线，由传入的 OHLC 序列计算得出；只传入可见部分时数值随窗口变化，
+    /// 此时应先对全部数据调用 [`heikin_ashi`] 再截取，以 `Candlestick` 绘制
+    HeikinAshi,
+    /// 美国线：竖线表示最高最低，左侧短线为开盘价，右侧短线为收盘价
+    Ohlc,
+    /// 收盘价折线
+    Line,
+    /// 收盘价面积图
+    Area,
+}
+
+impl ChartType {
+    /// 依次切换到下一种绘制方式
+    pub fn next(self) -> ChartType {
+        match self {
+            ChartType::Candlestick => ChartType::HeikinAshi,
+            ChartType::HeikinAshi => ChartType::Ohlc,
+            ChartType::Ohlc => ChartType::Line,
+            ChartType::Line => ChartType::Area,
+            ChartType::Area => ChartType::Candlestick,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChartType::Candlestick => "K 线",
+            ChartType::HeikinAshi => "平均K线",
+            ChartType::Ohlc => "美国线",
+            ChartType::Line => "收盘线",
+            ChartType::Area => "面积图",
+        }
+    }
+}
+
+/// 由 OHLC 序列计算平均 K 线（Heikin-Ashi）。
+///
+/// 计算依赖时间先后，数据可以按日期升序或降序排列，返回值与输入顺序一致。
+pub fn heikin_ashi<'a>(datas: &[KlineData<'a>]) -> Vec<KlineData<'a>> {
+    let newest_first = match (datas.first(), datas.last()) {
+        (Some(first), Some(last)) => first.date > last.date,
+        _ => false,
+    };
+    let mut result: Vec<KlineData<'a>> = Vec::with_capacity(datas.len());
+    let mut prev: Option<(f64, f64)> = None;
+    let mut push = |k_line_data: &KlineData<'a>| {
+        let close = (k_line_data.start + k_line_data.end + k_line_data.min + k_line_data.max) / 4.0;
+        let open = match prev {
+            Some((prev_open, prev_close)) => (prev_open + prev_close) / 2.0,
+            None => (k_line_data.start + k_line_data.end) / 2.0,
+        };
+        prev = Some((open, close));
+        let mut ha = k_line_data.clone();
+        ha.start = open;
+        ha.end = close;
+        ha.max = k_line_data.max.max(open).max(close);
+        ha.min = k_line_data.min.min(open).min(close);
+        result.push(ha);
+    };
+    if newest_first {
+        datas.iter().rev().for_each(&mut push);
+        result.reverse();
+    } else {
+        datas.iter().for_each(&mut push);
+    }
+    result
+}
+
 pub struct KlineDatas<'a> {
     pub coords: &'a [KlineData<'a>],
+    pub chart_type: ChartType,
+    /// 折线、面积图使用的颜色
+    pub color: Color,
+    /// 面积图填充的下边界，一般为 y 轴的最小值
+    pub baseline: f64,
+    /// 上涨与下跌 K 线的颜色
+    pub up_color: Color,
+    pub down_color: Color,
+    /// K 线实体与美国线开收盘短线的宽度，以相邻 K 线的间距为 1
+    pub candle_width: f64,
 }
 
-impl<'a> Shape for KlineDatas<'a> {
-    fn draw(&self, painter: &mut Painter) {
-        for (x, k_line_data) in self.coords.iter().enumerate() {
-            let mut block_bottom = k_line_data.start;
-            let mut block_top = k_line_data.end;
-            let mut color = Color::Red;
-            if k_line_data.start > k_line_data.end {
-                block_bottom = k_line_data.end;
-                block_top = k_line_data.start;
-                color = Color::Green;
-            }
+impl<'a> KlineDatas<'a> {
+    fn direction_color(&self, k_line_data: &KlineData) -> Color {
+        if k_line_data.start > k_line_data.end {
+            self.down_color
+        } else {
+            self.up_color
+        }
+    }
+
+    fn draw_candles(&self, coords: &[KlineData], painter: &mut Painter) {
+        for (x, k_line_data) in coords.iter().enumerate() {
             let kblock = Kblock {
                 x: x as f64,
+                width: self.candle_width,
                 min: k_line_data.min,
-                block_bottom: block_bottom,
-                block_top: block_top,
+                block_bottom: k_line_data.start.min(k_line_data.end),
+                block_top: k_line_data.start.max(k_line_data.end),
                 max: k_line_data.max,
-                color: color,
+                color: self.direction_color(k_line_data),
             };
             kblock.draw(painter);
         }
     }
+
+    fn draw_ohlc(&self, painter: &mut Painter) {
+        for (x, k_line_data) in self.coords.iter().enumerate() {
+            let x = x as f64;
+            let tick = self.candle_width / 2.0;
+            let color = self.direction_color(k_line_data);
+            let lines = [
+                Line {
+                    x1: x,
+                    y1: k_line_data.min,
+                    x2: x,
+                    y2: k_line_data.max,
+                    color,
+                },
+                Line {
+                    x1: x - tick,
+                    y1: k_line_data.start,
+                    x2: x,
+                    y2: k_line_data.start,
+                    color,
+                },
+                Line {
+                    x1: x,
+                    y1: k_line_data.end,
+                    x2: x + tick,
+                    y2: k_line_data.end,
+                    color,
+                },
+            ];
+            for line in &lines {
+                line.draw(painter);
+            }
+        }
+    }
+
+    fn draw_line(&self, painter: &mut Painter) {
+        for (x, pair) in self.coords.windows(2).enumerate() {
+            Line {
+                x1: x as f64,
+                y1: pair[0].end,
+                x2: (x + 1) as f64,
+                y2: pair[1].end,
+                color: self.color,
+            }
+            .draw(painter);
+        }
+    }
+
+    /// 在画布的网格上逐列填充收盘价与基线之间的区域
+    fn draw_area(&self, painter: &mut Painter) {
+        for (x, pair) in self.coords.windows(2).enumerate() {
+            let (x1, y1) = (x as f64, pair[0].end);
+            let (x2, y2) = ((x + 1) as f64, pair[1].end);
+            let (start, end) = match (painter.get_point(x1, y1), painter.get_point(x2, y2)) {
+                (Some(start), Some(end)) => (start, end),
+                _ => continue,
+            };
+            let bottom = match painter.get_point(x1, self.baseline) {
+                Some((_, bottom)) => bottom,
+                None => continue,
+            };
+            let columns = end.0.saturating_sub(start.0).max(1);
+            for column in start.0..=end.0 {
+                let ratio = (column - start.0) as f64 / columns as f64;
+                let top = start.1 as f64 + (end.1 as f64 - start.1 as f64) * ratio;
+                for row in top.round() as usize..=bottom {
+                    painter.paint(column, row, self.color);
+                }
+            }
+        }
+        self.draw_line(painter);
+    }
+}
+
+impl<'a> Shape for KlineDatas<'a> {
+    fn draw(&self, painter: &mut Painter) {
+        match self.chart_type {
+            ChartType::Candlestick => self.draw_candles(self.coords, painter),
+            ChartType::HeikinAshi => self.draw_candles(&heikin_ashi(self.coords), painter),
+            ChartType::Ohlc => self.draw_ohlc(painter),
+            ChartType::Line => self.draw_line(painter),
+            ChartType::Area => self.draw_area(painter),
+        }
+    }
 }
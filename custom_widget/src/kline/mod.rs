@@ -1,2 +1,5 @@
-pub mod klinedata;
+pub mod annotation;
+pub mod cell;
+#[allow(clippy::module_inception)]
 pub mod kline;
+pub mod klinedata;
@@ -1 +1,38 @@
+//! 终端 K 线图控件，基于 [`tui`] 0.19。
+//!
+//! [`KLine`] 绘制坐标轴、标签、图例与一个或多个 [`Dataset`]，支持空心 K 线、平均 K 线、
+//! 美国线、收盘线与面积图（[`ChartType`]），可以通过画布或直接写入单元格绘制（[`Renderer`]），
+//! 并在 K 线之上叠加价位线、趋势线等 [`Annotation`]。
+//!
+//! ```
+//! use custom_widget::{Axis, Dataset, KLine, KlineData, LegendPosition};
+//! use tui::{buffer::Buffer, layout::Rect, style::Color, widgets::Widget};
+//!
+//! let datas = vec![
+//!     KlineData { date: "2023-04-06".into(), start: 0.919, end: 0.904, min: 0.901, max: 0.924, ..Default::default() },
+//!     KlineData { date: "2023-04-07".into(), start: 0.902, end: 0.900, min: 0.894, max: 0.912, ..Default::default() },
+//! ];
+//! let kline = KLine::new(vec![Dataset::default()
+//!     .name("512690")
+//!     .candle_colors(Color::Red, Color::Green)
+//!     .candle_width(0.6)
+//!     .data(&datas)])
+//!     .legend_position(Some(LegendPosition::TopLeft))
+//!     .x_axis(Axis::default().bounds([-0.5, 1.5]))
+//!     .y_axis(Axis::default().bounds([0.894, 0.924]));
+//!
+//! let area = Rect::new(0, 0, 40, 10);
+//! let mut buf = Buffer::empty(area);
+//! kline.render(area, &mut buf);
+//! ```
+//!
+//! 运行 `cargo run -p custom_widget --example candlestick` 查看交互示例。
+
 pub mod kline;
+
+pub use kline::annotation::{Annotation, FIBONACCI_RATIOS};
+pub use kline::cell::CellCandles;
+pub use kline::kline::{padded_bounds, Axis, Dataset, KLine, LegendPosition, Renderer};
+pub use kline::klinedata::{
+    heikin_ashi, ChartType, Kblock, KlineData, KlineDatas, DEFAULT_CANDLE_WIDTH,
+};
@@ -0,0 +1,253 @@
+use custom_widget::{Axis, ChartType, Dataset, KLine, KlineData, LegendPosition, Renderer};
+use std::borrow::Cow;
+use tui::buffer::Buffer;
+use tui::layout::Rect;
+use tui::symbols;
+use tui::text::Span;
+use tui::widgets::{Block, Borders, Widget};
+use unicode_width::UnicodeWidthStr;
+
+const RENDERERS: [(&str, Renderer); 2] = [("canvas", Renderer::Canvas), ("cell", Renderer::Cell)];
+
+fn bar(date: &'static str, start: f64, end: f64, min: f64, max: f64) -> KlineData<'static> {
+    KlineData {
+        date: Cow::Borrowed(date),
+        start,
+        end,
+        min,
+        max,
+        ..KlineData::default()
+    }
+}
+
+/// 程序内置的 `K_LINE_DATAS` 中最近的 9 根 k 线，最新的在前
+fn sample() -> Vec<KlineData<'static>> {
+    vec![
+        bar("2023-04-07", 0.902, 0.900, 0.894, 0.912),
+        bar("2023-04-06", 0.919, 0.904, 0.901, 0.924),
+        bar("2023-04-04", 0.917, 0.925, 0.908, 0.927),
+        bar("2023-04-03", 0.919, 0.918, 0.908, 0.926),
+        bar("2023-03-31", 0.927, 0.920, 0.919, 0.939),
+        bar("2023-03-30", 0.911, 0.924, 0.905, 0.926),
+        bar("2023-03-29", 0.908, 0.912, 0.898, 0.914),
+        bar("2023-03-28", 0.886, 0.903, 0.886, 0.906),
+        bar("2023-03-27", 0.894, 0.884, 0.880, 0.895),
+    ]
+}
+
+fn y_bounds(datas: &[KlineData]) -> [f64; 2] {
+    datas.iter().fold([f64::MAX, f64::MIN], |[min, max], data| {
+        [min.min(data.min), max.max(data.max)]
+    })
+}
+
+/// 与程序中的 K 线图相同的配置：三个 x 轴标签、三个 y 轴标签
+fn chart<'a>(datas: &'a [KlineData<'a>], y_bounds: [f64; 2], renderer: Renderer) -> KLine<'a> {
+    let dates = match (datas.first(), datas.last()) {
+        (Some(first), Some(last)) => vec![
+            Span::raw(first.date.as_ref()),
+            Span::raw(datas[datas.len() / 2].date.as_ref()),
+            Span::raw(last.date.as_ref()),
+        ],
+        _ => vec![],
+    };
+    let prices = vec![
+        Span::raw(format!("{:.3}", y_bounds[0])),
+        Span::raw(format!("{:.3}", (y_bounds[0] + y_bounds[1]) / 2.0)),
+        Span::raw(format!("{:.3}", y_bounds[1])),
+    ];
+    let dataset = Dataset::default()
+        .name("K 线图")
+        .marker(symbols::Marker::Braille)
+        .chart_type(ChartType::Candlestick)
+        .data(datas);
+    KLine::new(vec![dataset])
+        .renderer(renderer)
+        .block(Block::default().title("512690").borders(Borders::ALL))
+        .x_axis(
+            Axis::default()
+                .bounds([-0.5, datas.len() as f64 - 0.5])
+                .labels(dates),
+        )
+        .y_axis(Axis::default().bounds(y_bounds).labels(prices))
+}
+
+/// 绘制到 `width`×`height` 的缓冲区，返回每行的字符，宽字符后被占用的单元格跳过
+fn render(kline: KLine, width: u16, height: u16) -> String {
+    let area = Rect::new(0, 0, width, height);
+    let mut buf = Buffer::empty(area);
+    kline.render(area, &mut buf);
+    let mut lines = Vec::new();
+    for y in 0..height {
+        let mut line = String::new();
+        let mut x = 0;
+        while x < width {
+            let symbol = &buf.get(x, y).symbol;
+            line.push_str(symbol);
+            x += symbol.width().max(1) as u16;
+        }
+        lines.push(line);
+    }
+    lines.join("\n")
+}
+
+#[test]
+fn candlestick_canvas() {
+    let datas = sample();
+    let kline = chart(&datas, y_bounds(&datas), Renderer::Canvas);
+    insta::assert_snapshot!(render(kline, 60, 16));
+}
+
+#[test]
+fn candlestick_cell() {
+    let datas = sample();
+    let kline = chart(&datas, y_bounds(&datas), Renderer::Cell);
+    insta::assert_snapshot!(render(kline, 60, 16));
+}
+
+#[test]
+fn line_and_area() {
+    let datas = sample();
+    for chart_type in [ChartType::Line, ChartType::Area] {
+        let dataset = Dataset::default()
+            .marker(symbols::Marker::Braille)
+            .chart_type(chart_type)
+            .data(&datas);
+        let kline = KLine::new(vec![dataset])
+            .x_axis(Axis::default().bounds([-0.5, datas.len() as f64 - 0.5]))
+            .y_axis(Axis::default().bounds(y_bounds(&datas)));
+        insta::assert_snapshot!(
+            format!("{:?}", chart_type).to_lowercase(),
+            render(kline, 30, 8)
+        );
+    }
+}
+
+#[test]
+fn empty_data() {
+    let kline = chart(&[], [0.0, 0.0], Renderer::Canvas);
+    insta::assert_snapshot!(render(kline, 40, 10));
+}
+
+#[test]
+fn empty_datasets() {
+    let kline = KLine::new(vec![]).block(Block::default().borders(Borders::ALL));
+    insta::assert_snapshot!(render(kline, 20, 5));
+}
+
+#[test]
+fn single_bar() {
+    let datas = vec![bar("2023-04-07", 0.902, 0.900, 0.894, 0.912)];
+    for (name, renderer) in RENDERERS {
+        let kline = chart(&datas, y_bounds(&datas), renderer);
+        insta::assert_snapshot!(format!("single_bar_{}", name), render(kline, 40, 12));
+    }
+}
+
+#[test]
+fn doji_bars() {
+    let datas = vec![
+        bar("2023-04-03", 0.930, 0.930, 0.920, 0.940),
+        bar("2023-04-04", 0.930, 0.930, 0.930, 0.940),
+        bar("2023-04-06", 0.930, 0.930, 0.920, 0.930),
+        bar("2023-04-07", 0.930, 0.930, 0.930, 0.930),
+    ];
+    for (name, renderer) in RENDERERS {
+        let kline = chart(&datas, y_bounds(&datas), renderer);
+        insta::assert_snapshot!(format!("doji_bars_{}", name), render(kline, 40, 12));
+    }
+}
+
+/// 停牌等情况下所有价格相同，y 轴范围的最小值等于最大值
+#[test]
+fn flat_series() {
+    let datas = vec![
+        bar("2023-04-04", 0.9, 0.9, 0.9, 0.9),
+        bar("2023-04-06", 0.9, 0.9, 0.9, 0.9),
+        bar("2023-04-07", 0.9, 0.9, 0.9, 0.9),
+    ];
+    let bounds = y_bounds(&datas);
+    assert_eq!(bounds[0], bounds[1]);
+    for (name, renderer) in RENDERERS {
+        let kline = chart(&datas, bounds, renderer);
+        insta::assert_snapshot!(format!("flat_series_{}", name), render(kline, 40, 12));
+    }
+}
+
+#[test]
+fn single_labels() {
+    let datas = sample();
+    let dataset = Dataset::default()
+        .marker(symbols::Marker::Braille)
+        .chart_type(ChartType::Candlestick)
+        .data(&datas);
+    let kline = KLine::new(vec![dataset])
+        .x_axis(
+            Axis::default()
+                .bounds([-0.5, datas.len() as f64 - 0.5])
+                .labels(vec![Span::raw("2023-04-07")]),
+        )
+        .y_axis(
+            Axis::default()
+                .bounds(y_bounds(&datas))
+                .labels(vec![Span::raw("0.880")]),
+        );
+    insta::assert_snapshot!(render(kline, 40, 10));
+}
+
+#[test]
+fn no_data_text() {
+    let kline = KLine::new(vec![Dataset::default()]).no_data("No data");
+    insta::assert_snapshot!(render(kline, 20, 3));
+}
+
+#[test]
+fn candle_width() {
+    let datas = sample();
+    for (name, renderer) in RENDERERS {
+        let mut snapshot = String::new();
+        for ratio in [0.0, 0.5, 1.0] {
+            let dataset = Dataset::default()
+                .marker(symbols::Marker::Braille)
+                .candle_width(ratio)
+                .data(&datas);
+            let kline = KLine::new(vec![dataset])
+                .renderer(renderer)
+                .x_axis(Axis::default().bounds([-0.5, datas.len() as f64 - 0.5]))
+                .y_axis(Axis::default().bounds(y_bounds(&datas)));
+            snapshot.push_str(&format!("{}\n{}\n", ratio, render(kline, 45, 8)));
+        }
+        insta::assert_snapshot!(format!("candle_width_{}", name), snapshot);
+    }
+}
+
+#[test]
+fn legend_positions() {
+    let datas = sample();
+    let mut snapshot = String::new();
+    for position in [
+        Some(LegendPosition::TopLeft),
+        Some(LegendPosition::BottomLeft),
+        Some(LegendPosition::BottomRight),
+        None,
+    ] {
+        let kline = chart(&datas, y_bounds(&datas), Renderer::Cell).legend_position(position);
+        snapshot.push_str(&format!("{:?}\n{}\n", position, render(kline, 60, 20)));
+    }
+    insta::assert_snapshot!(snapshot);
+}
+
+#[test]
+fn tiny_areas() {
+    let datas = sample();
+    let mut snapshot = String::new();
+    for (width, height) in [(1, 1), (2, 2), (4, 3), (8, 4), (12, 5), (16, 6)] {
+        for (name, renderer) in RENDERERS {
+            let kline = chart(&datas, y_bounds(&datas), renderer);
+            snapshot.push_str(&format!("{}x{} {}\n", width, height, name));
+            snapshot.push_str(&render(kline, width, height));
+            snapshot.push('\n');
+        }
+    }
+    insta::assert_snapshot!(snapshot);
+}
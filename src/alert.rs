@@ -0,0 +1,278 @@
+use crate::{model::StockData, storage, watchlist::Quote};
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+use std::{
+    io,
+    path::PathBuf,
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// 通知横幅显示的时长
+const BANNER_DURATION: Duration = Duration::from_secs(10);
+
+/// 提醒日志保留的条数
+const MAX_LOG_LEN: usize = 200;
+
+/// 提醒条件，在条件由不满足变为满足时触发
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Condition {
+    /// 价格向上突破
+    PriceAbove { price: f64 },
+    /// 价格向下跌破
+    PriceBelow { price: f64 },
+    /// 涨跌幅绝对值超过阈值（%）
+    ChangeAbove { percent: f64 },
+    /// 成交量超过 5 日均量的 `ratio` 倍
+    VolumeSpike { ratio: f64 },
+    /// MACD 金叉：dif 上穿 dea
+    MacdGoldenCross,
+    /// MACD 死叉：dif 下穿 dea
+    MacdDeathCross,
+    /// 主力净流入超过 `amount`
+    MainInflowAbove { amount: f64 },
+}
+
+impl Condition {
+    /// 条件是否满足，数据不足时返回 `None`
+    fn check(&self, stock_data: &StockData) -> Option<bool> {
+        let quote = Quote::from_stock_data(stock_data)?;
+        let result = match self {
+            Condition::PriceAbove { price } => quote.price > *price,
+            Condition::PriceBelow { price } => quote.price < *price,
+            Condition::ChangeAbove { percent } => quote.change.abs() >= *percent,
+            Condition::VolumeSpike { ratio } => {
+                let mut k_line_datas = stock_data.get_k_line_datas();
+                k_line_datas.sort_by(|a, b| a.date.cmp(&b.date));
+                let latest = k_line_datas.last()?;
+                latest.avg_q5 > 0.0 && latest.quantity as f64 >= latest.avg_q5 * ratio
+            }
+            Condition::MacdGoldenCross => macd_crossed(&stock_data.get_macd_series(), true)?,
+            Condition::MacdDeathCross => macd_crossed(&stock_data.get_macd_series(), false)?,
+            Condition::MainInflowAbove { amount } => quote.main_inflow? > *amount,
+        };
+        Some(result)
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Condition::PriceAbove { price } => format!("价格突破 {:.3}", price),
+            Condition::PriceBelow { price } => format!("价格跌破 {:.3}", price),
+            Condition::ChangeAbove { percent } => format!("涨跌幅超过 {:.2}%", percent),
+            Condition::VolumeSpike { ratio } => format!("成交量超过5日均量 {:.1} 倍", ratio),
+            Condition::MacdGoldenCross => String::from("MACD 金叉"),
+            Condition::MacdDeathCross => String::from("MACD 死叉"),
+            Condition::MainInflowAbove { amount } => format!("主力净流入超过 {}", amount),
+        }
+    }
+}
+
+/// 最近一根 K 线上 dif 是否穿过 dea，`upward` 为 `true` 时检查上穿，不足两根时返回 `None`
+fn macd_crossed(series: &[(f64, f64)], upward: bool) -> Option<bool> {
+    let (dif, dea) = *series.last()?;
+    let (prev_dif, prev_dea) = *series.get(series.len().checked_sub(2)?)?;
+    let crossed = if upward {
+        prev_dif <= prev_dea && dif > dea
+    } else {
+        prev_dif >= prev_dea && dif < dea
+    };
+    Some(crossed)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AlertRule {
+    pub stock_code: String,
+    pub condition: Condition,
+    /// 触发时执行的本地命令，通过环境变量 `STOCK_CODE`、`STOCK_ALERT` 获取提醒内容
+    #[serde(default)]
+    pub command: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AlertEvent {
+    pub time: DateTime<FixedOffset>,
+    pub stock_code: String,
+    pub message: String,
+}
+
+/// 提醒引擎：每次刷新数据后检查规则，条件由不满足变为满足时产生提醒。
+/// 每条规则第一次检查只记录结果不触发，重启后已经满足的条件不会再次提醒
+#[derive(Debug, Default)]
+pub struct AlertEngine {
+    pub rules: Vec<AlertRule>,
+    /// 每条规则上次检查的结果，`None` 表示还没有检查过
+    states: Vec<Option<bool>>,
+    pub log: Vec<AlertEvent>,
+    banner: Option<(usize, Instant)>,
+}
+
+impl AlertEngine {
+    fn path() -> PathBuf {
+        storage::data_dir().join("alerts.json")
+    }
+
+    pub fn load() -> io::Result<AlertEngine> {
+        let rules: Vec<AlertRule> = storage::load_json(&Self::path())?.unwrap_or_default();
+        Ok(AlertEngine {
+            states: vec![None; rules.len()],
+            rules,
+            ..AlertEngine::default()
+        })
+    }
+
+    /// 检查 `stock_code` 的全部规则，返回新触发的提醒
+    pub fn evaluate(
+        &mut self,
+        stock_code: &str,
+        stock_data: &StockData,
+        now: DateTime<FixedOffset>,
+    ) -> Vec<AlertEvent> {
+        let mut events = Vec::new();
+        for (rule, state) in self.rules.iter().zip(self.states.iter_mut()) {
+            if rule.stock_code != stock_code {
+                continue;
+            }
+            let matched = match rule.condition.check(stock_data) {
+                Some(matched) => matched,
+                None => continue,
+            };
+            let triggered = matched && *state == Some(false);
+            *state = Some(matched);
+            if !triggered {
+                continue;
+            }
+            let event = AlertEvent {
+                time: now,
+                stock_code: stock_code.to_string(),
+                message: rule.condition.describe(),
+            };
+            if let Some(ref command) = rule.command {
+                run_command(command, &event);
+            }
+            events.push(event);
+        }
+        for event in &events {
+            self.log.push(event.clone());
+            self.banner = Some((self.log.len() - 1, Instant::now()));
+        }
+        if self.log.len() > MAX_LOG_LEN {
+            let overflow = self.log.len() - MAX_LOG_LEN;
+            self.log.drain(..overflow);
+            self.banner = self
+                .banner
+                .and_then(|(i, shown_at)| Some((i.checked_sub(overflow)?, shown_at)));
+        }
+        events
+    }
+
+    /// 正在显示的通知横幅
+    pub fn banner(&self) -> Option<&AlertEvent> {
+        match self.banner {
+            Some((i, shown_at)) if shown_at.elapsed() < BANNER_DURATION => self.log.get(i),
+            _ => None,
+        }
+    }
+
+    pub fn dismiss_banner(&mut self) {
+        self.banner = None;
+    }
+}
+
+/// 在后台线程中执行用户配置的命令，不阻塞界面。
+/// 命令的输出写入日志而不是终端，避免破坏界面
+fn run_command(command: &str, event: &AlertEvent) {
+    let command = command.to_string();
+    let stock_code = event.stock_code.clone();
+    let message = event.message.clone();
+    thread::spawn(move || {
+        let result = Command::new("sh")
+            .arg("-c")
+            .arg(&command)
+            .env("STOCK_CODE", stock_code)
+            .env("STOCK_ALERT", message)
+            .stdin(Stdio::null())
+            .output();
+        let output = match result {
+            Ok(output) => output,
+            Err(err) => {
+                log::error!("run alert command `{}` failed: {}", command, err);
+                return;
+            }
+        };
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if output.status.success() {
+            log::info!(
+                "alert command `{}` finished stdout={:?} stderr={:?}",
+                command,
+                stdout.trim(),
+                stderr.trim()
+            );
+        } else {
+            log::error!(
+                "alert command `{}` exited with {} stdout={:?} stderr={:?}",
+                command,
+                output.status,
+                stdout.trim(),
+                stderr.trim()
+            );
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::decode;
+    use crate::market;
+
+    #[test]
+    fn macd_cross_uses_last_two_bars() {
+        // dif 一直在 dea 之上不是金叉
+        assert_eq!(macd_crossed(&[(1.0, 0.0), (2.0, 0.0)], true), Some(false));
+        assert_eq!(macd_crossed(&[(-1.0, 0.0), (1.0, 0.0)], true), Some(true));
+        assert_eq!(macd_crossed(&[(0.0, 0.0), (1.0, 0.0)], true), Some(true));
+        assert_eq!(macd_crossed(&[(-1.0, 0.0), (1.0, 0.0)], false), Some(false));
+        assert_eq!(macd_crossed(&[(1.0, 0.0), (-1.0, 0.0)], false), Some(true));
+        assert_eq!(
+            macd_crossed(&[(-2.0, 0.0), (-1.0, 0.0)], false),
+            Some(false)
+        );
+        assert_eq!(macd_crossed(&[(1.0, 0.0)], true), None);
+        assert_eq!(macd_crossed(&[], false), None);
+    }
+
+    #[test]
+    fn first_evaluation_does_not_fire() {
+        let wrapper = decode(include_bytes!("../assets/resp.json")).unwrap();
+        let stock_data = wrapper.get_stock_data("day").unwrap();
+        let price = Quote::from_stock_data(stock_data).unwrap().price;
+        let mut engine = AlertEngine {
+            rules: vec![AlertRule {
+                stock_code: String::from("SH512690"),
+                condition: Condition::PriceAbove { price: price - 0.1 },
+                command: None,
+            }],
+            states: vec![None],
+            ..AlertEngine::default()
+        };
+        let now = market::shanghai_now();
+        // 启动时条件已经满足，只记录状态
+        assert!(engine.evaluate("SH512690", stock_data, now).is_empty());
+        assert!(engine.evaluate("SH512690", stock_data, now).is_empty());
+        engine.rules[0].condition = Condition::PriceAbove { price: price + 0.1 };
+        assert!(engine.evaluate("SH512690", stock_data, now).is_empty());
+        // 由不满足变为满足时触发
+        engine.rules[0].condition = Condition::PriceAbove { price: price - 0.1 };
+        let events = engine.evaluate("SH512690", stock_data, now);
+        assert_eq!(events.len(), 1);
+        assert_eq!(engine.log.len(), 1);
+        assert!(engine.banner().is_some());
+        // 其它股票的数据不影响这条规则
+        engine.rules[0].condition = Condition::PriceAbove { price: price + 0.1 };
+        assert!(engine.evaluate("SZ159915", stock_data, now).is_empty());
+        assert_eq!(engine.states, [Some(true)]);
+    }
+}
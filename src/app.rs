@@ -1,11 +1,36 @@
-use crate::{list::StatefulList, model::StockDataWrapper, net};
-use custom_widget::kline::klinedata::KlineData;
-use std::error::Error;
-use std::process::exit;
+use crate::{
+    alert::AlertEngine,
+    chart::ChartView,
+    compare::{self, CompareLine},
+    config::Config,
+    drawing::{Anchor, Drawing, DrawingTool, Drawings, Handle},
+    export::{self, Format},
+    import::ImportedSeries,
+    keymap::{Action, Key, Keymap, PendingKeys},
+    list::StatefulList,
+    market::{self, MarketCalendar, MarketStatus},
+    model::{StockData, StockDataWrapper, Szzs},
+    net::{self, FetchError, FetchResult},
+    portfolio::{Portfolio, Side, Transaction},
+    refresh::RefreshScheduler,
+    storage,
+    tab::Tab,
+    theme::Theme,
+    watchlist::{Column, Quote, WatchlistConfig},
+};
+use chrono::{DateTime, FixedOffset};
+use crossterm::event::{KeyCode, KeyModifiers};
+use custom_widget::kline::kline::{padded_bounds, Renderer};
+use custom_widget::kline::klinedata::{heikin_ashi, ChartType, KlineData};
+use std::borrow::Cow;
+use std::ops::Range;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Duration, Instant};
+use tui::{layout::Rect, widgets::TableState};
 
 const K_LINE_DATAS: [KlineData; 30] = [
     KlineData {
-        date: "2023-04-07",
+        date: Cow::Borrowed("2023-04-07"),
         pre_close: 0.904,
         start: 0.902,
         end: 0.9,
@@ -23,7 +48,7 @@ const K_LINE_DATAS: [KlineData; 30] = [
         avg_q10: 7745399.3,
     },
     KlineData {
-        date: "2023-04-06",
+        date: Cow::Borrowed("2023-04-06"),
         pre_close: 0.925,
         start: 0.919,
         end: 0.904,
@@ -41,7 +66,7 @@ const K_LINE_DATAS: [KlineData; 30] = [
         avg_q10: 7741763.7,
     },
     KlineData {
-        date: "2023-04-04",
+        date: Cow::Borrowed("2023-04-04"),
         pre_close: 0.918,
         start: 0.917,
         end: 0.925,
@@ -59,7 +84,7 @@ const K_LINE_DATAS: [KlineData; 30] = [
         avg_q10: 7349595.0,
     },
     KlineData {
-        date: "2023-04-03",
+        date: Cow::Borrowed("2023-04-03"),
         pre_close: 0.92,
         start: 0.919,
         end: 0.918,
@@ -77,7 +102,7 @@ const K_LINE_DATAS: [KlineData; 30] = [
         avg_q10: 7143790.0,
     },
     KlineData {
-        date: "2023-03-31",
+        date: Cow::Borrowed("2023-03-31"),
         pre_close: 0.924,
         start: 0.927,
         end: 0.92,
@@ -95,7 +120,7 @@ const K_LINE_DATAS: [KlineData; 30] = [
         avg_q10: 6914536.8,
     },
     KlineData {
-        date: "2023-03-30",
+        date: Cow::Borrowed("2023-03-30"),
         pre_close: 0.912,
         start: 0.911,
         end: 0.924,
@@ -113,7 +138,7 @@ const K_LINE_DATAS: [KlineData; 30] = [
         avg_q10: 6888259.8,
     },
     KlineData {
-        date: "2023-03-29",
+        date: Cow::Borrowed("2023-03-29"),
         pre_close: 0.903,
         start: 0.908,
         end: 0.912,
@@ -131,7 +156,7 @@ const K_LINE_DATAS: [KlineData; 30] = [
         avg_q10: 6280812.7,
     },
     KlineData {
-        date: "2023-03-28",
+        date: Cow::Borrowed("2023-03-28"),
         pre_close: 0.884,
         start: 0.886,
         end: 0.903,
@@ -149,7 +174,7 @@ const K_LINE_DATAS: [KlineData; 30] = [
         avg_q10: 5766302.5,
     },
     KlineData {
-        date: "2023-03-27",
+        date: Cow::Borrowed("2023-03-27"),
         pre_close: 0.892,
         start: 0.894,
         end: 0.884,
@@ -167,7 +192,7 @@ const K_LINE_DATAS: [KlineData; 30] = [
         avg_q10: 5723955.8,
     },
     KlineData {
-        date: "2023-03-24",
+        date: Cow::Borrowed("2023-03-24"),
         pre_close: 0.892,
         start: 0.892,
         end: 0.892,
@@ -185,7 +210,7 @@ const K_LINE_DATAS: [KlineData; 30] = [
         avg_q10: 5642290.3,
     },
     KlineData {
-        date: "2023-03-23",
+        date: Cow::Borrowed("2023-03-23"),
         pre_close: 0.882,
         start: 0.878,
         end: 0.892,
@@ -203,7 +228,7 @@ const K_LINE_DATAS: [KlineData; 30] = [
         avg_q10: 5778471.4,
     },
     KlineData {
-        date: "2023-03-22",
+        date: Cow::Borrowed("2023-03-22"),
         pre_close: 0.886,
         start: 0.888,
         end: 0.882,
@@ -221,7 +246,7 @@ const K_LINE_DATAS: [KlineData; 30] = [
         avg_q10: 5637894.8,
     },
     KlineData {
-        date: "2023-03-21",
+        date: Cow::Borrowed("2023-03-21"),
         pre_close: 0.857,
         start: 0.861,
         end: 0.886,
@@ -239,7 +264,7 @@ const K_LINE_DATAS: [KlineData; 30] = [
         avg_q10: 5512129.3,
     },
     KlineData {
-        date: "2023-03-20",
+        date: Cow::Borrowed("2023-03-20"),
         pre_close: 0.869,
         start: 0.868,
         end: 0.857,
@@ -257,7 +282,7 @@ const K_LINE_DATAS: [KlineData; 30] = [
         avg_q10: 5363985.6,
     },
     KlineData {
-        date: "2023-03-17",
+        date: Cow::Borrowed("2023-03-17"),
         pre_close: 0.883,
         start: 0.891,
         end: 0.869,
@@ -275,7 +300,7 @@ const K_LINE_DATAS: [KlineData; 30] = [
         avg_q10: 5360378.7,
     },
     KlineData {
-        date: "2023-03-16",
+        date: Cow::Borrowed("2023-03-16"),
         pre_close: 0.885,
         start: 0.881,
         end: 0.883,
@@ -293,7 +318,7 @@ const K_LINE_DATAS: [KlineData; 30] = [
         avg_q10: 5355412.8,
     },
     KlineData {
-        date: "2023-03-15",
+        date: Cow::Borrowed("2023-03-15"),
         pre_close: 0.896,
         start: 0.905,
         end: 0.885,
@@ -311,7 +336,7 @@ const K_LINE_DATAS: [KlineData; 30] = [
         avg_q10: 5355956.4,
     },
     KlineData {
-        date: "2023-03-14",
+        date: Cow::Borrowed("2023-03-14"),
         pre_close: 0.904,
         start: 0.905,
         end: 0.896,
@@ -329,7 +354,7 @@ const K_LINE_DATAS: [KlineData; 30] = [
         avg_q10: 5424816.0,
     },
     KlineData {
-        date: "2023-03-13",
+        date: Cow::Borrowed("2023-03-13"),
         pre_close: 0.886,
         start: 0.888,
         end: 0.904,
@@ -347,7 +372,7 @@ const K_LINE_DATAS: [KlineData; 30] = [
         avg_q10: 5227113.7,
     },
     KlineData {
-        date: "2023-03-10",
+        date: Cow::Borrowed("2023-03-10"),
         pre_close: 0.9,
         start: 0.892,
         end: 0.886,
@@ -365,7 +390,7 @@ const K_LINE_DATAS: [KlineData; 30] = [
         avg_q10: 5186664.2,
     },
     KlineData {
-        date: "2023-03-09",
+        date: Cow::Borrowed("2023-03-09"),
         pre_close: 0.908,
         start: 0.907,
         end: 0.9,
@@ -383,7 +408,7 @@ const K_LINE_DATAS: [KlineData; 30] = [
         avg_q10: 5168248.6,
     },
     KlineData {
-        date: "2023-03-08",
+        date: Cow::Borrowed("2023-03-08"),
         pre_close: 0.913,
         start: 0.908,
         end: 0.908,
@@ -401,7 +426,7 @@ const K_LINE_DATAS: [KlineData; 30] = [
         avg_q10: 5158585.8,
     },
     KlineData {
-        date: "2023-03-07",
+        date: Cow::Borrowed("2023-03-07"),
         pre_close: 0.931,
         start: 0.928,
         end: 0.913,
@@ -419,7 +444,7 @@ const K_LINE_DATAS: [KlineData; 30] = [
         avg_q10: 5180684.2,
     },
     KlineData {
-        date: "2023-03-06",
+        date: Cow::Borrowed("2023-03-06"),
         pre_close: 0.931,
         start: 0.931,
         end: 0.931,
@@ -437,7 +462,7 @@ const K_LINE_DATAS: [KlineData; 30] = [
         avg_q10: 5334560.8,
     },
     KlineData {
-        date: "2023-03-03",
+        date: Cow::Borrowed("2023-03-03"),
         pre_close: 0.938,
         start: 0.942,
         end: 0.931,
@@ -455,7 +480,7 @@ const K_LINE_DATAS: [KlineData; 30] = [
         avg_q10: 5578992.1,
     },
     KlineData {
-        date: "2023-03-02",
+        date: Cow::Borrowed("2023-03-02"),
         pre_close: 0.941,
         start: 0.94,
         end: 0.938,
@@ -473,7 +498,7 @@ const K_LINE_DATAS: [KlineData; 30] = [
         avg_q10: 5807404.7,
     },
     KlineData {
-        date: "2023-03-01",
+        date: Cow::Borrowed("2023-03-01"),
         pre_close: 0.931,
         start: 0.932,
         end: 0.941,
@@ -491,7 +516,7 @@ const K_LINE_DATAS: [KlineData; 30] = [
         avg_q10: 6167042.2,
     },
     KlineData {
-        date: "2023-02-28",
+        date: Cow::Borrowed("2023-02-28"),
         pre_close: 0.932,
         start: 0.934,
         end: 0.932,
@@ -509,7 +534,7 @@ const K_LINE_DATAS: [KlineData; 30] = [
         avg_q10: 6409298.2,
     },
     KlineData {
-        date: "2023-02-27",
+        date: Cow::Borrowed("2023-02-27"),
         pre_close: 0.92,
         start: 0.913,
         end: 0.932,
@@ -527,7 +552,7 @@ const K_LINE_DATAS: [KlineData; 30] = [
         avg_q10: 6416377.8,
     },
     KlineData {
-        date: "2023-02-24",
+        date: Cow::Borrowed("2023-02-24"),
         pre_close: 0.933,
         start: 0.933,
         end: 0.92,
@@ -545,7 +570,7 @@ const K_LINE_DATAS: [KlineData; 30] = [
         avg_q10: 6875704.7,
     },
     // KlineData {
-    //     date: "2023-02-23",
+    //     date: Cow::Borrowed("2023-02-23"),
     //     pre_close: 0.944,
     //     start: 0.944,
     //     end: 0.933,
@@ -563,7 +588,7 @@ const K_LINE_DATAS: [KlineData; 30] = [
     //     avg_q10: 6894120.7,
     // },
     // KlineData {
-    //     date: "2023-02-22",
+    //     date: Cow::Borrowed("2023-02-22"),
     //     pre_close: 0.946,
     //     start: 0.941,
     //     end: 0.944,
@@ -581,7 +606,7 @@ const K_LINE_DATAS: [KlineData; 30] = [
     //     avg_q10: 7324302.6,
     // },
     // KlineData {
-    //     date: "2023-02-21",
+    //     date: Cow::Borrowed("2023-02-21"),
     //     pre_close: 0.96,
     //     start: 0.96,
     //     end: 0.946,
@@ -600,109 +625,1133 @@ const K_LINE_DATAS: [KlineData; 30] = [
     // },
 ];
 
+/// 默认的自选股票
+pub fn default_stock_ids() -> Vec<StockBriefInfo<'static>> {
+    vec![
+        StockBriefInfo::new("酒ETF", "SH512690", 512690),
+        StockBriefInfo::new("新能源车ETF", "SH515030", 515030),
+        StockBriefInfo::new("钢铁ETF", "SH515210", 515210),
+        StockBriefInfo::new("地产ETF", "SZ159707", 159707),
+        StockBriefInfo::new("创业板ETF", "SZ159915", 159915),
+    ]
+}
+
 pub struct StockBriefInfo<'a> {
-    pub stock_name: &'a str,
-    pub stock_code: &'a str,
+    pub stock_name: Cow<'a, str>,
+    pub stock_code: Cow<'a, str>,
+    /// 请求数据使用的编号，导入的数据为负数
     pub stock_code_num: i32,
     pub stock_data: Option<StockDataWrapper>,
+    /// 从 CSV 导入的 k 线，有值时不再请求数据
+    pub imported: Option<Vec<KlineData<'static>>>,
+    /// 是否正在请求数据
+    pub loading: bool,
+    /// 最近一次请求失败的原因
+    pub fetch_error: Option<FetchError>,
+    /// 最近一次成功更新的时间（上海时间）
+    pub updated_at: Option<DateTime<FixedOffset>>,
+}
+
+impl<'a> StockBriefInfo<'a> {
+    pub fn new(
+        stock_name: &'a str,
+        stock_code: &'a str,
+        stock_code_num: i32,
+    ) -> StockBriefInfo<'a> {
+        StockBriefInfo {
+            stock_name: Cow::Borrowed(stock_name),
+            stock_code: Cow::Borrowed(stock_code),
+            stock_code_num,
+            stock_data: None,
+            imported: None,
+            loading: false,
+            fetch_error: None,
+            updated_at: None,
+        }
+    }
+
+    pub fn from_imported(series: &ImportedSeries, stock_code_num: i32) -> StockBriefInfo<'static> {
+        StockBriefInfo {
+            stock_name: Cow::Owned(series.stock_name.clone()),
+            stock_code: Cow::Owned(series.stock_code.clone()),
+            stock_code_num,
+            stock_data: None,
+            imported: Some(series.k_line_datas()),
+            loading: false,
+            fetch_error: None,
+            updated_at: None,
+        }
+    }
+
+    /// 最新行情，尚未加载时返回 `None`
+    pub fn quote(&self) -> Option<Quote> {
+        if let Some(ref imported) = self.imported {
+            return Quote::from_k_line_datas(imported.clone(), None);
+        }
+        Quote::from_stock_data(self.stock_data.as_ref()?.get_stock_data("day")?)
+    }
+
+    /// 日 k 线数据，尚未加载时返回 `None`
+    pub fn day_k_line_datas(&self) -> Option<Vec<KlineData<'static>>> {
+        if let Some(ref imported) = self.imported {
+            return Some(imported.clone());
+        }
+        let stock_data = self.stock_data.as_ref()?.get_stock_data("day")?;
+        Some(stock_data.get_k_line_datas())
+    }
+}
+
+fn contains(area: Rect, column: u16, row: u16) -> bool {
+    area.left() <= column && column < area.right() && area.top() <= row && row < area.bottom()
+}
+
+/// 处理按键后界面要做的事
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    Continue,
+    Quit,
+}
+
+/// 输入框的用途
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputPurpose {
+    /// 光标处的备注
+    Note,
+    /// 当前股票的成交记录
+    Trade(Side),
 }
 
-impl StockBriefInfo<'_> {
-    pub async fn get_stock_datas(&self) -> Result<StockDataWrapper, Box<dyn Error>> {
-        let stock_data = net::get_stock_datas(self.stock_code_num).await?;
-        Ok(stock_data)
+impl InputPurpose {
+    pub fn prompt(&self) -> String {
+        match self {
+            InputPurpose::Note => String::from("备注"),
+            InputPurpose::Trade(side) => format!("{} 数量 价格 [手续费] [日期]", side.name()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Input {
+    pub purpose: InputPurpose,
+    pub text: String,
+    /// 上次提交失败的原因
+    pub error: Option<String>,
+}
+
+impl Input {
+    pub fn new(purpose: InputPurpose) -> Input {
+        Input {
+            purpose,
+            text: String::new(),
+            error: None,
+        }
     }
 }
 
 pub struct App<'a> {
     pub stock_ids: StatefulList<StockBriefInfo<'a>>,
     pub k_line_datas: Vec<KlineData<'a>>,
+    pub chart_type: ChartType,
+    pub renderer: Renderer,
+    pub chart_view: ChartView,
+    pub drawings: Drawings,
+    pub drawing_tool: Option<DrawingTool>,
+    /// 正在输入的内容
+    pub input: Option<Input>,
+    /// 上次绘制时 K 线区域的位置，用于鼠标坐标换算
+    pub chart_area: Rect,
+    /// 上次绘制时自选表格的位置与滚动偏移，用于鼠标点击选择
+    pub list_area: Rect,
+    pub list_offset: usize,
+    /// 鼠标拖动开始时的列与可视窗口起点
+    drag_origin: Option<(u16, usize)>,
+    /// 鼠标拖动选中的画线时点中的部分与上次的价格
+    drawing_drag: Option<(Handle, f64)>,
+    /// 加入对比的股票代码
+    pub compare: Vec<i32>,
+    pub compare_mode: bool,
+    pub watchlist: WatchlistConfig,
+    /// 列选择弹窗中光标所在的列，`None` 表示弹窗关闭
+    pub column_cursor: Option<usize>,
+    pub calendar: MarketCalendar,
+    pub scheduler: RefreshScheduler,
+    pub alerts: AlertEngine,
+    pub show_alert_log: bool,
+    /// 是否显示当前股票数据的检查报告
+    pub show_debug: bool,
+    pub portfolio: Portfolio,
+    /// 当前显示的标签页
+    pub tab: Tab,
+    /// 图表页是否显示侧边的自选列表，宽度不足时也会自动收起
+    pub show_sidebar: bool,
+    /// 资金、持仓、日志视图中选中的行，切换标签页时保留
+    pub flows_state: TableState,
+    pub portfolio_state: TableState,
+    pub log_state: TableState,
+    /// 最近一次操作的结果，显示在标题栏
+    pub status: Option<String>,
+    pub keymap: Keymap,
+    pub theme: Theme,
+    /// 多键序列中已经按下的键
+    pending_keys: PendingKeys,
+    pub show_help: bool,
+    fetch_tx: Sender<FetchResult>,
+    fetch_rx: Receiver<FetchResult>,
 }
 
 impl<'a> App<'a> {
     pub fn new() -> App<'a> {
-        App {
-            stock_ids: StatefulList::with_items(vec![
-                StockBriefInfo {
-                    stock_name: "酒ETF",
-                    stock_code: "SH512690",
-                    stock_code_num: 512690,
-                    stock_data: None,
-                },
-                StockBriefInfo {
-                    stock_name: "新能源车ETF",
-                    stock_code: "SH515030",
-                    stock_code_num: 515030,
-                    stock_data: None,
-                },
-                StockBriefInfo {
-                    stock_name: "钢铁ETF",
-                    stock_code: "SH515210",
-                    stock_code_num: 515210,
-                    stock_data: None,
-                },
-                StockBriefInfo {
-                    stock_name: "地产ETF",
-                    stock_code: "SZ159707",
-                    stock_code_num: 159707,
-                    stock_data: None,
-                },
-                StockBriefInfo {
-                    stock_name: "创业板ETF",
-                    stock_code: "SZ159915",
-                    stock_code_num: 159915,
-                    stock_data: None,
-                },
-            ]),
+        let (fetch_tx, fetch_rx) = mpsc::channel();
+        let config = Config::load().unwrap_or_else(|err| {
+            log::error!("load config failed: {}", err);
+            Config::default()
+        });
+        let mut stock_ids = default_stock_ids();
+        let imported = ImportedSeries::load_all().unwrap_or_else(|err| {
+            log::error!("load imported series failed: {}", err);
+            Vec::new()
+        });
+        for (i, series) in imported.iter().enumerate() {
+            stock_ids.push(StockBriefInfo::from_imported(series, -(i as i32) - 1));
+        }
+        let mut app = App {
+            stock_ids: StatefulList::with_items(stock_ids),
             k_line_datas: K_LINE_DATAS.to_vec(),
+            chart_type: ChartType::default(),
+            renderer: Renderer::default(),
+            chart_view: ChartView::default(),
+            drawings: Drawings::default(),
+            drawing_tool: None,
+            input: None,
+            chart_area: Rect::default(),
+            list_area: Rect::default(),
+            list_offset: 0,
+            drag_origin: None,
+            drawing_drag: None,
+            compare: Vec::new(),
+            compare_mode: false,
+            watchlist: WatchlistConfig::load().unwrap_or_else(|err| {
+                log::error!("load watchlist config failed: {}", err);
+                WatchlistConfig::default()
+            }),
+            column_cursor: None,
+            calendar: MarketCalendar::new(&config.holidays),
+            scheduler: RefreshScheduler::new(
+                Duration::from_secs(config.refresh_interval),
+                Duration::from_secs(config.closed_refresh_interval),
+            ),
+            alerts: AlertEngine::load().unwrap_or_else(|err| {
+                log::error!("load alert rules failed: {}", err);
+                AlertEngine::default()
+            }),
+            show_alert_log: false,
+            show_debug: false,
+            portfolio: Portfolio::load().unwrap_or_else(|err| {
+                log::error!("load portfolio failed: {}", err);
+                Portfolio::default()
+            }),
+            tab: Tab::default(),
+            show_sidebar: true,
+            flows_state: TableState::default(),
+            portfolio_state: TableState::default(),
+            log_state: TableState::default(),
+            status: None,
+            keymap: Keymap::load().unwrap_or_else(|err| {
+                log::error!("load keymap failed: {}", err);
+                Keymap::default()
+            }),
+            theme: Theme::load().unwrap_or_else(|err| {
+                log::error!("load theme failed: {}", err);
+                Theme::default()
+            }),
+            pending_keys: PendingKeys::default(),
+            show_help: false,
+            fetch_tx,
+            fetch_rx,
+        };
+        app.on_select();
+        app
+    }
+
+    /// 处理一次按键：输入框打开时编辑文字，否则按键位映射执行操作
+    pub fn on_key(&mut self, key: Key) -> Control {
+        if self.input.is_some() {
+            match key.code {
+                KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    if let Some(input) = self.input.as_mut() {
+                        input.text.push(c);
+                    }
+                }
+                KeyCode::Backspace => self.on_backspace(),
+                KeyCode::Enter => self.on_enter(),
+                KeyCode::Esc => self.on_esc(),
+                _ => {}
+            }
+            return Control::Continue;
+        }
+        match self
+            .keymap
+            .resolve(&mut self.pending_keys, key, Instant::now())
+        {
+            Some(action) => self.on_action(action),
+            None => Control::Continue,
         }
     }
 
-    pub fn on_key(&mut self, i: char) {
-        match i {
-            'i' => {
-                // input
-                log::info!("input")
+    pub fn on_action(&mut self, action: Action) -> Control {
+        if self.column_cursor.is_some() && self.on_column_action(action) {
+            return Control::Continue;
+        }
+        match action {
+            Action::SelectNext => {
+                self.select_row(|selected, len| selected.map_or(0, |i| (i + 1) % len))
+            }
+            Action::SelectPrevious => {
+                self.select_row(|selected, len| selected.map_or(0, |i| (i + len - 1) % len))
+            }
+            Action::SelectFirst => self.select_row(|_, _| 0),
+            Action::SelectLast => self.select_row(|_, len| len - 1),
+            Action::CursorLeft => self.on_left(),
+            Action::CursorRight => self.on_right(),
+            Action::CycleChartType => {
+                // 切换价格图的绘制方式
+                self.chart_type = self.chart_type.next();
+            }
+            Action::ToggleRenderer => {
+                // 切换画布 / 字符单元格绘制
+                self.renderer = match self.renderer {
+                    Renderer::Canvas => Renderer::Cell,
+                    Renderer::Cell => Renderer::Canvas,
+                };
+            }
+            Action::ZoomIn => self.chart_view.zoom_in(self.k_line_datas.len()),
+            Action::ZoomOut => self.chart_view.zoom_out(self.k_line_datas.len()),
+            Action::PanLeft => self.chart_view.pan_left(self.k_line_datas.len()),
+            Action::PanRight => {
+                self.chart_view.pan_right(self.k_line_datas.len());
+                self.load_older_page();
+            }
+            Action::AddLevel => {
+                // 在光标处添加水平价位线
+                if let Some(anchor) = self.cursor_anchor() {
+                    self.add_drawing(Drawing::Level {
+                        price: anchor.price,
+                    });
+                }
+            }
+            Action::TrendLine => self.on_drawing_tool(DrawingTool::TrendLine(None)),
+            Action::Fibonacci => self.on_drawing_tool(DrawingTool::Fibonacci(None)),
+            Action::AddNote => {
+                // 在光标处添加备注，没有光标时忽略
+                if self.cursor_anchor().is_some() {
+                    self.input = Some(Input::new(InputPurpose::Note));
+                }
+            }
+            Action::RemoveDrawing => {
+                self.drawings.remove_selected();
+                self.save_drawings();
             }
-            'q' => {
-                // quit
+            Action::NextDrawing => self.drawings.select_next(),
+            Action::PreviousDrawing => self.drawings.select_previous(),
+            Action::RaiseDrawing => self.shift_selected_drawing(1.0),
+            Action::LowerDrawing => self.shift_selected_drawing(-1.0),
+            Action::ToggleCompare => {
+                // 加入或移出对比
+                let gp_id = match self.selected_item_brief_info() {
+                    Some(item) => item.stock_code_num,
+                    None => return Control::Continue,
+                };
+                match self.compare.iter().position(|&c| c == gp_id) {
+                    Some(position) => {
+                        self.compare.remove(position);
+                    }
+                    None => self.compare.push(gp_id),
+                }
+            }
+            Action::CompareView => {
+                // 切换对比视图，未选择时对比当前股票
+                self.compare_mode = !self.compare_mode;
+                if self.compare_mode {
+                    if let (true, Some(item)) =
+                        (self.compare.is_empty(), self.selected_item_brief_info())
+                    {
+                        self.compare.push(item.stock_code_num);
+                    }
+                    for gp_id in self.compare.clone() {
+                        self.fetch_stock_datas(gp_id);
+                    }
+                }
+            }
+            Action::ShowOverview => self.tab = Tab::Overview,
+            Action::ShowChart => self.tab = Tab::Chart,
+            Action::ShowFlows => self.tab = Tab::Flows,
+            Action::ShowPortfolio => self.tab = Tab::Portfolio,
+            Action::ShowLog => self.tab = Tab::Log,
+            Action::NextTab => self.tab = self.tab.next(),
+            Action::ToggleSidebar => self.show_sidebar = !self.show_sidebar,
+            Action::PreviousTab => self.tab = self.tab.previous(),
+            Action::Buy => self.input = Some(Input::new(InputPurpose::Trade(Side::Buy))),
+            Action::Sell => self.input = Some(Input::new(InputPurpose::Trade(Side::Sell))),
+            Action::Export => {
+                // 导出当前股票的日 k 线数据
+                self.export_selected();
+            }
+            Action::AlertLog => {
+                // 打开或关闭提醒日志
+                self.show_alert_log = !self.show_alert_log;
+            }
+            Action::Debug => {
+                // 打开或关闭数据检查报告
+                self.show_debug = !self.show_debug;
+            }
+            Action::Retry => {
+                // 立即重新请求当前股票
+                let i = self.stock_ids.state.selected().unwrap_or_default();
+                let item = match self.stock_ids.items.get_mut(i) {
+                    Some(item) => item,
+                    None => return Control::Continue,
+                };
+                if !item.loading && item.imported.is_none() {
+                    item.loading = true;
+                    net::spawn_fetch(item.stock_code_num, None, self.fetch_tx.clone());
+                }
+            }
+            Action::PauseRefresh => {
+                // 暂停或恢复自动刷新
+                self.scheduler.paused = !self.scheduler.paused;
+            }
+            Action::SortColumn => {
+                // 切换排序列
+                self.watchlist.next_sort_column();
+                self.sort_watchlist();
+                self.save_watchlist();
+            }
+            Action::SortOrder => {
+                // 切换升序 / 降序
+                self.watchlist.descending = !self.watchlist.descending;
+                self.sort_watchlist();
+                self.save_watchlist();
+            }
+            Action::EditColumns => {
+                // 打开或关闭列选择弹窗
+                self.column_cursor = match self.column_cursor {
+                    Some(_) => None,
+                    None => Some(0),
+                };
+            }
+            Action::Confirm => self.on_enter(),
+            Action::Cancel => self.on_esc(),
+            Action::Help => self.show_help = !self.show_help,
+            Action::Quit => {
                 self.stock_ids.unselect();
-                exit(0)
+                return Control::Quit;
             }
-            _ => {}
         }
+        Control::Continue
     }
 
-    pub fn on_left(&self) {}
+    /// 处理后台请求返回的数据，到达刷新时间时重新请求
+    pub fn on_tick(&mut self) {
+        let reload = self.scheduler.poll(self.market_status(), Instant::now());
+        self.refresh_visible(reload);
+        while let Ok(fetched) = self.fetch_rx.try_recv() {
+            self.on_fetched(fetched);
+        }
+    }
+
+    /// 退出前的收尾：最多等待 `timeout` 让进行中的请求完成（请求成功时会写入本地历史），
+    /// 然后保存自选列表和画线
+    pub fn shutdown(&mut self, timeout: Duration) {
+        let deadline = Instant::now() + timeout;
+        while self.stock_ids.items.iter().any(|item| item.loading) {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.fetch_rx.recv_timeout(remaining) {
+                Ok(fetched) => self.on_fetched(fetched),
+                Err(_) => {
+                    log::warn!("shutdown: gave up waiting for in-flight fetches");
+                    break;
+                }
+            }
+        }
+        self.save_watchlist();
+        self.save_drawings();
+    }
+
+    /// 处理一个后台请求的结果
+    pub fn on_fetched(&mut self, fetched: FetchResult) {
+        let item = self
+            .stock_ids
+            .items
+            .iter_mut()
+            .find(|item| item.stock_code_num == fetched.gp_id);
+        if let Some(item) = item {
+            item.loading = false;
+            if let Err(ref err) = fetched.result {
+                item.fetch_error = Some(err.clone());
+            }
+        }
+        if let Ok(wrapper) = fetched.result {
+            let older_page = fetched.cursor.is_some();
+            for wrapper in wrapper.split_by_stock() {
+                let gp_id = wrapper.get_gp_id().unwrap_or(fetched.gp_id);
+                self.on_stock_data(gp_id, wrapper, older_page);
+            }
+        }
+        self.sort_watchlist();
+        self.sync_k_line_datas();
+    }
+
+    /// 保存一只股票的数据，更早的一页并入已有数据，不在自选列表中的忽略
+    fn on_stock_data(&mut self, gp_id: i32, wrapper: StockDataWrapper, older_page: bool) {
+        let item = self
+            .stock_ids
+            .items
+            .iter_mut()
+            .find(|item| item.stock_code_num == gp_id);
+        let item = match item {
+            Some(item) => item,
+            None => return,
+        };
+        if let (true, Some(stock_data)) = (older_page, item.stock_data.as_mut()) {
+            stock_data.append_older(wrapper);
+            return;
+        }
+        let now = market::shanghai_now();
+        if let Some(day) = wrapper.get_stock_data("day") {
+            self.alerts.evaluate(&item.stock_code, day, now);
+        }
+        item.stock_data = Some(wrapper);
+        item.fetch_error = None;
+        item.updated_at = Some(now);
+    }
+
+    /// 翻到最早的 k 线时请求当前股票更早的一页
+    fn load_older_page(&mut self) {
+        let total = self.k_line_datas.len();
+        if self.chart_view.range(total).end < total {
+            return;
+        }
+        let i = self.stock_ids.state.selected().unwrap_or_default();
+        let item = match self.stock_ids.items.get_mut(i) {
+            Some(item) => item,
+            None => return,
+        };
+        let cursor = item
+            .stock_data
+            .as_ref()
+            .and_then(|stock_data| stock_data.get_older_cursor());
+        if let (false, Some(cursor)) = (item.loading, cursor) {
+            item.loading = true;
+            net::spawn_fetch(item.stock_code_num, Some(cursor), self.fetch_tx.clone());
+        }
+    }
+
+    /// 请求股票数据，已有数据或正在请求时跳过
+    pub fn fetch_stock_datas(&mut self, gp_id: i32) {
+        let item = self
+            .stock_ids
+            .items
+            .iter_mut()
+            .find(|item| item.stock_code_num == gp_id);
+        if let Some(item) = item {
+            if item.stock_data.is_none() && item.imported.is_none() && !item.loading {
+                item.loading = true;
+                net::spawn_fetch(item.stock_code_num, None, self.fetch_tx.clone());
+            }
+        }
+    }
+
+    /// 自选列表中当前显示的行，列表没有绘制时为空
+    fn visible_rows(&self) -> Range<usize> {
+        let len = self.stock_ids.items.len();
+        let rows = self.list_area.height.saturating_sub(3) as usize;
+        let start = self.list_offset.min(len);
+        start..(start + rows).min(len)
+    }
+
+    /// 需要请求的行：自选列表中显示的行与选中的股票，正在请求的和导入的跳过。
+    /// `reload` 为 `false` 时只返回还没有加载过的行，例如滚动后新出现的行
+    pub fn refresh_targets(&self, reload: bool) -> Vec<usize> {
+        let visible = self.visible_rows();
+        let selected = self.stock_ids.state.selected();
+        self.stock_ids
+            .items
+            .iter()
+            .enumerate()
+            .filter(|(i, item)| {
+                (visible.contains(i) || selected == Some(*i))
+                    && !item.loading
+                    && item.imported.is_none()
+                    && (reload || (item.stock_data.is_none() && item.fetch_error.is_none()))
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// 请求 `refresh_targets` 返回的行，自选列表很长时不会一次请求全部股票
+    pub fn refresh_visible(&mut self, reload: bool) {
+        for i in self.refresh_targets(reload) {
+            let item = &mut self.stock_ids.items[i];
+            item.loading = true;
+            net::spawn_fetch(item.stock_code_num, None, self.fetch_tx.clone());
+        }
+    }
+
+    pub fn market_status(&self) -> MarketStatus {
+        self.calendar.status(market::shanghai_now())
+    }
+
+    /// 当前股票的数据是否已过期
+    pub fn is_stale(&self) -> bool {
+        let updated_at = match self
+            .selected_item_brief_info()
+            .and_then(|item| item.updated_at)
+        {
+            Some(updated_at) => updated_at,
+            None => return false,
+        };
+        let since_update = (market::shanghai_now() - updated_at)
+            .to_std()
+            .unwrap_or_default();
+        self.scheduler.is_stale(self.market_status(), since_update)
+    }
+
+    /// 按当前排序列重新排序自选列表，保持选中的股票不变
+    fn sort_watchlist(&mut self) {
+        let selected = self
+            .stock_ids
+            .state
+            .selected()
+            .and(self.selected_item_brief_info())
+            .map(|item| item.stock_code_num);
+        self.watchlist.sort(&mut self.stock_ids.items);
+        if let Some(gp_id) = selected {
+            let i = self
+                .stock_ids
+                .items
+                .iter()
+                .position(|item| item.stock_code_num == gp_id);
+            self.stock_ids.state.select(i);
+        }
+    }
+
+    /// 列选择弹窗打开时处理上下移动与切换，返回是否已处理
+    fn on_column_action(&mut self, action: Action) -> bool {
+        let len = Column::ALL.len();
+        let cursor = self.column_cursor.unwrap_or_default();
+        match action {
+            Action::SelectNext => self.column_cursor = Some((cursor + 1) % len),
+            Action::SelectPrevious => self.column_cursor = Some((cursor + len - 1) % len),
+            Action::SelectFirst => self.column_cursor = Some(0),
+            Action::SelectLast => self.column_cursor = Some(len - 1),
+            Action::Confirm => {
+                self.watchlist.toggle_column(Column::ALL[cursor]);
+                self.save_watchlist();
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn save_watchlist(&self) {
+        if let Err(err) = self.watchlist.save() {
+            log::error!("save watchlist config failed: {}", err);
+        }
+    }
+
+    /// 对比视图中各股票相对共同起始日的涨跌幅
+    pub fn compare_lines(&self) -> Vec<CompareLine> {
+        let series: Vec<(String, Vec<KlineData>)> = self
+            .compare
+            .iter()
+            .filter_map(|&gp_id| {
+                self.stock_ids
+                    .items
+                    .iter()
+                    .find(|item| item.stock_code_num == gp_id)
+            })
+            .filter_map(|item| Some((item.stock_name.to_string(), item.day_k_line_datas()?)))
+            .collect();
+        compare::normalize(&series, &self.theme.compare)
+    }
+
+    pub fn on_left(&mut self) {
+        self.chart_view.move_cursor(-1, self.k_line_datas.len());
+    }
 
     pub fn on_up(&mut self) {
         self.stock_ids.previous();
+        self.on_select();
     }
 
-    pub fn on_right(&self) {}
+    pub fn on_right(&mut self) {
+        self.chart_view.move_cursor(1, self.k_line_datas.len());
+        self.load_older_page();
+    }
 
     pub fn on_bottom(&mut self) {
         self.stock_ids.next();
+        self.on_select();
+    }
+
+    fn select(&mut self, i: usize) {
+        if !self.stock_ids.items.is_empty() {
+            self.stock_ids.state.select(Some(i));
+            self.on_select();
+        }
+    }
+
+    /// 在当前标签页中选择一行：概览与图表选择股票，其余视图选择表格中的行。
+    /// `row` 由当前选中的行与总行数得到新的行，只在有数据时调用
+    fn select_row(&mut self, row: fn(Option<usize>, usize) -> usize) {
+        let len = match self.tab {
+            Tab::Overview | Tab::Chart => {
+                let len = self.stock_ids.items.len();
+                if len > 0 {
+                    self.select(row(self.stock_ids.state.selected(), len));
+                }
+                return;
+            }
+            Tab::Flows => self
+                .selected_day_data()
+                .map_or(0, |stock_data| stock_data.get_past_main_ins().len()),
+            Tab::Portfolio => self
+                .portfolio
+                .positions()
+                .map_or(0, |positions| positions.len()),
+            Tab::Log => self.alerts.log.len(),
+        };
+        let state = match self.tab {
+            Tab::Flows => &mut self.flows_state,
+            Tab::Portfolio => &mut self.portfolio_state,
+            _ => &mut self.log_state,
+        };
+        if len > 0 {
+            state.select(Some(row(state.selected(), len)));
+        }
+    }
+
+    /// 大盘指数，取自任意一只已加载股票的数据
+    pub fn market_index(&self) -> Option<&Szzs> {
+        self.stock_ids
+            .items
+            .iter()
+            .filter_map(|item| item.stock_data.as_ref())
+            .map(StockDataWrapper::get_szzs)
+            .find(|szzs| !szzs.get_trends().is_empty())
+    }
+
+    /// 当前股票的日线数据
+    pub fn selected_day_data(&self) -> Option<&StockData> {
+        self.selected_item_brief_info()?
+            .stock_data
+            .as_ref()?
+            .get_stock_data("day")
+    }
+
+    /// 切换股票后重置图表与资金流向的选择并加载画线
+    fn on_select(&mut self) {
+        self.chart_view = ChartView::default();
+        self.flows_state = TableState::default();
+        self.sync_k_line_datas();
+        self.load_drawings();
+    }
+
+    /// 图表显示当前股票的日 k 线，尚未加载时保留原来的数据，自选列表为空时清空
+    fn sync_k_line_datas(&mut self) {
+        match self.selected_item_brief_info() {
+            Some(item) => {
+                if let Some(k_line_datas) = item.day_k_line_datas() {
+                    self.k_line_datas = k_line_datas;
+                }
+            }
+            None => self.k_line_datas.clear(),
+        }
+    }
+
+    pub fn on_enter(&mut self) {
+        if let Some(input) = self.input.take() {
+            self.submit_input(input);
+            return;
+        }
+        if let Some(anchor) = self.cursor_anchor() {
+            self.place_anchor(anchor);
+        }
+    }
+
+    pub fn on_esc(&mut self) {
+        self.input = None;
+        self.drawing_tool = None;
+        self.show_alert_log = false;
+        self.show_debug = false;
+        self.column_cursor = None;
+        self.show_help = false;
+        self.pending_keys.clear();
+        self.alerts.dismiss_banner();
+        self.status = None;
     }
 
-    pub fn selected_item_brief_info(&self) -> &StockBriefInfo {
-        let i = match self.stock_ids.state.selected() {
-            Some(i) => i,
-            None => 0,
+    pub fn on_backspace(&mut self) {
+        if let Some(input) = self.input.as_mut() {
+            input.text.pop();
+        }
+    }
+
+    /// 把当前股票的日 k 线数据以 CSV 和 JSON 导出到数据目录下的 `exports`
+    fn export_selected(&mut self) {
+        let item = match self.selected_item_brief_info() {
+            Some(item) => item,
+            None => return,
+        };
+        let stock_data = match item
+            .stock_data
+            .as_ref()
+            .and_then(|data| data.get_stock_data("day"))
+        {
+            Some(stock_data) => stock_data,
+            None => {
+                self.status = Some(format!("{} 尚未加载数据，无法导出", item.stock_code));
+                return;
+            }
+        };
+        let dir = storage::data_dir().join("exports");
+        let result = export::export(&item.stock_code, stock_data, Format::Csv, &dir)
+            .and_then(|_| export::export(&item.stock_code, stock_data, Format::Json, &dir));
+        self.status = Some(match result {
+            Ok(_) => format!("已导出到 {}", dir.display()),
+            Err(err) => {
+                log::error!("export {} failed: {}", item.stock_code, err);
+                format!("导出失败: {}", err)
+            }
+        });
+    }
+
+    /// 提交输入内容，成交记录无效时保留输入框并显示错误
+    fn submit_input(&mut self, mut input: Input) {
+        match input.purpose {
+            InputPurpose::Note => {
+                if let (Some(at), false) = (self.cursor_anchor(), input.text.is_empty()) {
+                    self.add_drawing(Drawing::Note {
+                        at,
+                        text: input.text,
+                    });
+                }
+            }
+            InputPurpose::Trade(side) => {
+                let stock_code = match self.selected_item_brief_info() {
+                    Some(item) => item.stock_code.to_string(),
+                    None => return,
+                };
+                let today = market::shanghai_now().date_naive();
+                let result = Transaction::parse(&stock_code, side, &input.text, today)
+                    .and_then(|transaction| self.portfolio.record(transaction));
+                match result {
+                    Ok(()) => {
+                        if let Err(err) = self.portfolio.save() {
+                            log::error!("save portfolio failed: {}", err);
+                        }
+                    }
+                    Err(err) => {
+                        input.error = Some(err);
+                        self.input = Some(input);
+                    }
+                }
+            }
+        }
+    }
+
+    /// 鼠标位置在图表坐标系中的 x（相对第一根可见 K 线的下标）与价格，不在 K 线区域内时为 `None`
+    fn chart_point(&self, column: u16, row: u16) -> Option<(f64, f64)> {
+        let area = self.chart_area;
+        if !contains(area, column, row) || self.k_line_datas.is_empty() {
+            return None;
+        }
+        let [x_min, x_max] = self.get_x_bounds();
+        let [y_min, y_max] = self.get_y_bounds();
+        let x = x_min + (column - area.left()) as f64 / area.width as f64 * (x_max - x_min);
+        let price = y_max - (row - area.top()) as f64 / area.height as f64 * (y_max - y_min);
+        Some((x, price))
+    }
+
+    /// 鼠标位置对应的 K 线下标与价格，不在 K 线区域内时为 `None`
+    fn chart_position(&self, column: u16, row: u16) -> Option<(usize, f64)> {
+        let (x, price) = self.chart_point(column, row)?;
+        let range = self.chart_view.range(self.k_line_datas.len());
+        let index = (range.start + x.round().max(0.0) as usize).min(range.end - 1);
+        Some((index, price))
+    }
+
+    /// 鼠标位置点中的画线的下标与点中的部分
+    fn hit_drawing(&self, column: u16, row: u16) -> Option<(usize, Handle)> {
+        let point = self.chart_point(column, row)?;
+        let [x_min, x_max] = self.get_x_bounds();
+        let [y_min, y_max] = self.get_y_bounds();
+        let area = self.chart_area;
+        let cell = (
+            (x_max - x_min) / area.width as f64,
+            (y_max - y_min) / area.height as f64,
+        );
+        let start = self.chart_view.range(self.k_line_datas.len()).start;
+        self.drawings.hit(&self.k_line_datas, start, point, cell)
+    }
+
+    /// 鼠标点击 K 线区域：移动光标，正在画线时以鼠标所在价格作为锚点；
+    /// 点中画线时选中它，之后的拖动移动画线，否则取消选择，之后的拖动平移图表
+    pub fn on_chart_click(&mut self, column: u16, row: u16) {
+        let (cursor, price) = match self.chart_position(column, row) {
+            Some(position) => position,
+            None => return,
+        };
+        self.chart_view.cursor = Some(cursor);
+        if self.drawing_tool.is_none() {
+            self.drawings.selected = None;
+            if let Some((i, handle)) = self.hit_drawing(column, row) {
+                self.drawings.selected = Some(i);
+                self.drawing_drag = Some((handle, price));
+                return;
+            }
+        }
+        let range = self.chart_view.range(self.k_line_datas.len());
+        self.drag_origin = Some((column, range.start));
+        if self.drawing_tool.is_some() {
+            self.place_anchor(Anchor {
+                date: self.k_line_datas[cursor].date.to_string(),
+                price,
+            });
+        }
+    }
+
+    /// 鼠标悬停在 K 线区域时光标跟随鼠标
+    pub fn on_chart_hover(&mut self, column: u16, row: u16) {
+        if let Some((cursor, _)) = self.chart_position(column, row) {
+            self.chart_view.cursor = Some(cursor);
+        }
+    }
+
+    /// 右键点击画线删除它
+    pub fn on_chart_right_click(&mut self, column: u16, row: u16) {
+        if let Some((i, _)) = self.hit_drawing(column, row) {
+            self.drawings.selected = Some(i);
+            self.drawings.remove_selected();
+            self.save_drawings();
+        }
+    }
+
+    /// 拖动平移图表：向左拖看更早的数据，向右拖看更新的数据；点中画线后拖动则移动画线
+    pub fn on_chart_drag(&mut self, column: u16, row: u16) {
+        if let Some((handle, last_price)) = self.drawing_drag {
+            self.drag_drawing(handle, last_price, column, row);
+            return;
+        }
+        let (origin, start) = match self.drag_origin {
+            Some(origin) => origin,
+            None => return,
         };
-        &self.stock_ids.items[i]
+        let total = self.k_line_datas.len();
+        let width = self.chart_area.width.max(1) as isize;
+        let visible = self.chart_view.range(total).len() as isize;
+        let bars = (origin as isize - column as isize) * visible / width;
+        self.chart_view
+            .pan_to(start.saturating_add_signed(bars), total);
+        self.load_older_page();
+    }
+
+    /// 拖动选中的画线：拖动锚点时锚点跟随鼠标，拖动其余部分时上下移动整个图形，
+    /// 拖出 K 线区域时停在原处
+    fn drag_drawing(&mut self, handle: Handle, last_price: f64, column: u16, row: u16) {
+        let (index, price) = match self.chart_position(column, row) {
+            Some(position) => position,
+            None => return,
+        };
+        let date = self.k_line_datas[index].date.to_string();
+        let drawing = match self.drawings.selected_mut() {
+            Some(drawing) => drawing,
+            None => return,
+        };
+        match handle {
+            Handle::Anchor(i) => {
+                if let Some(anchor) = drawing.anchor_mut(i) {
+                    *anchor = Anchor { date, price };
+                }
+            }
+            Handle::Body => drawing.shift(price - last_price),
+        }
+        self.drawing_drag = Some((handle, price));
+    }
+
+    /// 结束拖动，拖动过画线时保存
+    pub fn on_mouse_up(&mut self) {
+        self.drag_origin = None;
+        if self.drawing_drag.take().is_some() {
+            self.save_drawings();
+        }
+    }
+
+    /// 终端尺寸变化后，拖动开始时的坐标已经失效
+    pub fn on_resize(&mut self) {
+        self.on_mouse_up();
+    }
+
+    /// 点击自选表格中的一行选中该股票
+    pub fn on_list_click(&mut self, column: u16, row: u16) {
+        // 跳过上边框与表头
+        let first_row = self.list_area.top() + 2;
+        if !contains(self.list_area, column, row) || row < first_row {
+            return;
+        }
+        let i = (row - first_row) as usize + self.list_offset;
+        if i < self.stock_ids.items.len() && self.stock_ids.state.selected() != Some(i) {
+            self.select(i);
+        }
+    }
+
+    /// 滚轮：在自选表格上移动选择，在 K 线区域上缩放
+    pub fn on_scroll(&mut self, column: u16, row: u16, down: bool) {
+        let total = self.k_line_datas.len();
+        if contains(self.list_area, column, row) {
+            if down {
+                self.on_bottom();
+            } else {
+                self.on_up();
+            }
+        } else if contains(self.chart_area, column, row) && total > 0 {
+            if down {
+                self.chart_view.zoom_out(total);
+            } else {
+                self.chart_view.zoom_in(total);
+            }
+        }
+    }
+
+    /// 光标所在 K 线的收盘价
+    pub fn cursor_anchor(&self) -> Option<Anchor> {
+        let k_line_data = self.k_line_datas.get(self.chart_view.cursor?)?;
+        Some(Anchor {
+            date: k_line_data.date.to_string(),
+            price: k_line_data.end,
+        })
+    }
+
+    /// 选择画线工具，再按一次同一工具键则在光标处放置第二个锚点
+    fn on_drawing_tool(&mut self, tool: DrawingTool) {
+        let pending = matches!(
+            (&self.drawing_tool, &tool),
+            (
+                Some(DrawingTool::TrendLine(Some(_))),
+                DrawingTool::TrendLine(_)
+            ) | (
+                Some(DrawingTool::Fibonacci(Some(_))),
+                DrawingTool::Fibonacci(_)
+            )
+        );
+        if !pending {
+            self.drawing_tool = Some(tool);
+        }
+        if let Some(anchor) = self.cursor_anchor() {
+            self.place_anchor(anchor);
+        }
+    }
+
+    /// 为正在绘制的工具放置锚点，两个锚点都确定后生成图形
+    fn place_anchor(&mut self, anchor: Anchor) {
+        let drawing = match self.drawing_tool.take() {
+            Some(DrawingTool::TrendLine(None)) => {
+                self.drawing_tool = Some(DrawingTool::TrendLine(Some(anchor)));
+                return;
+            }
+            Some(DrawingTool::Fibonacci(None)) => {
+                self.drawing_tool = Some(DrawingTool::Fibonacci(Some(anchor)));
+                return;
+            }
+            Some(DrawingTool::TrendLine(Some(from))) => Drawing::TrendLine { from, to: anchor },
+            Some(DrawingTool::Fibonacci(Some(from))) => Drawing::Fibonacci { from, to: anchor },
+            None => return,
+        };
+        self.add_drawing(drawing);
+    }
+
+    fn add_drawing(&mut self, drawing: Drawing) {
+        self.drawings.add(drawing);
+        self.save_drawings();
+    }
+
+    fn shift_selected_drawing(&mut self, steps: f64) {
+        let [min, max] = self.get_y_bounds();
+        if let Some(drawing) = self.drawings.selected_mut() {
+            drawing.shift(steps * (max - min) / 50.0);
+            self.save_drawings();
+        }
+    }
+
+    fn load_drawings(&mut self) {
+        self.drawing_tool = None;
+        self.drawing_drag = None;
+        let stock_code = match self.selected_item_brief_info() {
+            Some(item) => item.stock_code.to_string(),
+            None => {
+                self.drawings = Drawings::default();
+                return;
+            }
+        };
+        self.drawings = Drawings::load(&stock_code).unwrap_or_else(|err| {
+            log::error!("load drawings of {} failed: {}", stock_code, err);
+            Drawings {
+                stock_code: stock_code.clone(),
+                ..Drawings::default()
+            }
+        });
+    }
+
+    fn save_drawings(&self) {
+        if let Err(err) = self.drawings.save() {
+            log::error!(
+                "save drawings of {} failed: {}",
+                self.drawings.stock_code,
+                err
+            );
+        }
+    }
+
+    /// 当前选中的股票，自选列表为空时为 `None`
+    pub fn selected_item_brief_info(&self) -> Option<&StockBriefInfo<'_>> {
+        let i = self.stock_ids.state.selected().unwrap_or_default();
+        self.stock_ids.items.get(i)
+    }
+
+    /// 当前可视窗口内的 K 线数据
+    pub fn visible_k_line_datas(&self) -> &[KlineData<'a>] {
+        &self.k_line_datas[self.chart_view.range(self.k_line_datas.len())]
+    }
+
+    /// 图表中实际绘制的可见 K 线：平均 K 线按全部数据计算后再截取，缩放、平移不改变其数值
+    pub fn drawn_k_line_datas(&self) -> Cow<'_, [KlineData<'a>]> {
+        let range = self.chart_view.range(self.k_line_datas.len());
+        match self.chart_type {
+            ChartType::HeikinAshi => {
+                let mut datas = heikin_ashi(&self.k_line_datas);
+                datas.truncate(range.end);
+                datas.drain(..range.start);
+                Cow::Owned(datas)
+            }
+            _ => Cow::Borrowed(&self.k_line_datas[range]),
+        }
     }
 
     pub fn get_x_bounds(&self) -> [f64; 2] {
-        let min = -0.1;
-        let max = self.k_line_datas.capacity() as f64;
+        let min = -0.5;
+        let max = self.visible_k_line_datas().len() as f64 - 0.5;
         [min, max]
     }
 
+    /// 实际绘制的 K 线的价格范围，没有数据时为 `[0, 0]`，价格不变时向两侧扩展
     pub fn get_y_bounds(&self) -> [f64; 2] {
+        let k_line_datas = self.drawn_k_line_datas();
+        if k_line_datas.is_empty() {
+            return [0.0, 0.0];
+        }
         let mut min = f64::MAX;
         let mut max = f64::MIN;
-        for k_line_data in &self.k_line_datas {
+        for k_line_data in k_line_datas.iter() {
             if min > k_line_data.min {
                 min = k_line_data.min;
             }
@@ -710,19 +1759,27 @@ impl<'a> App<'a> {
                 max = k_line_data.max
             }
         }
-        [min, max]
+        padded_bounds([min, max])
     }
 
+    /// 没有数据时 x 轴标签为空字符串
     pub fn get_start_x_label(&self) -> &str {
-        self.k_line_datas.first().unwrap().date
+        self.visible_k_line_datas()
+            .first()
+            .map_or("", |k_line_data| &k_line_data.date)
     }
 
     pub fn get_center_x_label(&self) -> &str {
-        self.k_line_datas[self.k_line_datas.capacity() / 2].date
+        let k_line_datas = self.visible_k_line_datas();
+        k_line_datas
+            .get(k_line_datas.len() / 2)
+            .map_or("", |k_line_data| &k_line_data.date)
     }
 
     pub fn get_end_x_label(&self) -> &str {
-        self.k_line_datas.last().unwrap().date
+        self.visible_k_line_datas()
+            .last()
+            .map_or("", |k_line_data| &k_line_data.date)
     }
 
     pub fn get_center_y_label(&self) -> f64 {
@@ -0,0 +1,86 @@
+use std::ops::Range;
+
+/// 最少显示的 K 线数量
+const MIN_VISIBLE_BARS: usize = 5;
+
+/// K 线图的可视窗口，记录缩放与平移后显示的数据范围
+#[derive(Debug, Clone, Default)]
+pub struct ChartView {
+    /// 第一根可见 K 线的下标
+    pub start: usize,
+    /// 可见 K 线的数量，0 表示显示全部
+    pub len: usize,
+    /// 光标所在 K 线的下标
+    pub cursor: Option<usize>,
+}
+
+impl ChartView {
+    /// 当前可见的数据范围
+    pub fn range(&self, total: usize) -> Range<usize> {
+        let len = if self.len == 0 {
+            total
+        } else {
+            self.len.min(total)
+        };
+        let start = self.start.min(total - len);
+        start..start + len
+    }
+
+    pub fn zoom_in(&mut self, total: usize) {
+        let range = self.range(total);
+        let len = (range.len() * 2 / 3).max(MIN_VISIBLE_BARS).min(total);
+        // 以窗口中心为基准缩放
+        self.start = range.start + (range.len() - len) / 2;
+        self.len = len;
+    }
+
+    pub fn zoom_out(&mut self, total: usize) {
+        let range = self.range(total);
+        let len = (range.len() * 3 / 2).max(range.len() + 1);
+        if len >= total {
+            self.start = 0;
+            self.len = 0;
+            return;
+        }
+        self.start = range
+            .start
+            .saturating_sub((len - range.len()) / 2)
+            .min(total - len);
+        self.len = len;
+    }
+
+    pub fn pan_left(&mut self, total: usize) {
+        let range = self.range(total);
+        self.start = range.start.saturating_sub((range.len() / 4).max(1));
+    }
+
+    pub fn pan_right(&mut self, total: usize) {
+        let range = self.range(total);
+        self.start = (range.start + (range.len() / 4).max(1)).min(total - range.len());
+    }
+
+    /// 平移到以 `start` 开始的窗口，超出范围时停在两端
+    pub fn pan_to(&mut self, start: usize, total: usize) {
+        let range = self.range(total);
+        self.start = start.min(total - range.len());
+    }
+
+    /// 移动光标，光标移出可视窗口时跟随平移
+    pub fn move_cursor(&mut self, delta: isize, total: usize) {
+        if total == 0 {
+            return;
+        }
+        let range = self.range(total);
+        let cursor = match self.cursor {
+            Some(cursor) => cursor.saturating_add_signed(delta).min(total - 1),
+            None if delta < 0 => range.end - 1,
+            None => range.start,
+        };
+        if cursor < range.start {
+            self.start = cursor;
+        } else if cursor >= range.end {
+            self.start = cursor + 1 - range.len();
+        }
+        self.cursor = Some(cursor);
+    }
+}
@@ -0,0 +1,549 @@
+use crate::{
+    app::{self, StockBriefInfo},
+    export::{self, Format},
+    import::{ImportOptions, ImportedSeries},
+    model::StockDataWrapper,
+    net::{self, FetchError},
+    watchlist::{Quote, WatchlistConfig},
+};
+use clap::{Parser, Subcommand};
+use log::LevelFilter;
+use serde::Serialize;
+use std::{error::Error, path::PathBuf};
+use unicode_width::UnicodeWidthStr;
+
+/// 不启动界面时可以使用的子命令，结果输出到标准输出
+#[derive(Parser, Debug)]
+#[command(version, about = "终端股票行情")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// 日志级别：off、error、warn、info、debug、trace，日志写入数据目录下的 logs/stock_rs.log
+    #[arg(long, global = true, default_value = "info")]
+    pub log_level: LevelFilter,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// 最新行情
+    Quote {
+        /// 股票代码，如 SH512690
+        code: String,
+        /// 以 JSON 输出
+        #[arg(long)]
+        json: bool,
+    },
+    /// k 线数据
+    Kline {
+        code: String,
+        /// k 线级别：day、week、month、5min、15min、30min、60min、120min
+        #[arg(long, default_value = "day")]
+        view: String,
+        /// 输出最近的条数
+        #[arg(long, default_value_t = 30)]
+        limit: usize,
+        /// 请求的页数，大于 1 时沿 pre 游标继续请求更早的数据
+        #[arg(long, default_value_t = 1)]
+        pages: usize,
+        #[arg(long)]
+        json: bool,
+    },
+    /// 主力资金流向
+    Flows {
+        code: String,
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+        #[arg(long)]
+        json: bool,
+    },
+    /// 导出 k 线、分时、macd、kdj 与主力资金流向
+    Export {
+        code: String,
+        #[arg(long, default_value = "day")]
+        view: String,
+        #[arg(long, value_enum, default_value_t = Format::Csv)]
+        format: Format,
+        /// 输出目录
+        #[arg(long, default_value = ".")]
+        dir: PathBuf,
+    },
+    /// 从 CSV 导入历史 k 线，导入后在界面的自选列表中显示
+    Import {
+        file: PathBuf,
+        /// 导入后使用的代码
+        #[arg(long)]
+        code: String,
+        /// 导入后显示的名称，默认与代码相同
+        #[arg(long)]
+        name: Option<String>,
+        /// JSON 格式的导入设置，命令行参数会覆盖其中的值
+        #[arg(long)]
+        config: Option<PathBuf>,
+        #[arg(long)]
+        delimiter: Option<char>,
+        /// chrono 日期格式，如 %Y/%m/%d
+        #[arg(long)]
+        date_format: Option<String>,
+        /// 文件没有表头，此时列只能用序号指定
+        #[arg(long)]
+        no_headers: bool,
+        /// 列映射，如 --map date=日期 --map close=4，可重复
+        #[arg(long = "map", value_name = "FIELD=COLUMN")]
+        mappings: Vec<String>,
+    },
+    /// 自选列表的最新行情
+    Watch {
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Serialize)]
+struct QuoteRow<'a> {
+    code: &'a str,
+    name: &'a str,
+    #[serde(flatten)]
+    quote: Option<Quote>,
+}
+
+#[derive(Serialize)]
+struct KlineRow {
+    date: String,
+    open: f64,
+    high: f64,
+    low: f64,
+    close: f64,
+    change: f64,
+    volume: i64,
+    amount: i64,
+}
+
+#[derive(Serialize)]
+struct FlowRow {
+    date: String,
+    main: i32,
+    bigger: i32,
+    big: i32,
+    change: f32,
+    price: f32,
+}
+
+/// 解析 `SH512690`、`sz159915` 或 `512690` 形式的股票代码，返回数字部分；
+/// 前缀只能是 SH、SZ（不区分大小写），数字部分为 6 位
+pub fn parse_stock_code(code: &str) -> Result<i32, String> {
+    let invalid = || format!("无效的股票代码: {}", code);
+    let digits = match code.get(..2) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("sh") || prefix.eq_ignore_ascii_case("sz") => {
+            &code[2..]
+        }
+        _ => code,
+    };
+    if digits.len() != 6 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    digits.parse().map_err(|_| invalid())
+}
+
+pub async fn run(command: Command) -> Result<(), Box<dyn Error>> {
+    match command {
+        Command::Quote { code, json } => {
+            let wrapper = fetch(&code).await?;
+            let stock_data = wrapper
+                .get_stock_data("day")
+                .ok_or_else(|| format!("{} 没有日 k 线数据", code))?;
+            let row = QuoteRow {
+                code: &code,
+                name: stock_data.get_name(),
+                quote: Quote::from_stock_data(stock_data),
+            };
+            if json {
+                println!("{}", serde_json::to_string_pretty(&row)?);
+            } else {
+                print_quotes(&[row]);
+            }
+        }
+        Command::Kline {
+            code,
+            view,
+            limit,
+            pages,
+            json,
+        } => {
+            let mut wrapper = fetch(&code).await?;
+            let gp_id = parse_stock_code(&code)?;
+            for _ in 1..pages {
+                let cursor = match wrapper.get_older_cursor() {
+                    Some(cursor) => cursor,
+                    None => break,
+                };
+                let older = net::get_stock_datas_page(gp_id, Some(&cursor))
+                    .await
+                    .map_err(|err| format!("{} 获取更早的数据失败: {}", code, err))?;
+                match select_stock(older, gp_id) {
+                    Some(older) => wrapper.append_older(older),
+                    None => break,
+                }
+            }
+            let stock_data = wrapper
+                .get_stock_data(&view)
+                .ok_or_else(|| format!("{} 没有 {} 级别的 k 线数据", code, view))?;
+            let mut k_line_datas = stock_data.get_k_line_datas();
+            k_line_datas.sort_by(|a, b| a.date.cmp(&b.date));
+            let skip = k_line_datas.len().saturating_sub(limit);
+            let rows: Vec<KlineRow> = k_line_datas
+                .into_iter()
+                .skip(skip)
+                .map(|data| KlineRow {
+                    date: data.date.into_owned(),
+                    open: data.start,
+                    high: data.max,
+                    low: data.min,
+                    close: data.end,
+                    change: data.up_rate,
+                    volume: data.quantity,
+                    amount: data.amount,
+                })
+                .collect();
+            if json {
+                println!("{}", serde_json::to_string_pretty(&rows)?);
+            } else {
+                print_table(
+                    &[
+                        "日期",
+                        "开盘",
+                        "最高",
+                        "最低",
+                        "收盘",
+                        "涨幅",
+                        "成交量",
+                        "成交额",
+                    ],
+                    rows.iter().map(|row| {
+                        vec![
+                            row.date.clone(),
+                            format!("{:.3}", row.open),
+                            format!("{:.3}", row.high),
+                            format!("{:.3}", row.low),
+                            format!("{:.3}", row.close),
+                            format!("{:+.2}%", row.change),
+                            row.volume.to_string(),
+                            row.amount.to_string(),
+                        ]
+                    }),
+                );
+            }
+        }
+        Command::Flows { code, limit, json } => {
+            let wrapper = fetch(&code).await?;
+            let stock_data = wrapper
+                .get_stock_data("day")
+                .ok_or_else(|| format!("{} 没有日 k 线数据", code))?;
+            let mut main_ins: Vec<_> = stock_data.get_past_main_ins().iter().collect();
+            main_ins.sort_by(|a, b| a.get_date().cmp(b.get_date()));
+            let skip = main_ins.len().saturating_sub(limit);
+            let rows: Vec<FlowRow> = main_ins
+                .into_iter()
+                .skip(skip)
+                .map(|main_ins| FlowRow {
+                    date: main_ins.get_date().to_string(),
+                    main: main_ins.get_main(),
+                    bigger: main_ins.get_bigger(),
+                    big: main_ins.get_big(),
+                    change: main_ins.get_up_rate(),
+                    price: main_ins.get_price(),
+                })
+                .collect();
+            if json {
+                println!("{}", serde_json::to_string_pretty(&rows)?);
+            } else {
+                print_table(
+                    &["日期", "主力", "超大单", "大单", "涨幅", "价格"],
+                    rows.iter().map(|row| {
+                        vec![
+                            row.date.clone(),
+                            row.main.to_string(),
+                            row.bigger.to_string(),
+                            row.big.to_string(),
+                            format!("{:+.2}%", row.change),
+                            format!("{:.3}", row.price),
+                        ]
+                    }),
+                );
+            }
+        }
+        Command::Export {
+            code,
+            view,
+            format,
+            dir,
+        } => {
+            let wrapper = fetch(&code).await?;
+            let stock_data = wrapper
+                .get_stock_data(&view)
+                .ok_or_else(|| format!("{} 没有 {} 级别的 k 线数据", code, view))?;
+            for path in export::export(&code, stock_data, format, &dir)? {
+                println!("{}", path.display());
+            }
+        }
+        Command::Import {
+            file,
+            code,
+            name,
+            config,
+            delimiter,
+            date_format,
+            no_headers,
+            mappings,
+        } => {
+            let mut options = match config {
+                Some(path) => serde_json::from_str(&std::fs::read_to_string(path)?)?,
+                None => ImportOptions::default(),
+            };
+            if let Some(delimiter) = delimiter {
+                options.delimiter = delimiter;
+            }
+            if let Some(date_format) = date_format {
+                options.date_format = date_format;
+            }
+            if no_headers {
+                options.has_headers = false;
+            }
+            for mapping in &mappings {
+                options.columns.set(mapping)?;
+            }
+            let name = name.as_deref().unwrap_or(&code);
+            let series =
+                ImportedSeries::from_csv(&file, &code, name, &options).map_err(|errors| {
+                    for error in &errors {
+                        eprintln!("{}", error);
+                    }
+                    format!("{} 导入失败，共 {} 处错误", file.display(), errors.len())
+                })?;
+            series.save()?;
+            println!(
+                "已导入 {} 条 k 线（{} 至 {}）到 {}",
+                series.bars.len(),
+                series.bars[0].date,
+                series.bars[series.bars.len() - 1].date,
+                ImportedSeries::path(&code).display()
+            );
+        }
+        Command::Watch { json } => {
+            let mut items = app::default_stock_ids();
+            let results = fetch_all(&items).await;
+            for (item, result) in items.iter_mut().zip(results) {
+                match result {
+                    Ok(wrapper) => item.stock_data = select_stock(wrapper, item.stock_code_num),
+                    Err(err) => {
+                        eprintln!("{} 获取数据失败: {}", item.stock_code, err);
+                        item.fetch_error = Some(err);
+                    }
+                }
+            }
+            WatchlistConfig::load()?.sort(&mut items);
+            let rows: Vec<QuoteRow> = items
+                .iter()
+                .map(|item| QuoteRow {
+                    code: &item.stock_code,
+                    name: &item.stock_name,
+                    quote: item.quote(),
+                })
+                .collect();
+            if json {
+                println!("{}", serde_json::to_string_pretty(&rows)?);
+            } else {
+                print_quotes(&rows);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 接口返回多只股票时只保留 `gp_id` 的数据
+fn select_stock(wrapper: StockDataWrapper, gp_id: i32) -> Option<StockDataWrapper> {
+    wrapper
+        .split_by_stock()
+        .into_iter()
+        .find(|wrapper| wrapper.get_gp_id() == Some(gp_id))
+}
+
+async fn fetch(code: &str) -> Result<StockDataWrapper, Box<dyn Error>> {
+    let gp_id = parse_stock_code(code)?;
+    let wrapper = net::get_stock_datas(gp_id)
+        .await
+        .map_err(|err| format!("{} 获取数据失败: {}", code, err))?;
+    select_stock(wrapper, gp_id).ok_or_else(|| format!("{} 没有数据", code).into())
+}
+
+/// 同时请求所有股票的数据，结果与 `items` 顺序一致
+async fn fetch_all(items: &[StockBriefInfo<'_>]) -> Vec<Result<StockDataWrapper, FetchError>> {
+    let handles: Vec<_> = items
+        .iter()
+        .map(|item| tokio::spawn(net::get_stock_datas(item.stock_code_num)))
+        .collect();
+    let mut results = Vec::with_capacity(handles.len());
+    for handle in handles {
+        results.push(
+            handle
+                .await
+                .unwrap_or_else(|err| Err(FetchError::Network(err.to_string()))),
+        );
+    }
+    results
+}
+
+fn print_quotes(rows: &[QuoteRow]) {
+    print_table(
+        &["代码", "名称", "最新", "涨幅", "成交量", "成交额", "主力"],
+        rows.iter().map(|row| {
+            let (price, change, volume, amount, main_inflow) = match row.quote {
+                Some(ref quote) => (
+                    format!("{:.3}", quote.price),
+                    format!("{:+.2}%", quote.change),
+                    quote.volume.to_string(),
+                    quote.amount.to_string(),
+                    quote
+                        .main_inflow
+                        .map(|main| main.to_string())
+                        .unwrap_or_else(|| String::from("-")),
+                ),
+                None => {
+                    let none = || String::from("-");
+                    (none(), none(), none(), none(), none())
+                }
+            };
+            vec![
+                row.code.to_string(),
+                row.name.to_string(),
+                price,
+                change,
+                volume,
+                amount,
+                main_inflow,
+            ]
+        }),
+    );
+}
+
+/// 以空格对齐输出表格，按显示宽度计算中文字符
+fn print_table(header: &[&str], rows: impl Iterator<Item = Vec<String>>) {
+    let rows: Vec<Vec<String>> = rows.collect();
+    let widths: Vec<usize> = (0..header.len())
+        .map(|i| {
+            rows.iter()
+                .map(|row| row[i].width())
+                .chain(std::iter::once(header[i].width()))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let line = |cells: Vec<&str>| {
+        cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{}{}", cell, " ".repeat(width - cell.width())))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+    println!("{}", line(header.to_vec()));
+    for row in &rows {
+        println!("{}", line(row.iter().map(String::as_str).collect()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn stock_codes() {
+        assert_eq!(parse_stock_code("SH512690"), Ok(512690));
+        assert_eq!(parse_stock_code("sz159915"), Ok(159915));
+        assert_eq!(parse_stock_code("Sh512690"), Ok(512690));
+        assert_eq!(parse_stock_code("512690"), Ok(512690));
+        assert_eq!(parse_stock_code("000001"), Ok(1));
+        for code in [
+            "",
+            "SH",
+            "HK512690",
+            "SH51269",
+            "SH5126900",
+            "SH-12690",
+            "512690x",
+            "-12345",
+            "深512690",
+        ] {
+            assert_eq!(
+                parse_stock_code(code),
+                Err(format!("无效的股票代码: {}", code)),
+                "{}",
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn command_definitions() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn parse_kline_arguments() {
+        let cli = Cli::try_parse_from(["stock_rs", "kline", "SH512690"]).unwrap();
+        match cli.command {
+            Some(Command::Kline {
+                code,
+                view,
+                limit,
+                pages,
+                json,
+            }) => {
+                assert_eq!(code, "SH512690");
+                assert_eq!((view.as_str(), limit, pages, json), ("day", 30, 1, false));
+            }
+            command => panic!("unexpected command: {:?}", command),
+        }
+        let cli = Cli::try_parse_from([
+            "stock_rs", "kline", "SZ159915", "--view", "week", "--limit", "5", "--pages", "3",
+            "--json",
+        ])
+        .unwrap();
+        match cli.command {
+            Some(Command::Kline {
+                view,
+                limit,
+                pages,
+                json,
+                ..
+            }) => assert_eq!((view.as_str(), limit, pages, json), ("week", 5, 3, true)),
+            command => panic!("unexpected command: {:?}", command),
+        }
+    }
+
+    #[test]
+    fn parse_errors() {
+        // 缺少代码、数字参数无效、未知参数
+        for args in [
+            vec!["stock_rs", "kline"],
+            vec!["stock_rs", "kline", "SH512690", "--limit", "abc"],
+            vec!["stock_rs", "kline", "SH512690", "--pages", "-1"],
+            vec!["stock_rs", "quote", "SH512690", "--unknown"],
+            vec!["stock_rs", "export", "SH512690", "--format", "xml"],
+        ] {
+            assert!(Cli::try_parse_from(&args).is_err(), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn global_log_level() {
+        let cli = Cli::try_parse_from(["stock_rs"]).unwrap();
+        assert!(cli.command.is_none());
+        assert_eq!(cli.log_level, LevelFilter::Info);
+        let cli = Cli::try_parse_from(["stock_rs", "watch", "--log-level", "debug"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Watch { json: false })));
+        assert_eq!(cli.log_level, LevelFilter::Debug);
+    }
+}
@@ -0,0 +1,180 @@
+use custom_widget::kline::klinedata::KlineData;
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use tui::style::Color;
+
+/// 一条归一化后的对比线
+pub struct CompareLine {
+    pub name: String,
+    pub color: Color,
+    /// 按日期升序排列，`end` 为相对起始日的涨跌幅（%）
+    pub datas: Vec<KlineData<'static>>,
+}
+
+/// 把多只股票的 k 线归一化为相对共同起始日的涨跌幅。
+///
+/// 共同起始日取各序列最早日期中最晚的一个，使每条线都从 0% 开始；
+/// 某只股票缺少某天的数据（如停牌）时沿用前一天的收盘价。
+/// 各条线依次使用 `palette` 中的颜色。
+pub fn normalize(series: &[(String, Vec<KlineData>)], palette: &[Color]) -> Vec<CompareLine> {
+    let sorted: Vec<(&String, Vec<&KlineData>)> = series
+        .iter()
+        .filter(|(_, datas)| !datas.is_empty())
+        .map(|(name, datas)| {
+            let mut datas: Vec<&KlineData> = datas.iter().collect();
+            datas.sort_by(|a, b| a.date.cmp(&b.date));
+            (name, datas)
+        })
+        .collect();
+    let start = match sorted.iter().map(|(_, datas)| &datas[0].date).max() {
+        Some(start) => start.clone(),
+        None => return Vec::new(),
+    };
+    let dates: BTreeSet<&str> = sorted
+        .iter()
+        .flat_map(|(_, datas)| datas.iter().map(|k_line_data| k_line_data.date.as_ref()))
+        .filter(|date| *date >= start.as_ref())
+        .collect();
+
+    sorted
+        .iter()
+        .enumerate()
+        .map(|(i, (name, datas))| {
+            let mut rest = datas
+                .iter()
+                .skip_while(|k_line_data| k_line_data.date < start)
+                .peekable();
+            let base = rest
+                .peek()
+                .map(|k_line_data| k_line_data.end)
+                .unwrap_or(1.0);
+            let mut close = base;
+            let datas = dates
+                .iter()
+                .map(|date| {
+                    while let Some(k_line_data) =
+                        rest.next_if(|k_line_data| k_line_data.date.as_ref() <= *date)
+                    {
+                        close = k_line_data.end;
+                    }
+                    let percent = (close / base - 1.0) * 100.0;
+                    KlineData {
+                        date: Cow::Owned(date.to_string()),
+                        start: percent,
+                        end: percent,
+                        min: percent,
+                        max: percent,
+                        ..KlineData::default()
+                    }
+                })
+                .collect();
+            CompareLine {
+                name: name.to_string(),
+                color: palette
+                    .get(i % palette.len().max(1))
+                    .copied()
+                    .unwrap_or(Color::Reset),
+                datas,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn series(name: &str, closes: &[(&'static str, f64)]) -> (String, Vec<KlineData<'static>>) {
+        let datas = closes
+            .iter()
+            .map(|(date, end)| KlineData {
+                date: Cow::Borrowed(*date),
+                end: *end,
+                ..KlineData::default()
+            })
+            .collect();
+        (name.to_string(), datas)
+    }
+
+    fn percents(line: &CompareLine) -> Vec<(&str, f64)> {
+        line.datas
+            .iter()
+            .map(|k_line_data| {
+                (
+                    k_line_data.date.as_ref(),
+                    (k_line_data.end * 100.0).round() / 100.0,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn starts_at_latest_common_date() {
+        // 最新的在前，与接口返回的顺序一致
+        let lines = normalize(
+            &[
+                series(
+                    "a",
+                    &[
+                        ("2023-04-06", 1.2),
+                        ("2023-04-04", 1.1),
+                        ("2023-04-03", 1.0),
+                    ],
+                ),
+                series("b", &[("2023-04-06", 3.0), ("2023-04-04", 2.0)]),
+            ],
+            &[Color::Red, Color::Blue],
+        );
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            percents(&lines[0]),
+            vec![("2023-04-04", 0.0), ("2023-04-06", 9.09)]
+        );
+        assert_eq!(
+            percents(&lines[1]),
+            vec![("2023-04-04", 0.0), ("2023-04-06", 50.0)]
+        );
+        assert_eq!(lines[0].color, Color::Red);
+        assert_eq!(lines[1].color, Color::Blue);
+    }
+
+    #[test]
+    fn missing_day_keeps_previous_close() {
+        let lines = normalize(
+            &[
+                series(
+                    "a",
+                    &[
+                        ("2023-04-03", 1.0),
+                        ("2023-04-04", 1.5),
+                        ("2023-04-06", 2.0),
+                    ],
+                ),
+                series("b", &[("2023-04-03", 1.0), ("2023-04-06", 0.5)]),
+            ],
+            &[Color::Red],
+        );
+        assert_eq!(
+            percents(&lines[1]),
+            vec![
+                ("2023-04-03", 0.0),
+                ("2023-04-04", 0.0),
+                ("2023-04-06", -50.0)
+            ]
+        );
+        // 颜色不够时循环使用
+        assert_eq!(lines[1].color, Color::Red);
+    }
+
+    #[test]
+    fn empty_series_are_skipped() {
+        assert!(normalize(&[], &[Color::Red]).is_empty());
+        let lines = normalize(
+            &[series("a", &[]), series("b", &[("2023-04-03", 1.0)])],
+            &[],
+        );
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].name, "b");
+        assert_eq!(lines[0].color, Color::Reset);
+    }
+}
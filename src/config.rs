@@ -0,0 +1,36 @@
+use crate::storage;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::{io, path::PathBuf};
+
+/// 应用配置，保存在数据目录下的 `config.json`，缺少的字段使用默认值
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Config {
+    /// 交易时段内的刷新间隔（秒）
+    pub refresh_interval: u64,
+    /// 非交易时段的刷新间隔（秒）
+    pub closed_refresh_interval: u64,
+    /// 休市日，如 `2023-05-01`
+    pub holidays: Vec<NaiveDate>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            refresh_interval: 10,
+            closed_refresh_interval: 30 * 60,
+            holidays: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    fn path() -> PathBuf {
+        storage::data_dir().join("config.json")
+    }
+
+    pub fn load() -> io::Result<Config> {
+        Ok(storage::load_json(&Self::path())?.unwrap_or_default())
+    }
+}
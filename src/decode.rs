@@ -0,0 +1,543 @@
+use crate::model::StockDataWrapper;
+use serde_json::{json, Map, Value};
+use std::fmt;
+
+/// 目前能解析的数据版本，没有 `version` 字段时视为该版本
+pub const PAYLOAD_VERSION: u64 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FindingKind {
+    /// 字段缺失、为 null 或类型不符，已用默认值代替
+    Defaulted,
+    /// 缺少必需字段的条目，已丢弃
+    Dropped,
+    /// 数据不满足约束，如最低价高于开盘价
+    Invalid,
+    /// 与数据版本有关的提示
+    Version,
+}
+
+impl FindingKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FindingKind::Defaulted => "默认值",
+            FindingKind::Dropped => "已丢弃",
+            FindingKind::Invalid => "不一致",
+            FindingKind::Version => "版本",
+        }
+    }
+}
+
+/// 一类问题，同一位置的同一问题只记录一次并计数
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub kind: FindingKind,
+    /// 出问题的位置，如 `data[512690/day].pastKLines[*].cr`
+    pub path: String,
+    pub message: String,
+    pub count: usize,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}] {}: {}", self.kind.name(), self.path, self.message)?;
+        if self.count > 1 {
+            write!(f, " ×{}", self.count)?;
+        }
+        Ok(())
+    }
+}
+
+/// 解析与校验的结果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DecodeReport {
+    pub findings: Vec<Finding>,
+}
+
+impl DecodeReport {
+    pub fn add(&mut self, kind: FindingKind, path: String, message: String) {
+        let existing = self.findings.iter_mut().find(|finding| {
+            finding.kind == kind && finding.path == path && finding.message == message
+        });
+        match existing {
+            Some(finding) => finding.count += 1,
+            None => self.findings.push(Finding {
+                kind,
+                path,
+                message,
+                count: 1,
+            }),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    pub fn count(&self, kind: FindingKind) -> usize {
+        self.findings
+            .iter()
+            .filter(|finding| finding.kind == kind)
+            .count()
+    }
+}
+
+fn kind_of(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    match path {
+        "" => key.to_string(),
+        path => format!("{}.{}", path, key),
+    }
+}
+
+/// 缺失、为 null 或类型不符的字段换成默认值
+fn fill(
+    object: &mut Map<String, Value>,
+    path: &str,
+    defaults: &[(&str, Value)],
+    report: &mut DecodeReport,
+) {
+    for (key, default) in defaults {
+        let message = match object.get(*key) {
+            None => String::from("缺失"),
+            Some(Value::Null) => String::from("为 null"),
+            Some(value) if kind_of(value) != kind_of(default) => {
+                format!("应为 {}，实际为 {}", kind_of(default), kind_of(value))
+            }
+            Some(_) => continue,
+        };
+        report.add(
+            FindingKind::Defaulted,
+            join(path, key),
+            format!("{}，使用默认值 {}", message, default),
+        );
+        object.insert(key.to_string(), default.clone());
+    }
+}
+
+/// 整理 `object[key]` 中的每个条目：缺少必需字段的丢弃，其余字段补上默认值
+fn fill_each(
+    object: &mut Map<String, Value>,
+    key: &str,
+    path: &str,
+    required: &[(&str, &str)],
+    defaults: &[(&str, Value)],
+    report: &mut DecodeReport,
+) {
+    let path = format!("{}[*]", join(path, key));
+    let items = match object.get_mut(key) {
+        Some(Value::Array(items)) => items,
+        _ => return,
+    };
+    items.retain_mut(|item| {
+        let item = match item.as_object_mut() {
+            Some(item) => item,
+            None => {
+                report.add(FindingKind::Dropped, path.clone(), String::from("不是对象"));
+                return false;
+            }
+        };
+        for (key, kind) in required {
+            let actual = item.get(*key).map_or("null", kind_of);
+            if actual != *kind {
+                let message = format!("必需字段 {} 应为 {}，实际为 {}", key, kind, actual);
+                report.add(FindingKind::Dropped, path.clone(), message);
+                return false;
+            }
+        }
+        fill(item, &path, defaults, report);
+        true
+    });
+}
+
+fn fill_object(
+    object: &mut Map<String, Value>,
+    key: &str,
+    path: &str,
+    defaults: &[(&str, Value)],
+    report: &mut DecodeReport,
+) {
+    if let Some(Value::Object(child)) = object.get_mut(key) {
+        fill(child, &join(path, key), defaults, report);
+    }
+}
+
+fn fill_stock_data(stock_data: &mut Map<String, Value>, path: &str, report: &mut DecodeReport) {
+    fill(
+        stock_data,
+        path,
+        &[
+            ("name", json!("")),
+            ("bkName", json!("")),
+            ("preClose", json!(0.0)),
+            ("kLines", json!([])),
+            ("mainInOut", json!({"list": [], "max": 0})),
+            ("pastMainIns", json!([])),
+            ("pastKLines", json!([])),
+            ("macd", json!([])),
+            ("kdj", json!([])),
+            ("max", json!(0.0)),
+            ("min", json!(0.0)),
+            ("holdCount", json!(0)),
+            ("isInterested", json!(false)),
+            ("markPrice", json!(0)),
+        ],
+        report,
+    );
+    let point = [("end", json!(0.0)), ("avg", json!(0.0)), ("qty", json!(0))];
+    fill_each(stock_data, "kLines", path, &[], &point, report);
+    fill_object(
+        stock_data,
+        "mainInOut",
+        path,
+        &[("list", json!([])), ("max", json!(0))],
+        report,
+    );
+    fill_each(
+        stock_data,
+        "pastMainIns",
+        path,
+        &[("date", "string")],
+        &[
+            ("main", json!(0)),
+            ("bigger", json!(0)),
+            ("big", json!(0)),
+            ("upRate", json!(0.0)),
+            ("price", json!(0.0)),
+        ],
+        report,
+    );
+    fill_each(
+        stock_data,
+        "pastKLines",
+        path,
+        &[
+            ("date", "string"),
+            ("start", "number"),
+            ("end", "number"),
+            ("min", "number"),
+            ("max", "number"),
+        ],
+        &[
+            ("preClose", json!(0.0)),
+            ("quantity", json!(0)),
+            ("amount", json!(0.0)),
+            ("upRate", json!(0.0)),
+            ("cr", json!(0.0)),
+        ],
+        report,
+    );
+    let macd = [
+        ("dif", json!(0.0)),
+        ("dea", json!(0.0)),
+        ("macd", json!(0.0)),
+    ];
+    fill_each(stock_data, "macd", path, &[], &macd, report);
+    let kdj = [("k", json!(0.0)), ("d", json!(0.0)), ("j", json!(0.0))];
+    fill_each(stock_data, "kdj", path, &[], &kdj, report);
+    if let Some(Value::Object(report_section)) = stock_data.get_mut("report") {
+        fill(
+            report_section,
+            &join(path, "report"),
+            &[
+                ("w", json!(0.0)),
+                ("n", json!(0.0)),
+                ("amount", json!(0)),
+                ("lb", json!(0)),
+                ("mainIn", json!(0)),
+                ("bigIn", json!(0)),
+                ("bigOut", json!(0)),
+            ],
+            report,
+        );
+    }
+}
+
+/// 在解析为 `StockDataWrapper` 之前整理 json：补上缺失的可选部分，丢弃无法使用的条目
+fn normalize(value: &mut Value, report: &mut DecodeReport) {
+    let root = match value.as_object_mut() {
+        Some(root) => root,
+        None => return,
+    };
+    match root.get("version").and_then(Value::as_u64) {
+        Some(version) if version > PAYLOAD_VERSION => report.add(
+            FindingKind::Version,
+            String::from("version"),
+            format!(
+                "未知的数据版本 {}，按版本 {} 解析",
+                version, PAYLOAD_VERSION
+            ),
+        ),
+        _ => {}
+    }
+    fill(
+        root,
+        "",
+        &[
+            ("pre", json!("")),
+            ("next", json!("")),
+            ("szzs", json!({"name": "", "preClose": 0.0, "trends": []})),
+            ("data", json!([])),
+        ],
+        report,
+    );
+    fill_object(
+        root,
+        "szzs",
+        "",
+        &[
+            ("name", json!("")),
+            ("preClose", json!(0.0)),
+            ("trends", json!([])),
+        ],
+        report,
+    );
+    if let Some(Value::Object(szzs)) = root.get_mut("szzs") {
+        let point = [("end", json!(0.0)), ("avg", json!(0.0)), ("qty", json!(0))];
+        fill_each(szzs, "trends", "szzs", &[], &point, report);
+    }
+    fill_each(
+        root,
+        "data",
+        "",
+        &[("gpId", "string"), ("view", "string")],
+        &[],
+        report,
+    );
+    if let Some(Value::Array(datas)) = root.get_mut("data") {
+        for stock_data in datas.iter_mut().filter_map(Value::as_object_mut) {
+            let path = format!(
+                "data[{}/{}]",
+                stock_data["gpId"].as_str().unwrap_or_default(),
+                stock_data["view"].as_str().unwrap_or_default()
+            );
+            fill_stock_data(stock_data, &path, report);
+        }
+    }
+}
+
+/// 检查数据之间的约束：k 线价格在最高最低价之间，macd、kdj 与 k 线一一对应
+pub fn validate(wrapper: &StockDataWrapper) -> DecodeReport {
+    let mut report = DecodeReport::default();
+    for stock_data in wrapper.get_stock_datas() {
+        let path = format!("data[{}/{}]", stock_data.get_gp_id(), stock_data.get_view());
+        let k_line_datas = stock_data.get_k_line_datas();
+        for data in &k_line_datas {
+            if data.min > data.max {
+                report.add(
+                    FindingKind::Invalid,
+                    format!("{}.pastKLines[{}]", path, data.date),
+                    format!("最低价 {} 高于最高价 {}", data.min, data.max),
+                );
+            } else if data.start.min(data.end) < data.min || data.start.max(data.end) > data.max {
+                report.add(
+                    FindingKind::Invalid,
+                    format!("{}.pastKLines[{}]", path, data.date),
+                    format!(
+                        "开盘价 {} 或收盘价 {} 超出 [{}, {}]",
+                        data.start, data.end, data.min, data.max
+                    ),
+                );
+            }
+        }
+        for (name, len) in [
+            ("macd", stock_data.get_macd().len()),
+            ("kdj", stock_data.get_kdj().len()),
+        ] {
+            if len != k_line_datas.len() {
+                report.add(
+                    FindingKind::Invalid,
+                    format!("{}.{}", path, name),
+                    format!(
+                        "长度 {} 与 pastKLines 的长度 {} 不一致",
+                        len,
+                        k_line_datas.len()
+                    ),
+                );
+            }
+        }
+    }
+    report
+}
+
+/// 解析接口返回的数据，可以容忍的问题记录在报告中，与校验结果一起保存在返回值里
+pub fn decode(body: &[u8]) -> Result<StockDataWrapper, serde_json::Error> {
+    let mut value: Value = serde_json::from_slice(body)?;
+    let mut report = DecodeReport::default();
+    normalize(&mut value, &mut report);
+    let mut wrapper: StockDataWrapper = serde_json::from_value(value)?;
+    report.findings.extend(validate(&wrapper).findings);
+    wrapper.set_report(report);
+    Ok(wrapper)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Value {
+        serde_json::from_str(include_str!("../assets/resp.json")).unwrap()
+    }
+
+    fn decode_value(value: &Value) -> StockDataWrapper {
+        decode(&serde_json::to_vec(value).unwrap()).unwrap()
+    }
+
+    fn has(report: &DecodeReport, kind: FindingKind, path: &str) -> bool {
+        report
+            .findings
+            .iter()
+            .any(|finding| finding.kind == kind && finding.path == path)
+    }
+
+    #[test]
+    fn sample_decodes_without_findings() {
+        let wrapper = decode_value(&sample());
+        assert_eq!(wrapper.get_stock_datas().len(), 8);
+        assert!(
+            wrapper.get_report().is_empty(),
+            "{:?}",
+            wrapper.get_report()
+        );
+    }
+
+    #[test]
+    fn missing_kdj_is_defaulted() {
+        let mut value = sample();
+        value["data"][0].as_object_mut().unwrap().remove("kdj");
+        let wrapper = decode_value(&value);
+        let report = wrapper.get_report();
+        assert!(has(report, FindingKind::Defaulted, "data[512690/day].kdj"));
+        assert!(has(report, FindingKind::Invalid, "data[512690/day].kdj"));
+        assert!(wrapper.get_stock_data("day").unwrap().get_kdj().is_empty());
+    }
+
+    #[test]
+    fn null_main_in_is_defaulted() {
+        let mut value = sample();
+        value["data"][0]["report"]["mainIn"] = Value::Null;
+        value["data"][1]["report"] = Value::Null;
+        let wrapper = decode_value(&value);
+        let report = wrapper.get_report();
+        assert!(has(
+            report,
+            FindingKind::Defaulted,
+            "data[512690/day].report.mainIn"
+        ));
+        assert_eq!(report.findings.len(), 1);
+    }
+
+    #[test]
+    fn missing_sections_are_defaulted() {
+        let mut value = sample();
+        let root = value.as_object_mut().unwrap();
+        root.remove("szzs");
+        root.insert(String::from("pre"), Value::Null);
+        let stock_data = value["data"][0].as_object_mut().unwrap();
+        stock_data.remove("mainInOut");
+        stock_data.remove("pastMainIns");
+        stock_data.insert(String::from("holdCount"), json!("many"));
+        let wrapper = decode_value(&value);
+        let report = wrapper.get_report();
+        for path in [
+            "szzs",
+            "pre",
+            "data[512690/day].mainInOut",
+            "data[512690/day].pastMainIns",
+            "data[512690/day].holdCount",
+        ] {
+            assert!(has(report, FindingKind::Defaulted, path), "{}", path);
+        }
+        let day = wrapper.get_stock_data("day").unwrap();
+        assert!(day.get_past_main_ins().is_empty());
+        assert_eq!(day.get_latest_main_in(), None);
+    }
+
+    #[test]
+    fn missing_optional_bar_fields_are_counted_once() {
+        let mut value = sample();
+        for bar in value["data"][0]["pastKLines"].as_array_mut().unwrap() {
+            bar.as_object_mut().unwrap().remove("cr");
+        }
+        let wrapper = decode_value(&value);
+        let finding = &wrapper.get_report().findings[0];
+        assert_eq!(finding.path, "data[512690/day].pastKLines[*].cr");
+        assert_eq!(finding.count, 90);
+    }
+
+    #[test]
+    fn bars_without_required_fields_are_dropped() {
+        let mut value = sample();
+        value["data"][0]["pastKLines"][0]
+            .as_object_mut()
+            .unwrap()
+            .remove("date");
+        value["data"][0]["pastKLines"][1]["max"] = json!("n/a");
+        let wrapper = decode_value(&value);
+        let report = wrapper.get_report();
+        assert!(has(
+            report,
+            FindingKind::Dropped,
+            "data[512690/day].pastKLines[*]"
+        ));
+        // 丢弃两根后 macd 与 kdj 的长度不再一致
+        assert!(has(report, FindingKind::Invalid, "data[512690/day].macd"));
+        assert!(has(report, FindingKind::Invalid, "data[512690/day].kdj"));
+        let day = wrapper.get_stock_data("day").unwrap();
+        assert_eq!(day.get_k_line_datas().len(), 88);
+    }
+
+    #[test]
+    fn stock_datas_without_id_are_dropped() {
+        let mut value = sample();
+        value["data"][7].as_object_mut().unwrap().remove("gpId");
+        let wrapper = decode_value(&value);
+        assert_eq!(wrapper.get_stock_datas().len(), 7);
+        assert!(has(wrapper.get_report(), FindingKind::Dropped, "data[*]"));
+    }
+
+    #[test]
+    fn inconsistent_prices_are_reported() {
+        let mut value = sample();
+        value["data"][0]["pastKLines"][0]["min"] = json!(0.95);
+        value["data"][0]["pastKLines"][1]["max"] = json!(0.5);
+        value["data"][0]["pastKLines"][1]["min"] = json!(0.6);
+        let wrapper = decode_value(&value);
+        let report = wrapper.get_report();
+        assert!(has(
+            report,
+            FindingKind::Invalid,
+            "data[512690/day].pastKLines[2023-04-07]"
+        ));
+        assert!(has(
+            report,
+            FindingKind::Invalid,
+            "data[512690/day].pastKLines[2023-04-06]"
+        ));
+        assert_eq!(report.count(FindingKind::Invalid), 2);
+    }
+
+    #[test]
+    fn newer_versions_are_reported() {
+        let mut value = sample();
+        value["version"] = json!(PAYLOAD_VERSION + 1);
+        let wrapper = decode_value(&value);
+        assert!(has(wrapper.get_report(), FindingKind::Version, "version"));
+    }
+
+    #[test]
+    fn malformed_json_is_an_error() {
+        assert!(decode(b"{\"data\": [").is_err());
+        assert!(decode(b"[]").is_err());
+    }
+}
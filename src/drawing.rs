@@ -0,0 +1,387 @@
+use crate::storage;
+use custom_widget::kline::{
+    annotation::{Annotation, FIBONACCI_RATIOS},
+    klinedata::KlineData,
+};
+use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, io, path::PathBuf};
+use tui::style::Color;
+use unicode_width::UnicodeWidthStr;
+
+/// 鼠标与图形的距离不超过这么多个单元格时视为点中
+const HIT_DISTANCE: f64 = 1.0;
+
+/// 锚定在某根 K 线上的点，用日期而不是下标定位，缩放、平移后位置不变
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Anchor {
+    pub date: String,
+    pub price: f64,
+}
+
+/// 鼠标点中的是图形的哪一部分
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Handle {
+    /// 第几个锚点，拖动时只移动这个锚点
+    Anchor(usize),
+    /// 线段等其余部分，拖动时上下移动整个图形
+    Body,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Drawing {
+    Level { price: f64 },
+    TrendLine { from: Anchor, to: Anchor },
+    Fibonacci { from: Anchor, to: Anchor },
+    Note { at: Anchor, text: String },
+}
+
+impl Drawing {
+    /// 上下移动整个图形
+    pub fn shift(&mut self, delta: f64) {
+        match self {
+            Drawing::Level { price } => *price += delta,
+            Drawing::TrendLine { from, to } | Drawing::Fibonacci { from, to } => {
+                from.price += delta;
+                to.price += delta;
+            }
+            Drawing::Note { at, .. } => at.price += delta,
+        }
+    }
+
+    /// 第 `i` 个锚点，水平价位线没有锚点
+    pub fn anchor_mut(&mut self, i: usize) -> Option<&mut Anchor> {
+        match (self, i) {
+            (Drawing::TrendLine { from, .. } | Drawing::Fibonacci { from, .. }, 0) => Some(from),
+            (Drawing::TrendLine { to, .. } | Drawing::Fibonacci { to, .. }, 1) => Some(to),
+            (Drawing::Note { at, .. }, 0) => Some(at),
+            _ => None,
+        }
+    }
+
+    /// 判断图表坐标 `point`（与 [`Drawing::to_annotation`] 相同的坐标系）是否落在图形上，
+    /// 返回点中的部分与距离；`cell` 为一个单元格对应的 x 与价格，用于按单元格计算距离
+    pub fn hit(
+        &self,
+        datas: &[KlineData],
+        start: usize,
+        point: (f64, f64),
+        cell: (f64, f64),
+    ) -> Option<(Handle, f64)> {
+        let to_cell = |x: f64, y: f64| (x / cell.0, y / cell.1);
+        let (px, py) = to_cell(point.0, point.1);
+        let point_distance = |(x, y): (f64, f64)| (px - x).hypot(py - y);
+        let segment_distance = |(x1, y1): (f64, f64), (x2, y2): (f64, f64)| {
+            let (dx, dy) = (x2 - x1, y2 - y1);
+            let len = dx * dx + dy * dy;
+            let t = if len == 0.0 {
+                0.0
+            } else {
+                (((px - x1) * dx + (py - y1) * dy) / len).clamp(0.0, 1.0)
+            };
+            point_distance((x1 + t * dx, y1 + t * dy))
+        };
+        // 从 `left` 向右延伸到图表边缘的水平线
+        let ray_distance = |left: f64, y: f64| {
+            if px >= left {
+                (py - y).abs()
+            } else {
+                point_distance((left, y))
+            }
+        };
+        let (anchors, body) = match self.to_annotation(datas, start, Color::Reset)? {
+            Annotation::Level { price, .. } => (vec![], (py - price / cell.1).abs()),
+            Annotation::TrendLine { x1, y1, x2, y2, .. } => {
+                let (from, to) = (to_cell(x1, y1), to_cell(x2, y2));
+                (vec![from, to], segment_distance(from, to))
+            }
+            Annotation::Fibonacci { x1, y1, x2, y2, .. } => {
+                let (from, to) = (to_cell(x1, y1), to_cell(x2, y2));
+                let left = from.0.min(to.0);
+                let levels = FIBONACCI_RATIOS
+                    .iter()
+                    .map(|ratio| ray_distance(left, to.1 + (from.1 - to.1) * ratio))
+                    .fold(segment_distance(from, to), f64::min);
+                (vec![from, to], levels)
+            }
+            Annotation::Note { x, y, text, .. } => {
+                let at = to_cell(x, y);
+                let width = text.width().saturating_sub(1) as f64;
+                (vec![at], segment_distance(at, (at.0 + width, at.1)))
+            }
+            Annotation::Crosshair { .. } => return None,
+        };
+        let anchor = anchors
+            .into_iter()
+            .map(point_distance)
+            .enumerate()
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        match anchor {
+            Some((i, distance)) if distance <= HIT_DISTANCE => Some((Handle::Anchor(i), distance)),
+            _ if body <= HIT_DISTANCE => Some((Handle::Body, body)),
+            _ => None,
+        }
+    }
+
+    /// 转换为图表的标注，锚点日期不在 `datas` 中时返回 `None`
+    ///
+    /// `datas` 是全部 K 线，`start` 是第一根可见 K 线的下标，可见范围外的锚点坐标落在
+    /// x 轴范围之外，由标注绘制时裁剪
+    pub fn to_annotation(
+        &self,
+        datas: &[KlineData],
+        start: usize,
+        color: Color,
+    ) -> Option<Annotation<'static>> {
+        let x = |anchor: &Anchor| {
+            datas
+                .iter()
+                .position(|k_line_data| k_line_data.date == anchor.date)
+                .map(|x| x as f64 - start as f64)
+        };
+        let annotation = match self {
+            Drawing::Level { price } => Annotation::Level {
+                price: *price,
+                color,
+            },
+            Drawing::TrendLine { from, to } => Annotation::TrendLine {
+                x1: x(from)?,
+                y1: from.price,
+                x2: x(to)?,
+                y2: to.price,
+                color,
+            },
+            Drawing::Fibonacci { from, to } => Annotation::Fibonacci {
+                x1: x(from)?,
+                y1: from.price,
+                x2: x(to)?,
+                y2: to.price,
+                color,
+            },
+            Drawing::Note { at, text } => Annotation::Note {
+                x: x(at)?,
+                y: at.price,
+                text: Cow::Owned(text.clone()),
+                color,
+            },
+        };
+        Some(annotation)
+    }
+}
+
+/// 正在绘制中的工具
+#[derive(Debug, Clone, PartialEq)]
+pub enum DrawingTool {
+    TrendLine(Option<Anchor>),
+    Fibonacci(Option<Anchor>),
+}
+
+impl DrawingTool {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DrawingTool::TrendLine(_) => "趋势线",
+            DrawingTool::Fibonacci(_) => "斐波那契回撤",
+        }
+    }
+}
+
+/// 某只股票上的全部画线，按股票代码保存在数据目录下
+#[derive(Debug, Default)]
+pub struct Drawings {
+    pub stock_code: String,
+    pub items: Vec<Drawing>,
+    pub selected: Option<usize>,
+}
+
+impl Drawings {
+    fn path(stock_code: &str) -> PathBuf {
+        storage::data_dir()
+            .join("drawings")
+            .join(format!("{}.json", stock_code))
+    }
+
+    pub fn load(stock_code: &str) -> io::Result<Drawings> {
+        let items = storage::load_json(&Self::path(stock_code))?.unwrap_or_default();
+        Ok(Drawings {
+            stock_code: stock_code.to_string(),
+            items,
+            selected: None,
+        })
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        storage::save_json(&Self::path(&self.stock_code), &self.items)
+    }
+
+    pub fn add(&mut self, drawing: Drawing) {
+        self.items.push(drawing);
+        self.selected = Some(self.items.len() - 1);
+    }
+
+    pub fn remove_selected(&mut self) {
+        if let Some(i) = self.selected.take() {
+            self.items.remove(i);
+        }
+    }
+
+    pub fn select_next(&mut self) {
+        self.selected = match self.selected {
+            _ if self.items.is_empty() => None,
+            Some(i) if i + 1 < self.items.len() => Some(i + 1),
+            Some(_) => None,
+            None => Some(0),
+        };
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = match self.selected {
+            _ if self.items.is_empty() => None,
+            Some(0) => None,
+            Some(i) => Some(i - 1),
+            None => Some(self.items.len() - 1),
+        };
+    }
+
+    pub fn selected_mut(&mut self) -> Option<&mut Drawing> {
+        self.selected.and_then(|i| self.items.get_mut(i))
+    }
+
+    /// 离 `point` 最近的被点中的图形的下标与点中的部分，参数同 [`Drawing::hit`]
+    pub fn hit(
+        &self,
+        datas: &[KlineData],
+        start: usize,
+        point: (f64, f64),
+        cell: (f64, f64),
+    ) -> Option<(usize, Handle)> {
+        self.items
+            .iter()
+            .enumerate()
+            .filter_map(|(i, drawing)| {
+                let (handle, distance) = drawing.hit(datas, start, point, cell)?;
+                Some((i, handle, distance))
+            })
+            .min_by(|a, b| a.2.total_cmp(&b.2))
+            .map(|(i, handle, _)| (i, handle))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn datas() -> Vec<KlineData<'static>> {
+        ["2023-04-07", "2023-04-06", "2023-04-04", "2023-04-03"]
+            .into_iter()
+            .map(|date| KlineData {
+                date: Cow::Borrowed(date),
+                ..KlineData::default()
+            })
+            .collect()
+    }
+
+    fn anchor(date: &str, price: f64) -> Anchor {
+        Anchor {
+            date: date.to_string(),
+            price,
+        }
+    }
+
+    #[test]
+    fn anchors_relative_to_visible_start() {
+        let drawing = Drawing::TrendLine {
+            from: anchor("2023-04-07", 1.0),
+            to: anchor("2023-04-03", 2.0),
+        };
+        // 平移后第一个锚点在可见范围左侧，坐标为负数，仍然生成标注
+        match drawing.to_annotation(&datas(), 2, Color::White) {
+            Some(Annotation::TrendLine { x1, x2, .. }) => {
+                assert_eq!(x1, -2.0);
+                assert_eq!(x2, 1.0);
+            }
+            annotation => panic!("unexpected annotation: {:?}", annotation),
+        }
+    }
+
+    #[test]
+    fn unknown_anchor_date() {
+        let drawing = Drawing::Note {
+            at: anchor("2020-01-01", 1.0),
+            text: "note".to_string(),
+        };
+        assert!(drawing.to_annotation(&datas(), 0, Color::White).is_none());
+    }
+
+    #[test]
+    fn hit_anchor_and_body() {
+        let drawing = Drawing::TrendLine {
+            from: anchor("2023-04-07", 1.0),
+            to: anchor("2023-04-03", 2.0),
+        };
+        // 一个单元格对应 1 根 K 线、0.1 元
+        let cell = (1.0, 0.1);
+        let hit = |point| {
+            drawing
+                .hit(&datas(), 0, point, cell)
+                .map(|(handle, _)| handle)
+        };
+        assert_eq!(hit((0.0, 1.05)), Some(Handle::Anchor(0)));
+        assert_eq!(hit((3.0, 1.95)), Some(Handle::Anchor(1)));
+        assert_eq!(hit((1.5, 1.5)), Some(Handle::Body));
+        assert_eq!(hit((1.5, 1.9)), None);
+        // 锚点日期不在数据中时无法点中
+        assert_eq!(drawing.hit(&datas()[..2], 0, (0.0, 1.0), cell), None);
+    }
+
+    #[test]
+    fn hit_levels() {
+        let cell = (1.0, 0.1);
+        let level = Drawing::Level { price: 1.0 };
+        assert!(level.hit(&datas(), 0, (100.0, 1.05), cell).is_some());
+        assert!(level.hit(&datas(), 0, (0.0, 1.2), cell).is_none());
+        // 斐波那契回撤的各条价位线从较早的点向右延伸
+        let fibonacci = Drawing::Fibonacci {
+            from: anchor("2023-04-07", 1.0),
+            to: anchor("2023-04-03", 2.0),
+        };
+        let hit = |point| {
+            fibonacci
+                .hit(&datas(), 0, point, cell)
+                .map(|(handle, _)| handle)
+        };
+        assert_eq!(hit((10.0, 1.52)), Some(Handle::Body));
+        assert_eq!(hit((-2.0, 1.5)), None);
+    }
+
+    #[test]
+    fn hit_nearest_drawing() {
+        let drawings = Drawings {
+            items: vec![
+                Drawing::Level { price: 1.0 },
+                Drawing::Level { price: 1.08 },
+            ],
+            ..Drawings::default()
+        };
+        let hit = drawings.hit(&datas(), 0, (0.0, 1.07), (1.0, 0.1));
+        assert_eq!(hit, Some((1, Handle::Body)));
+    }
+
+    #[test]
+    fn anchors_by_index() {
+        let mut level = Drawing::Level { price: 1.0 };
+        assert!(level.anchor_mut(0).is_none());
+        let mut note = Drawing::Note {
+            at: anchor("2023-04-07", 1.0),
+            text: "note".to_string(),
+        };
+        note.anchor_mut(0).unwrap().price = 2.0;
+        assert!(note.anchor_mut(1).is_none());
+        assert_eq!(
+            note,
+            Drawing::Note {
+                at: anchor("2023-04-07", 2.0),
+                text: "note".to_string(),
+            }
+        );
+    }
+}
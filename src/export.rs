@@ -0,0 +1,366 @@
+use crate::model::StockData;
+use chrono::{Duration, NaiveDate, NaiveTime};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// 导出文件的格式
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// 每张表一个 CSV 文件
+    Csv,
+    /// 所有表合并为一个 JSON 文件
+    Json,
+}
+
+#[derive(Serialize, Debug)]
+pub struct KlineRow {
+    date: String,
+    pre_close: f32,
+    open: f32,
+    high: f32,
+    low: f32,
+    close: f32,
+    volume: i64,
+    amount: i64,
+    change: f32,
+    cr: f32,
+    avg5: f32,
+    avg10: f32,
+    avg20: f32,
+    avg30: f32,
+    avg_volume5: f32,
+    avg_volume10: f32,
+}
+
+#[derive(Serialize, Debug)]
+pub struct IntradayRow {
+    time: String,
+    price: f32,
+    avg_price: f32,
+    volume: i32,
+}
+
+#[derive(Serialize, Debug)]
+pub struct MacdRow {
+    date: String,
+    dif: f32,
+    dea: f32,
+    macd: f32,
+}
+
+#[derive(Serialize, Debug)]
+pub struct KdjRow {
+    date: String,
+    k: f32,
+    d: f32,
+    j: f32,
+}
+
+#[derive(Serialize, Debug)]
+pub struct FlowRow {
+    date: String,
+    main: i32,
+    bigger: i32,
+    big: i32,
+    change: f32,
+    price: f32,
+}
+
+/// 一只股票某个 k 线级别下可导出的全部数据，均按时间升序
+#[derive(Serialize, Debug)]
+pub struct ExportTables {
+    kline: Vec<KlineRow>,
+    intraday: Vec<IntradayRow>,
+    macd: Vec<MacdRow>,
+    kdj: Vec<KdjRow>,
+    flows: Vec<FlowRow>,
+}
+
+/// 转换为 ISO 8601 格式，`2023-04-07 15:00` 变为 `2023-04-07T15:00`
+fn iso_date(date: &str) -> String {
+    date.trim().replacen(' ', "T", 1)
+}
+
+/// 分时数据第 `index` 分钟对应的时间，上午 09:30–11:30，下午 13:01–15:00
+fn intraday_time(date: Option<NaiveDate>, index: usize) -> String {
+    let minutes = if index <= 120 {
+        index as i64
+    } else {
+        index as i64 + 90
+    };
+    let time = NaiveTime::from_hms_opt(9, 30, 0).unwrap() + Duration::minutes(minutes);
+    match date {
+        Some(date) => format!("{}T{}", date, time.format("%H:%M")),
+        None => time.format("%H:%M").to_string(),
+    }
+}
+
+impl ExportTables {
+    pub fn from_stock_data(stock_data: &StockData) -> ExportTables {
+        let k_line_datas = stock_data.get_k_line_datas();
+        let dates: Vec<String> = k_line_datas
+            .iter()
+            .map(|data| iso_date(&data.date))
+            .collect();
+        let mut kline: Vec<KlineRow> = k_line_datas
+            .iter()
+            .zip(&dates)
+            .map(|(data, date)| KlineRow {
+                date: date.clone(),
+                pre_close: data.pre_close as f32,
+                open: data.start as f32,
+                high: data.max as f32,
+                low: data.min as f32,
+                close: data.end as f32,
+                volume: data.quantity,
+                amount: data.amount,
+                change: data.up_rate as f32,
+                cr: data.cr as f32,
+                avg5: data.avg5 as f32,
+                avg10: data.avg10 as f32,
+                avg20: data.avg20 as f32,
+                avg30: data.avg30 as f32,
+                avg_volume5: data.avg_q5 as f32,
+                avg_volume10: data.avg_q10 as f32,
+            })
+            .collect();
+        let mut macd: Vec<MacdRow> = stock_data
+            .get_macd()
+            .iter()
+            .zip(&dates)
+            .map(|(macd, date)| MacdRow {
+                date: date.clone(),
+                dif: macd.get_dif(),
+                dea: macd.get_dea(),
+                macd: macd.get_macd(),
+            })
+            .collect();
+        let mut kdj: Vec<KdjRow> = stock_data
+            .get_kdj()
+            .iter()
+            .zip(&dates)
+            .map(|(kdj, date)| KdjRow {
+                date: date.clone(),
+                k: kdj.get_k(),
+                d: kdj.get_d(),
+                j: kdj.get_j(),
+            })
+            .collect();
+        if stock_data.is_newest_first() {
+            kline.reverse();
+            macd.reverse();
+            kdj.reverse();
+        }
+        let trade_date = dates
+            .iter()
+            .max()
+            .and_then(|date| NaiveDate::parse_from_str(date.get(..10)?, "%Y-%m-%d").ok());
+        let intraday = stock_data
+            .get_k_lines()
+            .iter()
+            .enumerate()
+            .map(|(index, k_line)| IntradayRow {
+                time: intraday_time(trade_date, index),
+                price: k_line.get_end(),
+                avg_price: k_line.get_avg(),
+                volume: k_line.get_qty(),
+            })
+            .collect();
+        let mut flows: Vec<FlowRow> = stock_data
+            .get_past_main_ins()
+            .iter()
+            .map(|main_ins| FlowRow {
+                date: iso_date(main_ins.get_date()),
+                main: main_ins.get_main(),
+                bigger: main_ins.get_bigger(),
+                big: main_ins.get_big(),
+                change: main_ins.get_up_rate(),
+                price: main_ins.get_price(),
+            })
+            .collect();
+        flows.sort_by(|a, b| a.date.cmp(&b.date));
+        ExportTables {
+            kline,
+            intraday,
+            macd,
+            kdj,
+            flows,
+        }
+    }
+}
+
+fn write_csv<T: Serialize>(path: &Path, rows: &[T]) -> io::Result<()> {
+    let mut writer = csv::Writer::from_path(path)?;
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    writer.flush()
+}
+
+/// 把 `stock_data` 导出到 `dir` 下，返回写入的文件
+///
+/// CSV 为 `<代码>_<级别>_<表>.csv`，表有 kline、intraday、macd、kdj、flows；
+/// JSON 为 `<代码>_<级别>.json`，每张表是其中的一个字段
+pub fn export(
+    stock_code: &str,
+    stock_data: &StockData,
+    format: Format,
+    dir: &Path,
+) -> io::Result<Vec<PathBuf>> {
+    fs::create_dir_all(dir)?;
+    let tables = ExportTables::from_stock_data(stock_data);
+    let prefix = format!("{}_{}", stock_code, stock_data.get_view());
+    match format {
+        Format::Csv => {
+            let path = |table: &str| dir.join(format!("{}_{}.csv", prefix, table));
+            let paths = vec![
+                path("kline"),
+                path("intraday"),
+                path("macd"),
+                path("kdj"),
+                path("flows"),
+            ];
+            write_csv(&paths[0], &tables.kline)?;
+            write_csv(&paths[1], &tables.intraday)?;
+            write_csv(&paths[2], &tables.macd)?;
+            write_csv(&paths[3], &tables.kdj)?;
+            write_csv(&paths[4], &tables.flows)?;
+            Ok(paths)
+        }
+        Format::Json => {
+            let path = dir.join(format!("{}.json", prefix));
+            fs::write(&path, serde_json::to_string_pretty(&tables)?)?;
+            Ok(vec![path])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::decode;
+    use crate::model::StockDataWrapper;
+    use std::env;
+
+    fn sample() -> StockDataWrapper {
+        decode(include_bytes!("../assets/resp.json")).unwrap()
+    }
+
+    fn dates<'a>(rows: impl Iterator<Item = &'a String>) -> Vec<&'a str> {
+        rows.map(String::as_str).collect()
+    }
+
+    /// macd、kdj 与 k 线按同一组日期升序排列
+    fn assert_aligned(tables: &ExportTables) {
+        let kline = dates(tables.kline.iter().map(|row| &row.date));
+        assert_eq!(dates(tables.macd.iter().map(|row| &row.date)), kline);
+        assert_eq!(dates(tables.kdj.iter().map(|row| &row.date)), kline);
+        assert!(kline.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn iso_dates() {
+        assert_eq!(iso_date("2023-04-07"), "2023-04-07");
+        assert_eq!(iso_date(" 2023-04-07 15:00 "), "2023-04-07T15:00");
+        let date = NaiveDate::from_ymd_opt(2023, 4, 7);
+        assert_eq!(intraday_time(date, 0), "2023-04-07T09:30");
+        assert_eq!(intraday_time(date, 120), "2023-04-07T11:30");
+        assert_eq!(intraday_time(date, 121), "2023-04-07T13:01");
+        assert_eq!(intraday_time(date, 240), "2023-04-07T15:00");
+        assert_eq!(intraday_time(None, 1), "09:31");
+    }
+
+    #[test]
+    fn tables_from_sample() {
+        let wrapper = sample();
+        let stock_data = wrapper.get_stock_data("day").unwrap();
+        let tables = ExportTables::from_stock_data(stock_data);
+        assert_eq!(tables.kline.len(), 90);
+        assert_aligned(&tables);
+        let last = tables.kline.last().unwrap();
+        assert_eq!(last.date, "2023-04-07");
+        // 接口按日期降序返回，第一条指标对应最新的 k 线
+        assert_eq!(
+            tables.macd.last().unwrap().dif,
+            stock_data.get_macd()[0].get_dif()
+        );
+        assert_eq!(
+            tables.kdj.last().unwrap().k,
+            stock_data.get_kdj()[0].get_k()
+        );
+        assert_eq!(tables.intraday.len(), 241);
+        assert_eq!(tables.intraday[0].time, "2023-04-07T09:30");
+        assert_eq!(tables.intraday[240].time, "2023-04-07T15:00");
+        let flows = dates(tables.flows.iter().map(|row| &row.date));
+        assert!(flows.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    /// 并入更早一页后 k 线变长，macd、kdj 仍与每根 k 线的日期对应
+    #[test]
+    fn tables_after_appending_older_page() {
+        let mut wrapper = sample();
+        for stock_data in wrapper.get_stock_datas_mut() {
+            let mut bars = stock_data.take_past_k_lines();
+            bars.truncate(30);
+            stock_data.set_past_k_lines(bars);
+            stock_data.recompute_indicators();
+        }
+        wrapper.append_older(sample());
+        let tables = ExportTables::from_stock_data(wrapper.get_stock_data("day").unwrap());
+        assert_eq!(tables.kline.len(), 90);
+        assert_eq!(tables.macd.len(), 90);
+        assert_eq!(tables.kdj.len(), 90);
+        assert_aligned(&tables);
+    }
+
+    #[test]
+    fn export_files() {
+        let dir = env::temp_dir().join(format!("stock_rs_exports_{}", std::process::id()));
+        let wrapper = sample();
+        let stock_data = wrapper.get_stock_data("day").unwrap();
+        let csv_paths = export("SH512690", stock_data, Format::Csv, &dir).unwrap();
+        let json_paths = export("SH512690", stock_data, Format::Json, &dir).unwrap();
+        let read = |path: &PathBuf| fs::read_to_string(path).unwrap();
+        let csv: Vec<String> = csv_paths.iter().map(read).collect();
+        let json: serde_json::Value = serde_json::from_str(&read(&json_paths[0])).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let names: Vec<_> = csv_paths
+            .iter()
+            .map(|path| path.file_name().unwrap())
+            .collect();
+        assert_eq!(names[0], "SH512690_day_kline.csv");
+        assert_eq!(names[4], "SH512690_day_flows.csv");
+        let headers: Vec<&str> = csv
+            .iter()
+            .map(|content| content.lines().next().unwrap())
+            .collect();
+        assert_eq!(
+            headers,
+            [
+                "date,pre_close,open,high,low,close,volume,amount,change,cr,avg5,avg10,avg20,avg30,avg_volume5,avg_volume10",
+                "time,price,avg_price,volume",
+                "date,dif,dea,macd",
+                "date,k,d,j",
+                "date,main,bigger,big,change,price",
+            ]
+        );
+        assert!(csv[0].lines().last().unwrap().starts_with("2023-04-07,"));
+        assert!(csv[1]
+            .lines()
+            .nth(1)
+            .unwrap()
+            .starts_with("2023-04-07T09:30,"));
+        assert_eq!(csv[2].lines().count(), 91);
+
+        assert_eq!(json_paths[0].file_name().unwrap(), "SH512690_day.json");
+        assert_eq!(json["kline"].as_array().unwrap().len(), 90);
+        assert_eq!(json["kline"][89]["date"], "2023-04-07");
+        assert_eq!(json["macd"][89]["date"], "2023-04-07");
+        assert_eq!(json["intraday"][121]["time"], "2023-04-07T13:01");
+    }
+}
@@ -0,0 +1,163 @@
+use crate::{
+    model::{KLinesData, StockDataWrapper},
+    storage,
+};
+use std::{collections::BTreeMap, io, path::PathBuf};
+
+/// 本地保存的历史 k 线，每只股票每个级别一个文件 `history/<gpId>/<级别>.json`，按日期升序
+fn path(gp_id: &str, view: &str) -> PathBuf {
+    storage::data_dir()
+        .join("history")
+        .join(gp_id)
+        .join(format!("{}.json", view))
+}
+
+fn load(gp_id: &str, view: &str) -> io::Result<Vec<KLinesData>> {
+    Ok(storage::load_json(&path(gp_id, view))?.unwrap_or_default())
+}
+
+/// 把新数据并入已保存的历史，同一日期以新数据为准，返回按日期升序的全部 k 线，
+/// 以及是否有新增或变化的 k 线
+fn merge(stored: Vec<KLinesData>, fetched: Vec<KLinesData>) -> (Vec<KLinesData>, bool) {
+    let mut bars: BTreeMap<String, KLinesData> = stored
+        .into_iter()
+        .map(|bar| (bar.get_date().to_string(), bar))
+        .collect();
+    let mut changed = false;
+    for bar in fetched {
+        let date = bar.get_date().to_string();
+        if bars.get(&date) != Some(&bar) {
+            bars.insert(date, bar);
+            changed = true;
+        }
+    }
+    (bars.into_values().collect(), changed)
+}
+
+/// 保存 `wrapper` 中每只股票每个级别的 k 线，并用保存的全部历史替换其中的 `past_k_lines`
+///
+/// 接口每次都返回最近 90 根，合并后超出窗口的旧数据仍然保留，图表可以一直往前看。
+/// 接口没有按日期过滤的参数，无法只请求上次保存之后的 k 线，所以总是请求最新一页再合并；
+/// 没有新增或变化的 k 线时不重写文件。
+/// 合并后 k 线比接口给出的 macd、kdj 长时按全部历史重新计算指标，保持一一对应
+pub fn update(wrapper: &mut StockDataWrapper) -> io::Result<()> {
+    for stock_data in wrapper.get_stock_datas_mut() {
+        let fetched = stock_data.take_past_k_lines();
+        // 接口按日期降序返回，不足两根无法判断时也按降序
+        let newest_first = match (fetched.first(), fetched.last()) {
+            (Some(first), Some(last)) if fetched.len() > 1 => first.get_date() > last.get_date(),
+            _ => true,
+        };
+        let gp_id = stock_data.get_gp_id().to_string();
+        let view = stock_data.get_view().to_string();
+        let stored = match load(&gp_id, &view) {
+            Ok(stored) => stored,
+            Err(err) => {
+                stock_data.set_past_k_lines(fetched);
+                return Err(err);
+            }
+        };
+        let (mut bars, changed) = merge(stored, fetched);
+        let saved = if changed {
+            storage::save_json(&path(&gp_id, &view), &bars)
+        } else {
+            Ok(())
+        };
+        if newest_first {
+            bars.reverse();
+        }
+        let len = bars.len();
+        stock_data.set_past_k_lines(bars);
+        if stock_data.get_macd().len() != len || stock_data.get_kdj().len() != len {
+            stock_data.recompute_indicators();
+        }
+        saved?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::decode;
+    use custom_widget::kline::klinedata::KlineData;
+
+    fn bars(dates: &[&str], end: f32) -> Vec<KLinesData> {
+        let bars: Vec<serde_json::Value> = dates
+            .iter()
+            .map(|date| {
+                serde_json::json!({
+                    "date": date, "preClose": end, "start": end, "end": end, "min": end,
+                    "max": end, "quantity": 0, "amount": 0.0, "upRate": 0.0, "cr": 0.0
+                })
+            })
+            .collect();
+        serde_json::from_value(serde_json::Value::Array(bars)).unwrap()
+    }
+
+    fn dates(bars: &[KLinesData]) -> Vec<&str> {
+        bars.iter().map(KLinesData::get_date).collect()
+    }
+
+    #[test]
+    fn merge_keeps_old_bars_and_prefers_fetched() {
+        let stored = bars(&["2023-04-03", "2023-04-04", "2023-04-06"], 1.0);
+        // 接口按日期降序返回
+        let fetched = bars(&["2023-04-07", "2023-04-06"], 2.0);
+        let (merged, changed) = merge(stored, fetched);
+        assert!(changed);
+        assert_eq!(
+            dates(&merged),
+            ["2023-04-03", "2023-04-04", "2023-04-06", "2023-04-07"]
+        );
+        let ends: Vec<f64> = merged.iter().map(|bar| KlineData::from(bar).end).collect();
+        assert_eq!(ends, [1.0, 1.0, 2.0, 2.0]);
+    }
+
+    /// 再次拿到相同的 k 线时不需要重写文件，收盘价变化或有新的一天时需要
+    #[test]
+    fn merge_reports_changes() {
+        let stored = || bars(&["2023-04-06", "2023-04-07"], 1.0);
+        let (_, changed) = merge(stored(), bars(&["2023-04-07", "2023-04-06"], 1.0));
+        assert!(!changed);
+        let (_, changed) = merge(stored(), bars(&["2023-04-07"], 2.0));
+        assert!(changed);
+        let (merged, changed) = merge(stored(), bars(&["2023-04-10", "2023-04-07"], 1.0));
+        assert!(changed);
+        assert_eq!(dates(&merged), ["2023-04-06", "2023-04-07", "2023-04-10"]);
+    }
+
+    #[test]
+    fn merge_with_empty_sides() {
+        assert_eq!(merge(Vec::new(), Vec::new()), (Vec::new(), false));
+        let (merged, _) = merge(Vec::new(), bars(&["2023-04-07", "2023-04-06"], 1.0));
+        assert_eq!(dates(&merged), ["2023-04-06", "2023-04-07"]);
+        let (merged, changed) = merge(bars(&["2023-04-06"], 1.0), Vec::new());
+        assert!(!changed);
+        assert_eq!(dates(&merged), ["2023-04-06"]);
+    }
+
+    /// 合并出的 k 线比接口的 90 根长时，macd、kdj 按全部历史重新计算并保持对齐
+    #[test]
+    fn indicators_follow_merged_bars() {
+        let mut wrapper = decode(include_bytes!("../assets/resp.json")).unwrap();
+        let stock_data = wrapper
+            .get_stock_datas_mut()
+            .iter_mut()
+            .find(|stock_data| stock_data.get_view() == "day")
+            .unwrap();
+        assert!(stock_data.is_newest_first());
+        let latest = stock_data.get_macd()[0].get_dif();
+        let fetched = stock_data.take_past_k_lines();
+        let (mut merged, _) = merge(bars(&["2000-01-03", "2000-01-04"], 0.9), fetched);
+        merged.reverse();
+        let len = merged.len();
+        stock_data.set_past_k_lines(merged);
+        stock_data.recompute_indicators();
+        assert_eq!(stock_data.get_macd().len(), len);
+        assert_eq!(stock_data.get_kdj().len(), len);
+        // 降序排列：最早一天在最后，dif 从 0 开始；最新一天与接口给出的值一致
+        assert_eq!(stock_data.get_macd()[len - 1].get_dif(), 0.0);
+        assert!((stock_data.get_macd()[0].get_dif() - latest).abs() < 0.001);
+    }
+}
@@ -0,0 +1,607 @@
+use crate::storage;
+use chrono::{NaiveDate, NaiveDateTime};
+use custom_widget::kline::klinedata::KlineData;
+use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+/// CSV 中的一列：按表头名称或从 0 开始的序号
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum ColumnRef {
+    Index(usize),
+    Name(String),
+}
+
+impl ColumnRef {
+    /// 纯数字视为序号，否则视为表头名称
+    pub fn parse(text: &str) -> ColumnRef {
+        match text.parse() {
+            Ok(index) => ColumnRef::Index(index),
+            Err(_) => ColumnRef::Name(text.to_string()),
+        }
+    }
+
+    fn resolve(&self, headers: Option<&csv::StringRecord>) -> Result<usize, String> {
+        match (self, headers) {
+            (ColumnRef::Index(index), _) => Ok(*index),
+            (ColumnRef::Name(name), Some(headers)) => headers
+                .iter()
+                .position(|header| header.trim().eq_ignore_ascii_case(name))
+                .ok_or_else(|| format!("找不到列 {}", name)),
+            (ColumnRef::Name(name), None) => Err(format!("没有表头时列 {} 必须用序号指定", name)),
+        }
+    }
+}
+
+/// 各字段对应的列，成交量与成交额可以缺省
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ColumnMapping {
+    pub date: ColumnRef,
+    pub open: ColumnRef,
+    pub high: ColumnRef,
+    pub low: ColumnRef,
+    pub close: ColumnRef,
+    pub volume: Option<ColumnRef>,
+    pub amount: Option<ColumnRef>,
+}
+
+impl Default for ColumnMapping {
+    fn default() -> Self {
+        let name = |name: &str| ColumnRef::Name(name.to_string());
+        ColumnMapping {
+            date: name("date"),
+            open: name("open"),
+            high: name("high"),
+            low: name("low"),
+            close: name("close"),
+            volume: Some(name("volume")),
+            amount: Some(name("amount")),
+        }
+    }
+}
+
+impl ColumnMapping {
+    /// 按 `字段=列` 修改映射，如 `close=收盘`、`date=0`，`volume=` 表示没有该列
+    pub fn set(&mut self, assignment: &str) -> Result<(), String> {
+        let (field, column) = assignment
+            .split_once('=')
+            .ok_or_else(|| format!("无效的列映射 {}，应为 字段=列", assignment))?;
+        let column = column.trim();
+        let optional = || (!column.is_empty()).then(|| ColumnRef::parse(column));
+        match field.trim() {
+            "date" => self.date = ColumnRef::parse(column),
+            "open" => self.open = ColumnRef::parse(column),
+            "high" => self.high = ColumnRef::parse(column),
+            "low" => self.low = ColumnRef::parse(column),
+            "close" => self.close = ColumnRef::parse(column),
+            "volume" => self.volume = optional(),
+            "amount" => self.amount = optional(),
+            field => return Err(format!("未知的字段 {}", field)),
+        }
+        Ok(())
+    }
+}
+
+/// 导入设置
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ImportOptions {
+    pub delimiter: char,
+    /// chrono 格式，如 `%Y-%m-%d`、`%Y/%m/%d %H:%M`
+    pub date_format: String,
+    pub has_headers: bool,
+    pub columns: ColumnMapping,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        ImportOptions {
+            delimiter: ',',
+            date_format: String::from("%Y-%m-%d"),
+            has_headers: true,
+            columns: ColumnMapping::default(),
+        }
+    }
+}
+
+/// 导入失败的原因，`line` 为 CSV 文件中的行号，0 表示与具体行无关
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportError {
+    pub line: u64,
+    pub message: String,
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "第 {} 行: {}", self.line, self.message)
+        }
+    }
+}
+
+/// 一根 k 线，日期为 ISO 8601 格式
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Bar {
+    pub date: String,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: i64,
+    pub amount: i64,
+}
+
+struct Columns {
+    date: usize,
+    open: usize,
+    high: usize,
+    low: usize,
+    close: usize,
+    volume: Option<usize>,
+    amount: Option<usize>,
+}
+
+fn parse_date(text: &str, format: &str) -> Option<String> {
+    if let Ok(date) = NaiveDate::parse_from_str(text, format) {
+        return Some(date.format("%Y-%m-%d").to_string());
+    }
+    NaiveDateTime::parse_from_str(text, format)
+        .ok()
+        .map(|time| time.format("%Y-%m-%dT%H:%M").to_string())
+}
+
+fn parse_bar(
+    record: &csv::StringRecord,
+    columns: &Columns,
+    date_format: &str,
+) -> Result<Bar, String> {
+    let field = |index: usize| {
+        record
+            .get(index)
+            .map(str::trim)
+            .ok_or_else(|| format!("缺少第 {} 列", index))
+    };
+    let number = |index: usize, name: &str| -> Result<f64, String> {
+        let text = field(index)?;
+        text.parse::<f64>()
+            .ok()
+            .filter(|value| value.is_finite())
+            .ok_or_else(|| format!("{} 不是有效的数字: {}", name, text))
+    };
+    let optional = |index: Option<usize>, name: &str| match index {
+        Some(index) => number(index, name).map(|value| value.round() as i64),
+        None => Ok(0),
+    };
+    let text = field(columns.date)?;
+    let date = parse_date(text, date_format)
+        .ok_or_else(|| format!("日期 {} 与格式 {} 不符", text, date_format))?;
+    let bar = Bar {
+        date,
+        open: number(columns.open, "开盘价")?,
+        high: number(columns.high, "最高价")?,
+        low: number(columns.low, "最低价")?,
+        close: number(columns.close, "收盘价")?,
+        volume: optional(columns.volume, "成交量")?,
+        amount: optional(columns.amount, "成交额")?,
+    };
+    if bar.low <= 0.0 {
+        return Err(format!("最低价 {} 必须大于 0", bar.low));
+    }
+    if bar.high < bar.open.max(bar.close) || bar.low > bar.open.min(bar.close) {
+        return Err(format!(
+            "价格不一致: 开 {} 高 {} 低 {} 收 {}",
+            bar.open, bar.high, bar.low, bar.close
+        ));
+    }
+    if bar.volume < 0 || bar.amount < 0 {
+        return Err(String::from("成交量和成交额不能为负"));
+    }
+    Ok(bar)
+}
+
+/// 记录在文件中的行号。csv 跳过空行后自己的行号会偏小，
+/// 并且字节位置指向被跳过的空行，这里跳过换行后按字节位置重新计算
+fn line_at(content: &[u8], position: Option<&csv::Position>) -> u64 {
+    let byte = match position {
+        Some(position) => (position.byte() as usize).min(content.len()),
+        None => return 0,
+    };
+    let start = byte
+        + content[byte..]
+            .iter()
+            .take_while(|b| matches!(b, b'\r' | b'\n'))
+            .count();
+    content[..start].iter().filter(|b| **b == b'\n').count() as u64 + 1
+}
+
+/// 读取 CSV，返回按日期升序的 k 线；有任何一行无效时返回全部错误
+pub fn read_csv<R: io::Read>(
+    mut reader: R,
+    options: &ImportOptions,
+) -> Result<Vec<Bar>, Vec<ImportError>> {
+    let mut content = Vec::new();
+    reader.read_to_end(&mut content).map_err(|err| {
+        vec![ImportError {
+            line: 0,
+            message: err.to_string(),
+        }]
+    })?;
+    let delimiter = u8::try_from(options.delimiter).map_err(|_| {
+        vec![ImportError {
+            line: 0,
+            message: format!("分隔符 {} 必须是单字节字符", options.delimiter),
+        }]
+    })?;
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(options.has_headers)
+        .flexible(true)
+        .from_reader(content.as_slice());
+    let headers = match options.has_headers {
+        true => Some(reader.headers().cloned().map_err(|err| {
+            vec![ImportError {
+                line: 1,
+                message: err.to_string(),
+            }]
+        })?),
+        false => None,
+    };
+    let mapping = &options.columns;
+    let resolve = |column: &ColumnRef| {
+        column
+            .resolve(headers.as_ref())
+            .map_err(|message| vec![ImportError { line: 0, message }])
+    };
+    // 成交量、成交额找不到时按缺省处理
+    let resolve_optional = |column: &Option<ColumnRef>| {
+        column
+            .as_ref()
+            .and_then(|column| column.resolve(headers.as_ref()).ok())
+    };
+    let columns = Columns {
+        date: resolve(&mapping.date)?,
+        open: resolve(&mapping.open)?,
+        high: resolve(&mapping.high)?,
+        low: resolve(&mapping.low)?,
+        close: resolve(&mapping.close)?,
+        volume: resolve_optional(&mapping.volume),
+        amount: resolve_optional(&mapping.amount),
+    };
+
+    let mut bars = Vec::new();
+    let mut errors = Vec::new();
+    let mut dates = HashSet::new();
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(err) => {
+                let line = line_at(&content, err.position());
+                errors.push(ImportError {
+                    line,
+                    message: err.to_string(),
+                });
+                continue;
+            }
+        };
+        let line = line_at(&content, record.position());
+        if record.iter().all(|field| field.trim().is_empty()) {
+            continue;
+        }
+        match parse_bar(&record, &columns, &options.date_format) {
+            Ok(bar) if !dates.insert(bar.date.clone()) => errors.push(ImportError {
+                line,
+                message: format!("日期 {} 重复", bar.date),
+            }),
+            Ok(bar) => bars.push(bar),
+            Err(message) => errors.push(ImportError { line, message }),
+        }
+    }
+    if bars.is_empty() && errors.is_empty() {
+        errors.push(ImportError {
+            line: 0,
+            message: String::from("文件中没有数据"),
+        });
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    bars.sort_by(|a, b| a.date.cmp(&b.date));
+    Ok(bars)
+}
+
+/// 导入的历史 k 线，保存在数据目录下的 `imports/<代码>.json`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ImportedSeries {
+    pub stock_code: String,
+    pub stock_name: String,
+    pub bars: Vec<Bar>,
+}
+
+fn moving_average(values: &[f64], end: usize, days: usize) -> f64 {
+    let start = (end + 1).saturating_sub(days);
+    let window = &values[start..=end];
+    window.iter().sum::<f64>() / window.len() as f64
+}
+
+impl ImportedSeries {
+    fn dir() -> PathBuf {
+        storage::data_dir().join("imports")
+    }
+
+    pub fn path(stock_code: &str) -> PathBuf {
+        Self::dir().join(format!("{}.json", stock_code))
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        storage::save_json(&Self::path(&self.stock_code), self)
+    }
+
+    /// 读取全部导入的数据，按代码排序
+    pub fn load_all() -> io::Result<Vec<ImportedSeries>> {
+        Self::load_dir(&Self::dir())
+    }
+
+    /// 读取 `dir` 下的全部 JSON 文件，无法读取或解析的文件记录日志后跳过
+    fn load_dir(dir: &Path) -> io::Result<Vec<ImportedSeries>> {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+        let mut series = Vec::new();
+        for entry in entries {
+            let path = match entry {
+                Ok(entry) => entry.path(),
+                Err(err) => {
+                    log::error!("read {} failed: {}", dir.display(), err);
+                    continue;
+                }
+            };
+            if path.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+            match storage::load_json::<ImportedSeries>(&path) {
+                Ok(loaded) => series.extend(loaded),
+                Err(err) => log::error!("load imported series {} failed: {}", path.display(), err),
+            }
+        }
+        series.sort_by(|a, b| a.stock_code.cmp(&b.stock_code));
+        Ok(series)
+    }
+
+    /// 从 CSV 文件导入
+    pub fn from_csv(
+        path: &Path,
+        stock_code: &str,
+        stock_name: &str,
+        options: &ImportOptions,
+    ) -> Result<ImportedSeries, Vec<ImportError>> {
+        let file = fs::File::open(path).map_err(|err| {
+            vec![ImportError {
+                line: 0,
+                message: format!("无法打开 {}: {}", path.display(), err),
+            }]
+        })?;
+        Ok(ImportedSeries {
+            stock_code: stock_code.to_string(),
+            stock_name: stock_name.to_string(),
+            bars: read_csv(file, options)?,
+        })
+    }
+
+    /// 转换为图表使用的数据，与接口返回的一样按日期降序，并补上昨收、涨幅和均线
+    pub fn k_line_datas(&self) -> Vec<KlineData<'static>> {
+        let closes: Vec<f64> = self.bars.iter().map(|bar| bar.close).collect();
+        let volumes: Vec<f64> = self.bars.iter().map(|bar| bar.volume as f64).collect();
+        let mut k_line_datas: Vec<KlineData<'static>> = self
+            .bars
+            .iter()
+            .enumerate()
+            .map(|(i, bar)| {
+                let pre_close = if i > 0 { closes[i - 1] } else { bar.open };
+                KlineData {
+                    date: Cow::Owned(bar.date.clone()),
+                    pre_close,
+                    start: bar.open,
+                    end: bar.close,
+                    min: bar.low,
+                    max: bar.high,
+                    quantity: bar.volume,
+                    amount: bar.amount,
+                    up_rate: (bar.close - pre_close) / pre_close * 100.0,
+                    avg5: moving_average(&closes, i, 5),
+                    avg10: moving_average(&closes, i, 10),
+                    avg20: moving_average(&closes, i, 20),
+                    avg30: moving_average(&closes, i, 30),
+                    avg_q5: moving_average(&volumes, i, 5),
+                    avg_q10: moving_average(&volumes, i, 10),
+                    ..KlineData::default()
+                }
+            })
+            .collect();
+        k_line_datas.reverse();
+        k_line_datas
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn read(csv: &str, options: &ImportOptions) -> Result<Vec<Bar>, Vec<ImportError>> {
+        read_csv(csv.as_bytes(), options)
+    }
+
+    fn bar(date: &str, close: f64) -> Bar {
+        Bar {
+            date: date.to_string(),
+            open: close,
+            high: close,
+            low: close,
+            close,
+            volume: 0,
+            amount: 0,
+        }
+    }
+
+    #[test]
+    fn columns_by_name() {
+        let csv = "Date,Close,Open,High,Low,Volume\n\
+                   2023-04-07,0.900,0.902,0.912,0.894,7707000\n\
+                   2023-04-06,0.904,0.919,0.924,0.901,8000000\n";
+        let bars = read(csv, &ImportOptions::default()).unwrap();
+        // 表头名称不区分大小写，结果按日期升序，缺少的成交额为 0
+        assert_eq!(bars.len(), 2);
+        assert_eq!(bars[0].date, "2023-04-06");
+        assert_eq!(
+            bars[1],
+            Bar {
+                date: String::from("2023-04-07"),
+                open: 0.902,
+                high: 0.912,
+                low: 0.894,
+                close: 0.900,
+                volume: 7707000,
+                amount: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn columns_by_index() {
+        let mut options = ImportOptions {
+            delimiter: ';',
+            date_format: String::from("%Y/%m/%d"),
+            has_headers: false,
+            ..ImportOptions::default()
+        };
+        for assignment in [
+            "date=0", "open=1", "high=2", "low=3", "close=4", "volume=", "amount=5",
+        ] {
+            options.columns.set(assignment).unwrap();
+        }
+        let bars = read("2023/04/07;1;2;0.5;1.5;100\n", &options).unwrap();
+        assert_eq!(bars[0].date, "2023-04-07");
+        assert_eq!(bars[0].close, 1.5);
+        assert_eq!(bars[0].volume, 0);
+        assert_eq!(bars[0].amount, 100);
+        assert!(options.columns.set("price=1").is_err());
+        assert!(options.columns.set("close").is_err());
+    }
+
+    #[test]
+    fn missing_column() {
+        let errors = read(
+            "date,open,high,low\n2023-04-07,1,1,1\n",
+            &ImportOptions::default(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            errors,
+            [ImportError {
+                line: 0,
+                message: String::from("找不到列 close")
+            }]
+        );
+        // 没有表头时只能用序号
+        let options = ImportOptions {
+            has_headers: false,
+            ..ImportOptions::default()
+        };
+        assert_eq!(
+            read("2023-04-07,1,1,1,1\n", &options).unwrap_err()[0].line,
+            0
+        );
+    }
+
+    #[test]
+    fn bad_number_reports_line() {
+        // 空行也计入行号
+        let csv = "date,open,high,low,close\n\
+                   2023-04-06,1,1,1,1\n\
+                   \r\n\
+                   \n\
+                   2023-04-07,1,1,1,abc\n";
+        let errors = read(csv, &ImportOptions::default()).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 5);
+        assert_eq!(errors[0].to_string(), "第 5 行: 收盘价 不是有效的数字: abc");
+    }
+
+    #[test]
+    fn duplicate_date() {
+        let csv = "date,open,high,low,close\n\
+                   2023-04-07,1,1,1,1\n\
+                   2023-04-07,2,2,2,2\n";
+        let errors = read(csv, &ImportOptions::default()).unwrap_err();
+        assert_eq!(
+            errors,
+            [ImportError {
+                line: 3,
+                message: String::from("日期 2023-04-07 重复")
+            }]
+        );
+    }
+
+    #[test]
+    fn inconsistent_prices_and_empty_file() {
+        let errors = read(
+            "date,open,high,low,close\n2023-04-07,1,0.9,0.8,1\n",
+            &ImportOptions::default(),
+        )
+        .unwrap_err();
+        assert_eq!(errors[0].line, 2);
+        let errors = read("date,open,high,low,close\n", &ImportOptions::default()).unwrap_err();
+        assert_eq!(errors[0].message, "文件中没有数据");
+    }
+
+    #[test]
+    fn k_line_datas_with_moving_averages() {
+        let series = ImportedSeries {
+            stock_code: String::from("TEST"),
+            stock_name: String::from("测试"),
+            bars: (1..=6)
+                .map(|i| bar(&format!("2023-04-{:02}", i), i as f64))
+                .collect(),
+        };
+        let k_line_datas = series.k_line_datas();
+        // 与接口返回的一样最新的在前
+        assert_eq!(k_line_datas[0].date, "2023-04-06");
+        assert_eq!(k_line_datas[0].pre_close, 5.0);
+        assert_eq!(k_line_datas[0].up_rate, 20.0);
+        assert_eq!(k_line_datas[0].avg5, 4.0);
+        // 不足天数时按已有的天数平均
+        assert_eq!(k_line_datas[0].avg10, 3.5);
+        assert_eq!(k_line_datas[5].avg5, 1.0);
+        assert_eq!(k_line_datas[5].pre_close, 1.0);
+        assert_eq!(k_line_datas[5].up_rate, 0.0);
+    }
+
+    #[test]
+    fn load_dir_skips_bad_files() {
+        let dir = env::temp_dir().join(format!("stock_rs_imports_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let series = ImportedSeries {
+            stock_code: String::from("TEST"),
+            stock_name: String::from("测试"),
+            bars: vec![bar("2023-04-07", 1.0)],
+        };
+        storage::save_json(&dir.join("TEST.json"), &series).unwrap();
+        fs::write(dir.join("BAD.json"), "{").unwrap();
+        fs::write(dir.join("notes.txt"), "not json").unwrap();
+        let loaded = ImportedSeries::load_dir(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].stock_code, "TEST");
+        assert!(ImportedSeries::load_dir(&dir).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,109 @@
+//! 由收盘价等序列计算技术指标，参数与接口返回的一致，序列均按日期升序
+
+/// 指数移动平均，第一个值作为初始值
+fn ema(values: impl Iterator<Item = f64>, days: f64, initial: Option<f64>) -> Vec<f64> {
+    let mut previous = initial;
+    values
+        .map(|value| {
+            let ema = match previous {
+                Some(previous) => (2.0 * value + (days - 1.0) * previous) / (days + 1.0),
+                None => value,
+            };
+            previous = Some(ema);
+            ema
+        })
+        .collect()
+}
+
+/// MACD(12, 26, 9)，返回每天的 (dif, dea, macd)
+pub fn macd(closes: &[f64]) -> Vec<(f64, f64, f64)> {
+    let fast = ema(closes.iter().copied(), 12.0, None);
+    let slow = ema(closes.iter().copied(), 26.0, None);
+    let difs: Vec<f64> = fast
+        .iter()
+        .zip(&slow)
+        .map(|(fast, slow)| fast - slow)
+        .collect();
+    let deas = ema(difs.iter().copied(), 9.0, Some(0.0));
+    difs.iter()
+        .zip(deas)
+        .map(|(dif, dea)| (*dif, dea, 2.0 * (dif - dea)))
+        .collect()
+}
+
+/// KDJ(9, 3, 3)，返回每天的 (k, d, j)，k、d 从 50 开始
+pub fn kdj(highs: &[f64], lows: &[f64], closes: &[f64]) -> Vec<(f64, f64, f64)> {
+    let (mut k, mut d) = (50.0, 50.0);
+    (0..closes.len())
+        .map(|i| {
+            let start = (i + 1).saturating_sub(9);
+            let high = highs[start..=i].iter().copied().fold(f64::MIN, f64::max);
+            let low = lows[start..=i].iter().copied().fold(f64::MAX, f64::min);
+            let rsv = if high > low {
+                (closes[i] - low) / (high - low) * 100.0
+            } else {
+                50.0
+            };
+            k = (2.0 * k + rsv) / 3.0;
+            d = (2.0 * d + k) / 3.0;
+            (k, d, 3.0 * k - 2.0 * d)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::decode;
+
+    /// 用接口返回的 90 天数据重新计算，与接口给出的指标一致（接口保留 3 位小数）
+    #[test]
+    fn matches_server_values() {
+        let wrapper = decode(include_bytes!("../assets/resp.json")).unwrap();
+        let stock_data = wrapper.get_stock_data("day").unwrap();
+        let mut k_line_datas = stock_data.get_k_line_datas();
+        k_line_datas.sort_by(|a, b| a.date.cmp(&b.date));
+        let closes: Vec<f64> = k_line_datas
+            .iter()
+            .map(|k_line_data| k_line_data.end)
+            .collect();
+        let highs: Vec<f64> = k_line_datas
+            .iter()
+            .map(|k_line_data| k_line_data.max)
+            .collect();
+        let lows: Vec<f64> = k_line_datas
+            .iter()
+            .map(|k_line_data| k_line_data.min)
+            .collect();
+
+        let mut expected_macd: Vec<_> = stock_data.get_macd().iter().collect();
+        let mut expected_kdj: Vec<_> = stock_data.get_kdj().iter().collect();
+        if stock_data.is_newest_first() {
+            expected_macd.reverse();
+            expected_kdj.reverse();
+        }
+        let (dif, dea, macd) = *macd(&closes).last().unwrap();
+        let expected = expected_macd.last().unwrap();
+        assert!((dif - expected.get_dif() as f64).abs() < 0.001);
+        assert!((dea - expected.get_dea() as f64).abs() < 0.001);
+        assert!((macd - expected.get_macd() as f64).abs() < 0.002);
+        // kdj 经过 9 天后与初始值无关
+        for ((k, d, j), expected) in kdj(&highs, &lows, &closes)
+            .iter()
+            .zip(expected_kdj)
+            .skip(30)
+        {
+            assert!((k - expected.get_k() as f64).abs() < 0.01);
+            assert!((d - expected.get_d() as f64).abs() < 0.01);
+            assert!((j - expected.get_j() as f64).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn flat_and_empty_series() {
+        assert!(macd(&[]).is_empty());
+        assert!(kdj(&[], &[], &[]).is_empty());
+        assert_eq!(macd(&[1.0, 1.0]), [(0.0, 0.0, 0.0), (0.0, 0.0, 0.0)]);
+        assert_eq!(kdj(&[1.0], &[1.0], &[1.0]), [(50.0, 50.0, 50.0)]);
+    }
+}
@@ -0,0 +1,577 @@
+use crate::storage;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fmt, io,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+/// 多键序列中两次按键的最长间隔，超过后丢弃已经按下的键
+const SEQUENCE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// 可以绑定到按键上的操作
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    SelectNext,
+    SelectPrevious,
+    SelectFirst,
+    SelectLast,
+    CursorLeft,
+    CursorRight,
+    ZoomIn,
+    ZoomOut,
+    PanLeft,
+    PanRight,
+    CycleChartType,
+    ToggleRenderer,
+    AddLevel,
+    TrendLine,
+    Fibonacci,
+    AddNote,
+    RemoveDrawing,
+    NextDrawing,
+    PreviousDrawing,
+    RaiseDrawing,
+    LowerDrawing,
+    ToggleCompare,
+    CompareView,
+    ShowOverview,
+    ShowChart,
+    ShowFlows,
+    ShowPortfolio,
+    ShowLog,
+    NextTab,
+    PreviousTab,
+    ToggleSidebar,
+    Buy,
+    Sell,
+    Export,
+    AlertLog,
+    Debug,
+    Retry,
+    PauseRefresh,
+    SortColumn,
+    SortOrder,
+    EditColumns,
+    Confirm,
+    Cancel,
+    Help,
+    Quit,
+}
+
+impl Action {
+    /// 帮助中列出的顺序
+    pub const ALL: [Action; 45] = [
+        Action::SelectNext,
+        Action::SelectPrevious,
+        Action::SelectFirst,
+        Action::SelectLast,
+        Action::CursorLeft,
+        Action::CursorRight,
+        Action::ZoomIn,
+        Action::ZoomOut,
+        Action::PanLeft,
+        Action::PanRight,
+        Action::CycleChartType,
+        Action::ToggleRenderer,
+        Action::AddLevel,
+        Action::TrendLine,
+        Action::Fibonacci,
+        Action::AddNote,
+        Action::RemoveDrawing,
+        Action::NextDrawing,
+        Action::PreviousDrawing,
+        Action::RaiseDrawing,
+        Action::LowerDrawing,
+        Action::ToggleCompare,
+        Action::CompareView,
+        Action::ShowOverview,
+        Action::ShowChart,
+        Action::ShowFlows,
+        Action::ShowPortfolio,
+        Action::ShowLog,
+        Action::NextTab,
+        Action::PreviousTab,
+        Action::ToggleSidebar,
+        Action::Buy,
+        Action::Sell,
+        Action::Export,
+        Action::AlertLog,
+        Action::Debug,
+        Action::Retry,
+        Action::PauseRefresh,
+        Action::SortColumn,
+        Action::SortOrder,
+        Action::EditColumns,
+        Action::Confirm,
+        Action::Cancel,
+        Action::Help,
+        Action::Quit,
+    ];
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Action::SelectNext => "选择下一只股票 / 下一行",
+            Action::SelectPrevious => "选择上一只股票 / 上一行",
+            Action::SelectFirst => "选择第一只股票 / 第一行",
+            Action::SelectLast => "选择最后一只股票 / 最后一行",
+            Action::CursorLeft => "光标左移",
+            Action::CursorRight => "光标右移",
+            Action::ZoomIn => "放大",
+            Action::ZoomOut => "缩小",
+            Action::PanLeft => "向左平移",
+            Action::PanRight => "向右平移",
+            Action::CycleChartType => "切换价格图绘制方式",
+            Action::ToggleRenderer => "切换画布 / 字符单元格绘制",
+            Action::AddLevel => "在光标处添加水平价位线",
+            Action::TrendLine => "趋势线",
+            Action::Fibonacci => "斐波那契回撤",
+            Action::AddNote => "在光标处添加备注",
+            Action::RemoveDrawing => "删除选中的画线",
+            Action::NextDrawing => "选择下一条画线",
+            Action::PreviousDrawing => "选择上一条画线",
+            Action::RaiseDrawing => "上移选中的画线",
+            Action::LowerDrawing => "下移选中的画线",
+            Action::ToggleCompare => "加入或移出对比",
+            Action::CompareView => "切换对比视图",
+            Action::ShowOverview => "概览",
+            Action::ShowChart => "图表",
+            Action::ShowFlows => "资金流向",
+            Action::ShowPortfolio => "持仓",
+            Action::ShowLog => "提醒记录",
+            Action::NextTab => "下一个标签页",
+            Action::PreviousTab => "上一个标签页",
+            Action::ToggleSidebar => "显示或收起图表页的自选列表",
+            Action::Buy => "记录买入",
+            Action::Sell => "记录卖出",
+            Action::Export => "导出当前股票的日 k 线数据",
+            Action::AlertLog => "打开或关闭提醒日志",
+            Action::Debug => "打开或关闭数据检查报告",
+            Action::Retry => "立即重新请求当前股票",
+            Action::PauseRefresh => "暂停或恢复自动刷新",
+            Action::SortColumn => "切换排序列",
+            Action::SortOrder => "切换升序 / 降序",
+            Action::EditColumns => "选择自选列表展示的列",
+            Action::Confirm => "在光标处放置锚点",
+            Action::Cancel => "取消 / 关闭弹窗",
+            Action::Help => "打开或关闭帮助",
+            Action::Quit => "退出",
+        }
+    }
+}
+
+/// 一个按键，字符键的大小写已经体现在字符中，不再记录 Shift
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl Key {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Key {
+        let modifiers = match code {
+            KeyCode::Char(_) | KeyCode::BackTab => modifiers - KeyModifiers::SHIFT,
+            _ => modifiers,
+        };
+        Key { code, modifiers }
+    }
+
+    /// 解析 `j`、`G`、`ctrl-d`、`alt-enter`、`f5` 这样的写法，`shift-g` 等同于 `G`
+    pub fn parse(text: &str) -> Option<Key> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = text;
+        // 单独的 `-` 是按键本身而不是分隔符
+        while let Some((prefix, tail)) = rest.split_once('-').filter(|(_, tail)| !tail.is_empty()) {
+            modifiers |= match prefix.to_lowercase().as_str() {
+                "ctrl" | "c" => KeyModifiers::CONTROL,
+                "alt" | "a" | "m" => KeyModifiers::ALT,
+                "shift" | "s" => KeyModifiers::SHIFT,
+                _ => return None,
+            };
+            rest = tail;
+        }
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match rest.to_lowercase().as_str() {
+                "enter" => KeyCode::Enter,
+                "esc" => KeyCode::Esc,
+                "backspace" => KeyCode::Backspace,
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "space" => KeyCode::Char(' '),
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                "delete" => KeyCode::Delete,
+                name => KeyCode::F(name.strip_prefix('f')?.parse().ok()?),
+            },
+        };
+        // 终端发送的 Shift 组合键已经体现在字符与 BackTab 中
+        let code = match code {
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::SHIFT) => {
+                KeyCode::Char(c.to_uppercase().next().unwrap_or(c))
+            }
+            KeyCode::Tab if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+            code => code,
+        };
+        Some(Key::new(code, modifiers))
+    }
+}
+
+impl From<KeyEvent> for Key {
+    fn from(event: KeyEvent) -> Self {
+        Key::new(event.code, event.modifiers)
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "ctrl-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "alt-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "shift-")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(n) => write!(f, "f{}", n),
+            KeyCode::Enter => write!(f, "enter"),
+            KeyCode::Esc => write!(f, "esc"),
+            KeyCode::Backspace => write!(f, "backspace"),
+            KeyCode::Tab => write!(f, "tab"),
+            KeyCode::BackTab => write!(f, "backtab"),
+            KeyCode::Left => write!(f, "left"),
+            KeyCode::Right => write!(f, "right"),
+            KeyCode::Up => write!(f, "up"),
+            KeyCode::Down => write!(f, "down"),
+            KeyCode::Home => write!(f, "home"),
+            KeyCode::End => write!(f, "end"),
+            KeyCode::PageUp => write!(f, "pageup"),
+            KeyCode::PageDown => write!(f, "pagedown"),
+            KeyCode::Delete => write!(f, "delete"),
+            code => write!(f, "{:?}", code),
+        }
+    }
+}
+
+/// 解析以空格分隔的按键序列，如 `g g`
+fn parse_sequence(text: &str) -> Option<Vec<Key>> {
+    let keys: Option<Vec<Key>> = text.split_whitespace().map(Key::parse).collect();
+    keys.filter(|keys| !keys.is_empty())
+}
+
+fn format_sequence(keys: &[Key]) -> String {
+    let keys: Vec<String> = keys.iter().map(Key::to_string).collect();
+    keys.join(" ")
+}
+
+/// 默认按键，vim 风格
+const DEFAULT_BINDINGS: [(&str, Action); 57] = [
+    ("j", Action::SelectNext),
+    ("down", Action::SelectNext),
+    ("k", Action::SelectPrevious),
+    ("up", Action::SelectPrevious),
+    ("g g", Action::SelectFirst),
+    ("G", Action::SelectLast),
+    ("h", Action::CursorLeft),
+    ("left", Action::CursorLeft),
+    ("l", Action::CursorRight),
+    ("right", Action::CursorRight),
+    ("+", Action::ZoomIn),
+    ("=", Action::ZoomIn),
+    ("-", Action::ZoomOut),
+    (",", Action::PanLeft),
+    ("<", Action::PanLeft),
+    (".", Action::PanRight),
+    (">", Action::PanRight),
+    ("c", Action::CycleChartType),
+    ("r", Action::ToggleRenderer),
+    ("L", Action::AddLevel),
+    ("t", Action::TrendLine),
+    ("f", Action::Fibonacci),
+    ("n", Action::AddNote),
+    ("x", Action::RemoveDrawing),
+    ("]", Action::NextDrawing),
+    ("[", Action::PreviousDrawing),
+    ("K", Action::RaiseDrawing),
+    ("J", Action::LowerDrawing),
+    ("m", Action::ToggleCompare),
+    ("v", Action::CompareView),
+    ("1", Action::ShowOverview),
+    ("2", Action::ShowChart),
+    ("3", Action::ShowFlows),
+    ("4", Action::ShowPortfolio),
+    ("P", Action::ShowPortfolio),
+    ("5", Action::ShowLog),
+    ("tab", Action::NextTab),
+    ("backtab", Action::PreviousTab),
+    ("w", Action::ToggleSidebar),
+    ("b", Action::Buy),
+    ("e", Action::Sell),
+    ("o", Action::Export),
+    ("a", Action::AlertLog),
+    ("D", Action::Debug),
+    ("R", Action::Retry),
+    ("p", Action::PauseRefresh),
+    ("s", Action::SortColumn),
+    ("S", Action::SortOrder),
+    ("C", Action::EditColumns),
+    ("enter", Action::Confirm),
+    ("esc", Action::Cancel),
+    ("?", Action::Help),
+    ("q", Action::Quit),
+    ("ctrl-n", Action::SelectNext),
+    ("ctrl-p", Action::SelectPrevious),
+    ("ctrl-u", Action::PanLeft),
+    ("ctrl-d", Action::PanRight),
+];
+
+/// 多键序列中已经按下的键
+#[derive(Debug, Default)]
+pub struct PendingKeys {
+    keys: Vec<Key>,
+    last_pressed: Option<Instant>,
+}
+
+impl PendingKeys {
+    pub fn clear(&mut self) {
+        self.keys.clear();
+        self.last_pressed = None;
+    }
+}
+
+/// 按键序列到操作的映射
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: Vec<(Vec<Key>, Action)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = DEFAULT_BINDINGS
+            .iter()
+            .filter_map(|(keys, action)| Some((parse_sequence(keys)?, *action)))
+            .collect();
+        Keymap { bindings }
+    }
+}
+
+impl Keymap {
+    fn path() -> PathBuf {
+        storage::data_dir().join("keymap.json")
+    }
+
+    /// 在默认按键上应用数据目录下 `keymap.json` 中的设置
+    ///
+    /// 文件是按键序列到操作名的映射，如 `{"ctrl-f": "pan_right", "g g": null}`，
+    /// 操作为 `null` 时取消该按键的绑定
+    pub fn load() -> io::Result<Keymap> {
+        let mut keymap = Keymap::default();
+        let overrides: BTreeMap<String, Option<Action>> =
+            storage::load_json(&Self::path())?.unwrap_or_default();
+        for (text, action) in overrides {
+            let keys = match parse_sequence(&text) {
+                Some(keys) => keys,
+                None => {
+                    log::warn!("ignore invalid key binding {:?}", text);
+                    continue;
+                }
+            };
+            keymap.bindings.retain(|(bound, _)| *bound != keys);
+            if let Some(action) = action {
+                keymap.bindings.push((keys, action));
+            }
+        }
+        Ok(keymap)
+    }
+
+    /// 把 `now` 时按下的 `key` 接在已按下的 `pending` 之后查找操作
+    ///
+    /// 完整匹配时清空 `pending` 并返回操作；是某个序列的前缀时保留等待下一个键；
+    /// 都不是时丢弃之前的按键，只用 `key` 重新查找。距上一个键超过 `SEQUENCE_TIMEOUT` 时
+    /// 之前的按键作废
+    pub fn resolve(&self, pending: &mut PendingKeys, key: Key, now: Instant) -> Option<Action> {
+        let expired = pending
+            .last_pressed
+            .is_some_and(|last_pressed| now.duration_since(last_pressed) > SEQUENCE_TIMEOUT);
+        if expired {
+            pending.clear();
+        }
+        pending.keys.push(key);
+        pending.last_pressed = Some(now);
+        let keys = &pending.keys;
+        if let Some((_, action)) = self.bindings.iter().find(|(bound, _)| bound == keys) {
+            pending.clear();
+            return Some(*action);
+        }
+        let is_prefix = self
+            .bindings
+            .iter()
+            .any(|(bound, _)| bound.len() > keys.len() && bound.starts_with(keys));
+        if is_prefix {
+            return None;
+        }
+        let retry = keys.len() > 1;
+        pending.clear();
+        if retry {
+            self.resolve(pending, key, now)
+        } else {
+            None
+        }
+    }
+
+    /// 帮助中的每一行：绑定的按键与操作说明，没有绑定按键的操作不列出
+    pub fn help(&self) -> Vec<(String, &'static str)> {
+        Action::ALL
+            .iter()
+            .filter_map(|action| {
+                let keys: Vec<String> = self
+                    .bindings
+                    .iter()
+                    .filter(|(_, bound)| bound == action)
+                    .map(|(keys, _)| format_sequence(keys))
+                    .collect();
+                match keys.is_empty() {
+                    true => None,
+                    false => Some((keys.join(" / "), action.description())),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(text: &str) -> Key {
+        Key::parse(text).unwrap()
+    }
+
+    #[test]
+    fn parse_keys() {
+        assert_eq!(key("j"), Key::new(KeyCode::Char('j'), KeyModifiers::NONE));
+        assert_eq!(key("G"), Key::new(KeyCode::Char('G'), KeyModifiers::NONE));
+        assert_eq!(key("shift-g"), key("G"));
+        assert_eq!(key("s-g"), key("G"));
+        assert_eq!(key("shift-tab"), key("backtab"));
+        assert_eq!(key("-"), Key::new(KeyCode::Char('-'), KeyModifiers::NONE));
+        assert_eq!(
+            key("ctrl--"),
+            Key::new(KeyCode::Char('-'), KeyModifiers::CONTROL)
+        );
+        assert_eq!(
+            key("Ctrl-D"),
+            Key::new(KeyCode::Char('D'), KeyModifiers::CONTROL)
+        );
+        assert_eq!(
+            key("alt-enter"),
+            Key::new(KeyCode::Enter, KeyModifiers::ALT)
+        );
+        assert_eq!(key("f5"), Key::new(KeyCode::F(5), KeyModifiers::NONE));
+        assert_eq!(
+            key("space"),
+            Key::new(KeyCode::Char(' '), KeyModifiers::NONE)
+        );
+        for invalid in ["", "hyper-j", "foo", "fx"] {
+            assert_eq!(Key::parse(invalid), None, "{:?}", invalid);
+        }
+        // 终端发送的 Shift+字符 与配置中的大写字符相同
+        let event = KeyEvent::new(KeyCode::Char('G'), KeyModifiers::SHIFT);
+        assert_eq!(Key::from(event), key("shift-g"));
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for text in [
+            "j",
+            "G",
+            "-",
+            "space",
+            "ctrl-d",
+            "alt-enter",
+            "ctrl-alt-x",
+            "shift-left",
+            "f12",
+            "backtab",
+            "pagedown",
+        ] {
+            let parsed = key(text);
+            assert_eq!(parsed.to_string(), text);
+            assert_eq!(key(&parsed.to_string()), parsed);
+        }
+        assert_eq!(key("shift-g").to_string(), "G");
+        assert_eq!(format_sequence(&parse_sequence("g  g").unwrap()), "g g");
+        assert_eq!(parse_sequence(" "), None);
+        assert_eq!(parse_sequence("g foo"), None);
+    }
+
+    #[test]
+    fn resolve_sequences() {
+        let keymap = Keymap::default();
+        let mut pending = PendingKeys::default();
+        let now = Instant::now();
+        assert_eq!(keymap.resolve(&mut pending, key("g"), now), None);
+        assert_eq!(
+            keymap.resolve(&mut pending, key("g"), now),
+            Some(Action::SelectFirst)
+        );
+        assert!(pending.keys.is_empty());
+        assert_eq!(
+            keymap.resolve(&mut pending, key("j"), now),
+            Some(Action::SelectNext)
+        );
+        // 前缀之后按下不匹配的键时，丢弃前缀并单独查找该键
+        assert_eq!(keymap.resolve(&mut pending, key("g"), now), None);
+        assert_eq!(
+            keymap.resolve(&mut pending, key("k"), now),
+            Some(Action::SelectPrevious)
+        );
+        assert_eq!(keymap.resolve(&mut pending, key("g"), now), None);
+        assert_eq!(keymap.resolve(&mut pending, key("F"), now), None);
+        assert!(pending.keys.is_empty());
+    }
+
+    #[test]
+    fn resolve_timeout_and_reset() {
+        let keymap = Keymap::default();
+        let mut pending = PendingKeys::default();
+        let start = Instant::now();
+        assert_eq!(keymap.resolve(&mut pending, key("g"), start), None);
+        let late = start + SEQUENCE_TIMEOUT + Duration::from_millis(1);
+        // 超时后第二个 g 重新开始一个序列
+        assert_eq!(keymap.resolve(&mut pending, key("g"), late), None);
+        assert_eq!(pending.keys, [key("g")]);
+        assert_eq!(
+            keymap.resolve(&mut pending, key("g"), late + SEQUENCE_TIMEOUT),
+            Some(Action::SelectFirst)
+        );
+        assert_eq!(keymap.resolve(&mut pending, key("g"), late), None);
+        pending.clear();
+        assert_eq!(keymap.resolve(&mut pending, key("g"), late), None);
+        assert_eq!(pending.keys.len(), 1);
+    }
+
+    #[test]
+    fn help_lists_bound_actions() {
+        let help = Keymap::default().help();
+        assert_eq!(
+            help[0],
+            (String::from("j / down / ctrl-n"), "选择下一只股票 / 下一行")
+        );
+        assert_eq!(help.len(), Action::ALL.len());
+    }
+}
@@ -1,5 +1,6 @@
 use tui::widgets::ListState;
 
+/// 带选中状态的列表，列表为空时 `next`/`previous` 取消选中
 pub struct StatefulList<T> {
     pub state: ListState,
     pub items: Vec<T>,
@@ -14,6 +15,10 @@ impl<T> StatefulList<T> {
     }
 
     pub fn next(&mut self) {
+        if self.items.is_empty() {
+            self.state.select(None);
+            return;
+        }
         let i = match self.state.selected() {
             Some(i) => {
                 if i >= self.items.len() - 1 {
@@ -28,6 +33,10 @@ impl<T> StatefulList<T> {
     }
 
     pub fn previous(&mut self) {
+        if self.items.is_empty() {
+            self.state.select(None);
+            return;
+        }
         let i = match self.state.selected() {
             Some(i) => {
                 if i == 0 {
@@ -45,3 +54,29 @@ impl<T> StatefulList<T> {
         self.state.select(None);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_list_has_no_selection() {
+        let mut list: StatefulList<i32> = StatefulList::with_items(vec![]);
+        list.next();
+        assert_eq!(list.state.selected(), None);
+        list.state.select(Some(0));
+        list.previous();
+        assert_eq!(list.state.selected(), None);
+    }
+
+    #[test]
+    fn next_and_previous_wrap() {
+        let mut list = StatefulList::with_items(vec![1, 2, 3]);
+        list.previous();
+        assert_eq!(list.state.selected(), Some(0));
+        list.previous();
+        assert_eq!(list.state.selected(), Some(2));
+        list.next();
+        assert_eq!(list.state.selected(), Some(0));
+    }
+}
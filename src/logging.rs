@@ -0,0 +1,151 @@
+use crate::storage;
+use chrono::{DateTime, Local};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::{
+    collections::VecDeque,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+    sync::{Mutex, OnceLock},
+};
+
+/// 日志文件超过该大小时轮转
+const MAX_FILE_SIZE: u64 = 5 * 1024 * 1024;
+
+/// 保留的旧日志文件数量：`stock_rs.log.1` 到 `stock_rs.log.3`
+const MAX_ROTATED_FILES: usize = 3;
+
+/// 界面中可以查看的最近日志条数
+const RECENT_ENTRIES: usize = 500;
+
+/// 一条日志
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub time: DateTime<Local>,
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+}
+
+/// 写入文件的日志，超过大小后轮转
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+}
+
+impl LogFile {
+    fn open(path: PathBuf) -> io::Result<LogFile> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(LogFile { path, file, size })
+    }
+
+    fn rotated(&self, i: usize) -> PathBuf {
+        self.path.with_extension(format!("log.{}", i))
+    }
+
+    /// `stock_rs.log` 改名为 `stock_rs.log.1`，原来的 `.1` 改为 `.2`，依此类推，最旧的删除
+    fn rotate(&mut self) -> io::Result<()> {
+        let _ = fs::remove_file(self.rotated(MAX_ROTATED_FILES));
+        for i in (1..MAX_ROTATED_FILES).rev() {
+            let _ = fs::rename(self.rotated(i), self.rotated(i + 1));
+        }
+        fs::rename(&self.path, self.rotated(1))?;
+        *self = LogFile::open(self.path.clone())?;
+        Ok(())
+    }
+
+    fn write(&mut self, line: &str) -> io::Result<()> {
+        if self.size + line.len() as u64 > MAX_FILE_SIZE {
+            self.rotate()?;
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+}
+
+struct Logger {
+    level: LevelFilter,
+    file: Mutex<LogFile>,
+    recent: Mutex<VecDeque<LogEntry>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let entry = LogEntry {
+            time: Local::now(),
+            level: record.level(),
+            target: record.target().to_string(),
+            message: record.args().to_string(),
+        };
+        let line = format!(
+            "{} {:<5} {} {}\n",
+            entry.time.format("%Y-%m-%dT%H:%M:%S%.3f%:z"),
+            entry.level,
+            entry.target,
+            entry.message
+        );
+        if let Ok(mut file) = self.file.lock() {
+            // 日志写不进去时没有别的地方可以报告，只能忽略
+            let _ = file.write(&line);
+        }
+        if let Ok(mut recent) = self.recent.lock() {
+            if recent.len() == RECENT_ENTRIES {
+                recent.pop_front();
+            }
+            recent.push_back(entry);
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock() {
+            let _ = file.file.flush();
+        }
+    }
+}
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+/// 日志文件的位置：数据目录下的 `logs/stock_rs.log`
+pub fn path() -> PathBuf {
+    storage::data_dir().join("logs").join("stock_rs.log")
+}
+
+/// 把 `log` 的输出写到日志文件，级别为 `Off` 时不做任何事
+///
+/// 界面占用了终端，日志不能输出到标准输出
+pub fn init(level: LevelFilter) -> io::Result<()> {
+    if level == LevelFilter::Off {
+        return Ok(());
+    }
+    let logger = Logger {
+        level,
+        file: Mutex::new(LogFile::open(path())?),
+        recent: Mutex::new(VecDeque::with_capacity(RECENT_ENTRIES)),
+    };
+    let logger = LOGGER.get_or_init(|| logger);
+    log::set_logger(logger).map_err(io::Error::other)?;
+    log::set_max_level(level);
+    Ok(())
+}
+
+/// 最近的日志，最新的在后
+pub fn recent() -> Vec<LogEntry> {
+    LOGGER
+        .get()
+        .and_then(|logger| logger.recent.lock().ok())
+        .map(|recent| recent.iter().cloned().collect())
+        .unwrap_or_default()
+}
@@ -1,14 +1,43 @@
+mod alert;
 mod app;
+mod chart;
+mod cli;
+mod compare;
+mod config;
+mod decode;
+mod drawing;
+mod export;
+mod history;
+mod import;
+mod indicator;
+mod keymap;
 mod list;
+mod logging;
+mod market;
 mod model;
 mod net;
+mod portfolio;
+mod refresh;
+mod storage;
+mod tab;
+mod theme;
 mod ui;
+mod watchlist;
 use crate::app::App;
+use crate::cli::Cli;
 use crate::ui::run;
+use clap::Parser;
 use std::{error::Error, time::Duration};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
+    if let Err(err) = logging::init(cli.log_level) {
+        eprintln!("init logging failed: {}", err);
+    }
+    if let Some(command) = cli.command {
+        return cli::run(command).await;
+    }
     let tick_rate = Duration::from_millis(250);
     let app = App::new();
     run(app, tick_rate)?;
@@ -0,0 +1,128 @@
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, NaiveTime, Utc, Weekday};
+use std::collections::HashSet;
+
+/// 上海时区（UTC+8，无夏令时）
+pub fn shanghai_now() -> DateTime<FixedOffset> {
+    let offset = FixedOffset::east_opt(8 * 3600).unwrap();
+    Utc::now().with_timezone(&offset)
+}
+
+/// A 股连续竞价时段
+const SESSIONS: [((u32, u32), (u32, u32)); 2] = [((9, 30), (11, 30)), ((13, 0), (15, 0))];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarketStatus {
+    /// 交易中
+    Open,
+    /// 交易日的午间休市或开盘前、收盘后
+    Break,
+    /// 周末或节假日
+    Closed,
+}
+
+impl MarketStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            MarketStatus::Open => "交易中",
+            MarketStatus::Break => "休市",
+            MarketStatus::Closed => "非交易日",
+        }
+    }
+}
+
+/// A 股交易日历：工作日且不在休市日列表中即为交易日
+#[derive(Debug, Clone, Default)]
+pub struct MarketCalendar {
+    holidays: HashSet<NaiveDate>,
+}
+
+impl MarketCalendar {
+    pub fn new(holidays: &[NaiveDate]) -> MarketCalendar {
+        MarketCalendar {
+            holidays: holidays.iter().cloned().collect(),
+        }
+    }
+
+    pub fn is_trading_day(&self, date: NaiveDate) -> bool {
+        !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) && !self.holidays.contains(&date)
+    }
+
+    /// `now` 为上海时间
+    pub fn status(&self, now: DateTime<FixedOffset>) -> MarketStatus {
+        if !self.is_trading_day(now.date_naive()) {
+            return MarketStatus::Closed;
+        }
+        let time = now.time();
+        let in_session = SESSIONS.iter().any(|&((h1, m1), (h2, m2))| {
+            let start = NaiveTime::from_hms_opt(h1, m1, 0).unwrap();
+            let end = NaiveTime::from_hms_opt(h2, m2, 0).unwrap();
+            start <= time && time <= end
+        });
+        if in_session {
+            MarketStatus::Open
+        } else {
+            MarketStatus::Break
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDateTime, TimeZone};
+
+    /// 上海时间 `datetime`，格式为 `2023-04-07 09:30:00`
+    fn at(datetime: &str) -> DateTime<FixedOffset> {
+        let datetime = NaiveDateTime::parse_from_str(datetime, "%Y-%m-%d %H:%M:%S").unwrap();
+        FixedOffset::east_opt(8 * 3600)
+            .unwrap()
+            .from_local_datetime(&datetime)
+            .unwrap()
+    }
+
+    fn date(date: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn session_edges() {
+        let calendar = MarketCalendar::default();
+        // 2023-04-07 为星期五
+        for (time, status) in [
+            ("09:29:59", MarketStatus::Break),
+            ("09:30:00", MarketStatus::Open),
+            ("11:30:00", MarketStatus::Open),
+            ("11:30:01", MarketStatus::Break),
+            ("12:00:00", MarketStatus::Break),
+            ("12:59:59", MarketStatus::Break),
+            ("13:00:00", MarketStatus::Open),
+            ("15:00:00", MarketStatus::Open),
+            ("15:00:01", MarketStatus::Break),
+        ] {
+            let now = at(&format!("2023-04-07 {}", time));
+            assert_eq!(calendar.status(now), status, "{}", time);
+        }
+    }
+
+    #[test]
+    fn weekends_and_holidays() {
+        let calendar = MarketCalendar::new(&[date("2023-05-01")]);
+        assert!(calendar.is_trading_day(date("2023-04-07")));
+        assert!(!calendar.is_trading_day(date("2023-04-08")));
+        assert!(!calendar.is_trading_day(date("2023-04-09")));
+        assert!(!calendar.is_trading_day(date("2023-05-01")));
+        assert!(calendar.is_trading_day(date("2023-05-02")));
+        assert_eq!(
+            calendar.status(at("2023-04-08 10:00:00")),
+            MarketStatus::Closed
+        );
+        assert_eq!(
+            calendar.status(at("2023-05-01 10:00:00")),
+            MarketStatus::Closed
+        );
+        assert_eq!(
+            calendar.status(at("2023-05-02 10:00:00")),
+            MarketStatus::Open
+        );
+    }
+}
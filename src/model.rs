@@ -1,13 +1,17 @@
+use crate::decode::DecodeReport;
+use crate::indicator;
+use custom_widget::kline::klinedata::KlineData;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Trend {
     end: f32,
     avg: f32,
     qty: i32,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Szzs {
     name: String,
     #[serde(rename = "preClose")]
@@ -15,6 +19,26 @@ pub struct Szzs {
     trends: Vec<Trend>,
 }
 
+impl Trend {
+    pub fn get_end(&self) -> f32 {
+        self.end
+    }
+}
+
+impl Szzs {
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_pre_close(&self) -> f32 {
+        self.pre_close
+    }
+
+    pub fn get_trends(&self) -> &[Trend] {
+        &self.trends
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Kline {
     end: f32,
@@ -22,6 +46,20 @@ pub struct Kline {
     qty: i32,
 }
 
+impl Kline {
+    pub fn get_end(&self) -> f32 {
+        self.end
+    }
+
+    pub fn get_avg(&self) -> f32 {
+        self.avg
+    }
+
+    pub fn get_qty(&self) -> i32 {
+        self.qty
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct MainInOut {
     list: Vec<i32>,
@@ -39,7 +77,36 @@ pub struct MainIns {
     price: f32,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl MainIns {
+    pub fn get_date(&self) -> &str {
+        &self.date
+    }
+
+    /// 主力净流入
+    pub fn get_main(&self) -> i32 {
+        self.main
+    }
+
+    /// 超大单净流入
+    pub fn get_bigger(&self) -> i32 {
+        self.bigger
+    }
+
+    /// 大单净流入
+    pub fn get_big(&self) -> i32 {
+        self.big
+    }
+
+    pub fn get_up_rate(&self) -> f32 {
+        self.up_rate
+    }
+
+    pub fn get_price(&self) -> f32 {
+        self.price
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct KLinesData {
     date: String,
     #[serde(rename = "preClose")]
@@ -67,6 +134,35 @@ pub struct KLinesData {
     avg_q10: f32,
 }
 
+impl KLinesData {
+    pub fn get_date(&self) -> &str {
+        &self.date
+    }
+}
+
+impl From<&KLinesData> for KlineData<'static> {
+    fn from(k_lines_data: &KLinesData) -> Self {
+        KlineData {
+            date: Cow::Owned(k_lines_data.date.clone()),
+            pre_close: k_lines_data.pre_close as f64,
+            start: k_lines_data.start as f64,
+            end: k_lines_data.end as f64,
+            min: k_lines_data.min as f64,
+            max: k_lines_data.max as f64,
+            quantity: k_lines_data.quantity as i64,
+            amount: k_lines_data.amount as i64,
+            up_rate: k_lines_data.up_rate as f64,
+            cr: k_lines_data.cr as f64,
+            avg5: k_lines_data.avg_5 as f64,
+            avg10: k_lines_data.avg_10 as f64,
+            avg20: k_lines_data.avg_20 as f64,
+            avg30: k_lines_data.avg_30 as f64,
+            avg_q5: k_lines_data.avg_q5 as f64,
+            avg_q10: k_lines_data.avg_q10 as f64,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Macd {
     dif: f32,
@@ -74,6 +170,20 @@ pub struct Macd {
     macd: f32,
 }
 
+impl Macd {
+    pub fn get_dif(&self) -> f32 {
+        self.dif
+    }
+
+    pub fn get_dea(&self) -> f32 {
+        self.dea
+    }
+
+    pub fn get_macd(&self) -> f32 {
+        self.macd
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Kdj {
     k: f32,
@@ -81,6 +191,20 @@ pub struct Kdj {
     j: f32,
 }
 
+impl Kdj {
+    pub fn get_k(&self) -> f32 {
+        self.k
+    }
+
+    pub fn get_d(&self) -> f32 {
+        self.d
+    }
+
+    pub fn get_j(&self) -> f32 {
+        self.j
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Report {
     w: f32,
@@ -132,16 +256,309 @@ pub struct StockData {
     mark_price: i32,
 }
 
+impl StockData {
+    pub fn get_gp_id(&self) -> &str {
+        &self.gp_id
+    }
+
+    pub fn get_view(&self) -> &str {
+        &self.view
+    }
+
+    /// k 线、macd、kdj 等序列是否按日期降序排列
+    pub fn is_newest_first(&self) -> bool {
+        match (self.past_k_lines.first(), self.past_k_lines.last()) {
+            (Some(first), Some(last)) => first.date > last.date,
+            _ => false,
+        }
+    }
+
+    /// macd 的 (dif, dea) 序列，按日期升序
+    pub fn get_macd_series(&self) -> Vec<(f64, f64)> {
+        let mut series: Vec<(f64, f64)> = self
+            .macd
+            .iter()
+            .map(|macd| (macd.dif as f64, macd.dea as f64))
+            .collect();
+        if self.is_newest_first() {
+            series.reverse();
+        }
+        series
+    }
+
+    /// 最近一天的主力净流入
+    pub fn get_latest_main_in(&self) -> Option<i32> {
+        self.past_main_ins
+            .iter()
+            .max_by(|a, b| a.date.cmp(&b.date))
+            .map(|main_ins| main_ins.main)
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// 当日的分时成交，按时间升序
+    pub fn get_k_lines(&self) -> &Vec<Kline> {
+        &self.k_lines
+    }
+
+    /// 与 `past_k_lines` 一一对应的 macd
+    pub fn get_macd(&self) -> &Vec<Macd> {
+        &self.macd
+    }
+
+    /// 与 `past_k_lines` 一一对应的 kdj
+    pub fn get_kdj(&self) -> &Vec<Kdj> {
+        &self.kdj
+    }
+
+    /// 过去的主力资金流向
+    pub fn get_past_main_ins(&self) -> &Vec<MainIns> {
+        &self.past_main_ins
+    }
+
+    /// 取出过去的 k 线，用于与本地历史合并
+    pub fn take_past_k_lines(&mut self) -> Vec<KLinesData> {
+        std::mem::take(&mut self.past_k_lines)
+    }
+
+    pub fn set_past_k_lines(&mut self, past_k_lines: Vec<KLinesData>) {
+        self.past_k_lines = past_k_lines;
+    }
+
+    /// 按 `past_k_lines` 重新计算 macd 与 kdj，使其与 k 线一一对应
+    pub fn recompute_indicators(&mut self) {
+        let newest_first = self.is_newest_first();
+        let mut bars: Vec<&KLinesData> = self.past_k_lines.iter().collect();
+        if newest_first {
+            bars.reverse();
+        }
+        let series = |value: fn(&KLinesData) -> f32| -> Vec<f64> {
+            bars.iter().map(|bar| value(bar) as f64).collect()
+        };
+        let closes = series(|bar| bar.end);
+        let mut macd: Vec<Macd> = indicator::macd(&closes)
+            .into_iter()
+            .map(|(dif, dea, macd)| Macd {
+                dif: dif as f32,
+                dea: dea as f32,
+                macd: macd as f32,
+            })
+            .collect();
+        let highs = series(|bar| bar.max);
+        let lows = series(|bar| bar.min);
+        let mut kdj: Vec<Kdj> = indicator::kdj(&highs, &lows, &closes)
+            .into_iter()
+            .map(|(k, d, j)| Kdj {
+                k: k as f32,
+                d: d as f32,
+                j: j as f32,
+            })
+            .collect();
+        if newest_first {
+            macd.reverse();
+            kdj.reverse();
+        }
+        self.macd = macd;
+        self.kdj = kdj;
+    }
+
+    /// 过去的 k 线，转换为图表使用的数据
+    pub fn get_k_line_datas(&self) -> Vec<KlineData<'static>> {
+        self.past_k_lines.iter().map(KlineData::from).collect()
+    }
+}
+
+/// 接口返回的分页游标，原样带回给接口即可取得相邻的一页
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageCursor(String);
+
+impl PageCursor {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct StockDataWrapper {
     pre: String,
     next: String,
     szzs: Szzs,
     data: Vec<StockData>,
+    /// 解析与校验这份数据时发现的问题
+    #[serde(skip)]
+    report: DecodeReport,
 }
 
 impl StockDataWrapper {
+    pub fn get_szzs(&self) -> &Szzs {
+        &self.szzs
+    }
+
+    /// 更早一页的分页游标（接口的 `pre`），没有更多数据时返回 `None`；
+    /// 总是从最新一页开始请求，用不到指向更新数据的 `next`
+    pub fn get_older_cursor(&self) -> Option<PageCursor> {
+        match self.pre.trim() {
+            "" => None,
+            cursor => Some(PageCursor(cursor.to_string())),
+        }
+    }
+
+    /// 按股票拆分，`data` 中包含多只股票时每只股票各得到一份
+    pub fn split_by_stock(self) -> Vec<StockDataWrapper> {
+        let mut wrappers: Vec<StockDataWrapper> = Vec::new();
+        for stock_data in self.data {
+            match wrappers
+                .iter_mut()
+                .find(|wrapper| wrapper.data[0].gp_id == stock_data.gp_id)
+            {
+                Some(wrapper) => wrapper.data.push(stock_data),
+                None => wrappers.push(StockDataWrapper {
+                    pre: self.pre.clone(),
+                    next: self.next.clone(),
+                    szzs: self.szzs.clone(),
+                    data: vec![stock_data],
+                    report: self.report.clone(),
+                }),
+            }
+        }
+        wrappers
+    }
+
+    pub fn get_report(&self) -> &DecodeReport {
+        &self.report
+    }
+
+    pub fn set_report(&mut self, report: DecodeReport) {
+        self.report = report;
+    }
+
+    /// 第一只股票的编号
+    pub fn get_gp_id(&self) -> Option<i32> {
+        self.data.first()?.gp_id.parse().ok()
+    }
+
+    /// 并入更早一页的数据：各级别的 k 线与主力数据按日期去重后接在后面，游标更新为这一页的 `pre`，
+    /// 之后按合并出的 k 线重新计算 macd、kdj
+    pub fn append_older(&mut self, older: StockDataWrapper) {
+        self.pre = older.pre;
+        for older_data in older.data {
+            let stock_data = self
+                .data
+                .iter_mut()
+                .find(|data| data.gp_id == older_data.gp_id && data.view == older_data.view);
+            let stock_data = match stock_data {
+                Some(stock_data) => stock_data,
+                None => {
+                    self.data.push(older_data);
+                    continue;
+                }
+            };
+            stock_data.past_k_lines = merge_older(
+                std::mem::take(&mut stock_data.past_k_lines),
+                older_data.past_k_lines,
+                |bar| &bar.date,
+            );
+            stock_data.past_main_ins = merge_older(
+                std::mem::take(&mut stock_data.past_main_ins),
+                older_data.past_main_ins,
+                |main_in| &main_in.date,
+            );
+            stock_data.recompute_indicators();
+        }
+    }
+
     pub fn get_stock_datas(&self) -> &Vec<StockData> {
         &self.data
     }
+
+    pub fn get_stock_datas_mut(&mut self) -> &mut Vec<StockData> {
+        &mut self.data
+    }
+
+    /// 指定 k 线级别（day、week、5min 等）的数据
+    pub fn get_stock_data(&self, view: &str) -> Option<&StockData> {
+        self.get_stock_datas()
+            .iter()
+            .find(|data| data.get_view() == view)
+    }
+}
+
+/// 把更早的 `older` 并入 `newer`，同一日期保留 `newer` 中的；顺序与 `newer` 相同，
+/// 接口按日期降序返回，不足两条无法判断时也按降序
+fn merge_older<T>(newer: Vec<T>, older: Vec<T>, date: fn(&T) -> &str) -> Vec<T> {
+    let newest_first = match (newer.first(), newer.last()) {
+        (Some(first), Some(last)) if newer.len() > 1 => date(first) > date(last),
+        _ => true,
+    };
+    let mut items = newer;
+    items.extend(older);
+    // 稳定排序，同一日期 `newer` 中的排在前面，去重时保留
+    items.sort_by(|a, b| date(a).cmp(date(b)));
+    items.dedup_by(|a, b| date(a) == date(b));
+    if newest_first {
+        items.reverse();
+    }
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::decode;
+
+    /// 只保留 `assets/resp.json` 中日 k 线的第 `range` 根（最新的在前），主力数据同样截取
+    fn page(range: std::ops::Range<usize>, pre: &str) -> StockDataWrapper {
+        let mut wrapper = decode(include_bytes!("../assets/resp.json")).unwrap();
+        wrapper.pre = pre.to_string();
+        wrapper.data.retain(|data| data.view == "day");
+        let data = &mut wrapper.data[0];
+        data.past_k_lines = data.past_k_lines.drain(range.clone()).collect();
+        data.past_main_ins = data.past_main_ins.drain(range).collect();
+        wrapper
+    }
+
+    #[test]
+    fn append_older_merges_by_date() {
+        let full = page(0..60, "");
+        let mut wrapper = page(0..30, "cursor-1");
+        wrapper.data[0].past_k_lines[29].end = 9.0;
+        wrapper.append_older(page(20..60, "cursor-2"));
+
+        assert_eq!(wrapper.pre, "cursor-2");
+        let data = &wrapper.data[0];
+        let dates: Vec<&str> = data.past_k_lines.iter().map(|bar| bar.get_date()).collect();
+        let expected: Vec<&str> = full.data[0]
+            .past_k_lines
+            .iter()
+            .map(|bar| bar.get_date())
+            .collect();
+        assert_eq!(dates, expected);
+        assert!(data.is_newest_first());
+        // 重叠的日期保留较新一页的数据
+        assert_eq!(data.past_k_lines[29].end, 9.0);
+        let main_dates: Vec<&str> = data.past_main_ins.iter().map(MainIns::get_date).collect();
+        let expected: Vec<&str> = full.data[0]
+            .past_main_ins
+            .iter()
+            .map(MainIns::get_date)
+            .collect();
+        assert_eq!(main_dates, expected);
+        assert_eq!(data.macd.len(), 60);
+        assert_eq!(data.kdj.len(), 60);
+    }
+
+    #[test]
+    fn append_older_adds_new_views() {
+        let mut wrapper = page(0..30, "cursor-1");
+        let mut older = decode(include_bytes!("../assets/resp.json")).unwrap();
+        older.data.retain(|data| data.view == "week");
+        wrapper.append_older(older);
+        assert!(wrapper.get_stock_data("day").is_some());
+        assert_eq!(
+            wrapper.get_stock_data("week").unwrap().past_k_lines.len(),
+            90
+        );
+    }
 }
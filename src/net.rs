@@ -1,12 +1,248 @@
-use crate::model::StockDataWrapper;
-use std::error::Error;
+use crate::{
+    decode, history,
+    model::{PageCursor, StockDataWrapper},
+};
+use reqwest::Client;
+use std::sync::mpsc::Sender;
+use std::sync::OnceLock;
+use std::{
+    error::Error,
+    fmt,
+    time::{Duration, Instant},
+};
+use tokio::sync::Semaphore;
 
-pub async fn get_stock_datas(gp_id: i32) -> Result<StockDataWrapper, Box<dyn Error>> {
-    let mut url = String::from("http://xxx.xx.xx.xx:xxxxx");
+const BASE_URL: &str = "http://xxx.xx.xx.xx:xxxxx";
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 整个请求（含读取响应）的超时
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// 暂时性失败时最多重试的次数
+const MAX_RETRIES: u32 = 3;
+
+/// 第一次重试前的等待时间，之后每次翻倍
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// 同时进行的请求数
+const MAX_CONCURRENT_REQUESTS: usize = 4;
+
+/// 请求失败的原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FetchError {
+    /// 连接失败、超时等网络错误
+    Network(String),
+    /// 服务器返回了非 2xx 状态码
+    Status(u16),
+    /// 响应内容无法解析
+    Decode(String),
+    /// 响应中没有任何股票数据
+    Empty,
+    /// 无法创建 HTTP 客户端（如 TLS 初始化失败），重试也不会成功
+    Client(String),
+}
+
+impl FetchError {
+    /// 是否值得重试：网络错误、429 与 5xx
+    pub fn is_transient(&self) -> bool {
+        match self {
+            FetchError::Network(_) => true,
+            FetchError::Status(status) => *status == 429 || *status >= 500,
+            FetchError::Decode(_) | FetchError::Empty | FetchError::Client(_) => false,
+        }
+    }
+
+    /// 界面上显示的简短说明
+    pub fn name(&self) -> &'static str {
+        match self {
+            FetchError::Network(_) => "网络错误",
+            FetchError::Status(_) => "服务器错误",
+            FetchError::Decode(_) => "数据格式错误",
+            FetchError::Empty => "没有数据",
+            FetchError::Client(_) => "客户端错误",
+        }
+    }
+
+    /// 服务器返回的 HTTP 状态码，没有收到响应时为 `None`
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            FetchError::Status(status) => Some(*status),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FetchError::Network(err) => write!(f, "{}: {}", self.name(), err),
+            FetchError::Status(status) => write!(f, "{}: HTTP {}", self.name(), status),
+            FetchError::Decode(err) => write!(f, "{}: {}", self.name(), err),
+            FetchError::Empty => write!(f, "{}", self.name()),
+            FetchError::Client(err) => write!(f, "{}: {}", self.name(), err),
+        }
+    }
+}
+
+impl Error for FetchError {}
+
+impl From<reqwest::Error> for FetchError {
+    fn from(err: reqwest::Error) -> Self {
+        match err.status() {
+            Some(status) => FetchError::Status(status.as_u16()),
+            None if err.is_decode() => FetchError::Decode(err.to_string()),
+            None => FetchError::Network(err.to_string()),
+        }
+    }
+}
+
+/// 后台请求的结果
+pub struct FetchResult {
+    pub gp_id: i32,
+    /// 请求的分页游标，`None` 为最新一页
+    pub cursor: Option<PageCursor>,
+    pub result: Result<StockDataWrapper, FetchError>,
+}
+
+/// 所有请求共用的客户端，复用连接；创建失败时记录日志，之后的请求都返回这个错误，
+/// 不退回到没有超时设置的默认客户端
+fn client() -> Result<&'static Client, FetchError> {
+    static CLIENT: OnceLock<Result<Client, String>> = OnceLock::new();
+    CLIENT
+        .get_or_init(|| {
+            Client::builder()
+                .connect_timeout(CONNECT_TIMEOUT)
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .map_err(|err| {
+                    log::error!("build http client failed: {}", err);
+                    err.to_string()
+                })
+        })
+        .as_ref()
+        .map_err(|err| FetchError::Client(err.clone()))
+}
+
+fn semaphore() -> &'static Semaphore {
+    static SEMAPHORE: Semaphore = Semaphore::const_new(MAX_CONCURRENT_REQUESTS);
+    &SEMAPHORE
+}
+
+/// 第 `attempt` 次重试前的等待时间：指数退避，再随机取其中的一半到全部，避免同时重试
+fn retry_delay(attempt: u32) -> Duration {
+    let delay = RETRY_BASE_DELAY * 2u32.pow(attempt);
+    delay.mul_f64(0.5 + fastrand::f64() * 0.5)
+}
+
+/// 请求地址：游标是完整地址时直接使用，否则作为 `page` 参数附加在最新一页的地址后
+fn url(gp_id: i32, cursor: Option<&PageCursor>) -> String {
+    let cursor = cursor.map(PageCursor::as_str);
+    if let Some(cursor) = cursor.filter(|cursor| cursor.starts_with("http")) {
+        return cursor.to_string();
+    }
+    let mut url = String::from(BASE_URL);
     url.push_str("?block=");
     url.push_str(gp_id.to_string().as_str());
     url.push_str("&type=local&view=dtl");
-    let resp = reqwest::get(url).await?;
-    let result = resp.json::<StockDataWrapper>().await?;
+    if let Some(cursor) = cursor {
+        url.push_str("&page=");
+        url.push_str(cursor);
+    }
+    url
+}
+
+/// `attempt` 为第几次请求，从 1 开始，只用于日志
+async fn request(url: &str, attempt: u32) -> Result<StockDataWrapper, FetchError> {
+    let _permit = semaphore()
+        .acquire()
+        .await
+        .map_err(|err| FetchError::Network(err.to_string()))?;
+    let start = Instant::now();
+    let body = match download(url).await {
+        Ok((status, body)) => {
+            log::info!(
+                "fetch url={} attempt={} status={} bytes={} latency_ms={}",
+                url,
+                attempt,
+                status,
+                body.len(),
+                start.elapsed().as_millis()
+            );
+            body
+        }
+        Err(err) => {
+            log::warn!(
+                "fetch url={} attempt={} status={} error={:?} latency_ms={}",
+                url,
+                attempt,
+                err.status()
+                    .map_or_else(|| "-".to_string(), |status| status.to_string()),
+                err.to_string(),
+                start.elapsed().as_millis()
+            );
+            return Err(err);
+        }
+    };
+    let result = decode::decode(&body).map_err(|err| FetchError::Decode(err.to_string()))?;
+    if result.get_stock_datas().is_empty() {
+        return Err(FetchError::Empty);
+    }
     Ok(result)
 }
+
+/// 下载 `url` 的内容，非 2xx 状态码视为失败
+async fn download(url: &str) -> Result<(u16, Vec<u8>), FetchError> {
+    let resp = client()?.get(url).send().await?;
+    let status = resp.status();
+    if !status.is_success() {
+        return Err(FetchError::Status(status.as_u16()));
+    }
+    Ok((status.as_u16(), resp.bytes().await?.to_vec()))
+}
+
+pub async fn get_stock_datas(gp_id: i32) -> Result<StockDataWrapper, FetchError> {
+    get_stock_datas_page(gp_id, None).await
+}
+
+/// 请求 `cursor` 指向的一页数据，暂时性失败时重试，成功后与本地历史合并
+pub async fn get_stock_datas_page(
+    gp_id: i32,
+    cursor: Option<&PageCursor>,
+) -> Result<StockDataWrapper, FetchError> {
+    let url = url(gp_id, cursor);
+    let mut attempt = 0;
+    let mut result = loop {
+        match request(&url, attempt + 1).await {
+            Err(err) if err.is_transient() && attempt < MAX_RETRIES => {
+                let delay = retry_delay(attempt);
+                log::warn!(
+                    "fetch {} attempt {} failed: {}, retry in {:?}",
+                    gp_id,
+                    attempt + 1,
+                    err,
+                    delay
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            result => break result?,
+        }
+    };
+    if let Err(err) = history::update(&mut result) {
+        log::error!("update history of {} failed: {}", gp_id, err);
+    }
+    Ok(result)
+}
+
+/// 在后台请求数据，结果通过 `tx` 发回界面线程
+pub fn spawn_fetch(gp_id: i32, cursor: Option<PageCursor>, tx: Sender<FetchResult>) {
+    tokio::spawn(async move {
+        let result = get_stock_datas_page(gp_id, cursor.as_ref()).await;
+        let _ = tx.send(FetchResult {
+            gp_id,
+            cursor,
+            result,
+        });
+    });
+}
@@ -0,0 +1,295 @@
+use crate::storage;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, io, path::PathBuf};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+impl Side {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Side::Buy => "买入",
+            Side::Sell => "卖出",
+        }
+    }
+}
+
+/// 一笔成交记录
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Transaction {
+    pub stock_code: String,
+    pub side: Side,
+    pub quantity: f64,
+    pub price: f64,
+    #[serde(default)]
+    pub fee: f64,
+    pub date: NaiveDate,
+}
+
+impl Transaction {
+    /// 解析 `数量 价格 [手续费] [日期]`，如 `1000 0.905 5 2023-04-07`
+    pub fn parse(
+        stock_code: &str,
+        side: Side,
+        text: &str,
+        today: NaiveDate,
+    ) -> Result<Transaction, String> {
+        let fields: Vec<&str> = text.split_whitespace().collect();
+        if fields.len() < 2 || fields.len() > 4 {
+            return Err(String::from("格式：数量 价格 [手续费] [日期]"));
+        }
+        let number = |field: &str, name: &str| {
+            field
+                .parse::<f64>()
+                .ok()
+                .filter(|value| value.is_finite() && *value >= 0.0)
+                .ok_or_else(|| format!("{}无效：{}", name, field))
+        };
+        let quantity = number(fields[0], "数量")?;
+        if quantity == 0.0 {
+            return Err(String::from("数量不能为 0"));
+        }
+        let price = number(fields[1], "价格")?;
+        let fee = match fields.get(2) {
+            Some(fee) => number(fee, "手续费")?,
+            None => 0.0,
+        };
+        let date = match fields.get(3) {
+            Some(date) => NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .map_err(|_| format!("日期无效：{}", date))?,
+            None => today,
+        };
+        Ok(Transaction {
+            stock_code: stock_code.to_string(),
+            side,
+            quantity,
+            price,
+            fee,
+            date,
+        })
+    }
+}
+
+/// 按移动加权平均成本计算的持仓
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Position {
+    pub stock_code: String,
+    pub quantity: f64,
+    /// 持仓总成本（含买入手续费）
+    pub cost: f64,
+    /// 已实现盈亏（扣除卖出手续费）
+    pub realized: f64,
+}
+
+impl Position {
+    pub fn average_cost(&self) -> f64 {
+        if self.quantity > 0.0 {
+            self.cost / self.quantity
+        } else {
+            0.0
+        }
+    }
+
+    pub fn market_value(&self, price: f64) -> f64 {
+        self.quantity * price
+    }
+
+    pub fn unrealized(&self, price: f64) -> f64 {
+        self.market_value(price) - self.cost
+    }
+
+    fn apply(&mut self, transaction: &Transaction) -> Result<(), String> {
+        match transaction.side {
+            Side::Buy => {
+                self.quantity += transaction.quantity;
+                self.cost += transaction.quantity * transaction.price + transaction.fee;
+            }
+            Side::Sell => {
+                if transaction.quantity > self.quantity + f64::EPSILON {
+                    return Err(format!(
+                        "{} 卖出数量 {} 超过持仓 {}",
+                        transaction.stock_code, transaction.quantity, self.quantity
+                    ));
+                }
+                let average_cost = self.average_cost();
+                self.realized +=
+                    transaction.quantity * (transaction.price - average_cost) - transaction.fee;
+                self.cost -= average_cost * transaction.quantity;
+                self.quantity -= transaction.quantity;
+                if self.quantity <= f64::EPSILON {
+                    self.quantity = 0.0;
+                    self.cost = 0.0;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 全部成交记录，保存在数据目录下的 `portfolio.json`
+#[derive(Debug, Default)]
+pub struct Portfolio {
+    pub transactions: Vec<Transaction>,
+}
+
+impl Portfolio {
+    fn path() -> PathBuf {
+        storage::data_dir().join("portfolio.json")
+    }
+
+    pub fn load() -> io::Result<Portfolio> {
+        Ok(Portfolio {
+            transactions: storage::load_json(&Self::path())?.unwrap_or_default(),
+        })
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        storage::save_json(&Self::path(), &self.transactions)
+    }
+
+    /// 记录一笔成交，卖出超过持仓时拒绝
+    pub fn record(&mut self, transaction: Transaction) -> Result<(), String> {
+        let mut transactions = self.transactions.clone();
+        transactions.push(transaction);
+        transactions.sort_by_key(|transaction| transaction.date);
+        Self::compute(&transactions)?;
+        self.transactions = transactions;
+        Ok(())
+    }
+
+    /// 按股票代码汇总的持仓，包括已清仓但有已实现盈亏的股票。
+    /// 手动修改 `portfolio.json` 后卖出可能超过持仓，此时返回错误
+    pub fn positions(&self) -> Result<Vec<Position>, String> {
+        Self::compute(&self.transactions)
+    }
+
+    fn compute(transactions: &[Transaction]) -> Result<Vec<Position>, String> {
+        let mut positions: BTreeMap<&str, Position> = BTreeMap::new();
+        for transaction in transactions {
+            positions
+                .entry(&transaction.stock_code)
+                .or_insert_with(|| Position {
+                    stock_code: transaction.stock_code.clone(),
+                    ..Position::default()
+                })
+                .apply(transaction)?;
+        }
+        Ok(positions.into_values().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2023, 4, day).unwrap()
+    }
+
+    fn trade(side: Side, quantity: f64, price: f64, fee: f64, day: u32) -> Transaction {
+        Transaction {
+            stock_code: String::from("SH512690"),
+            side,
+            quantity,
+            price,
+            fee,
+            date: date(day),
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn average_cost_includes_buy_fees() {
+        let mut portfolio = Portfolio::default();
+        portfolio
+            .record(trade(Side::Buy, 1000.0, 1.0, 5.0, 3))
+            .unwrap();
+        portfolio
+            .record(trade(Side::Buy, 1000.0, 2.0, 5.0, 4))
+            .unwrap();
+        let positions = portfolio.positions().unwrap();
+        assert_eq!(positions.len(), 1);
+        assert_eq!(positions[0].quantity, 2000.0);
+        assert_close(positions[0].cost, 3010.0);
+        assert_close(positions[0].average_cost(), 1.505);
+    }
+
+    #[test]
+    fn realized_and_unrealized() {
+        let mut portfolio = Portfolio::default();
+        portfolio
+            .record(trade(Side::Buy, 1000.0, 1.0, 10.0, 3))
+            .unwrap();
+        portfolio
+            .record(trade(Side::Sell, 400.0, 1.5, 2.0, 4))
+            .unwrap();
+        let position = &portfolio.positions().unwrap()[0];
+        // 卖出部分按平均成本 1.01 结转，扣除卖出手续费
+        assert_close(position.realized, 400.0 * (1.5 - 1.01) - 2.0);
+        assert_eq!(position.quantity, 600.0);
+        assert_close(position.cost, 606.0);
+        assert_close(position.market_value(1.2), 720.0);
+        assert_close(position.unrealized(1.2), 114.0);
+    }
+
+    #[test]
+    fn closed_position_keeps_realized() {
+        let mut portfolio = Portfolio::default();
+        portfolio
+            .record(trade(Side::Buy, 100.0, 2.0, 0.0, 3))
+            .unwrap();
+        portfolio
+            .record(trade(Side::Sell, 100.0, 1.0, 1.0, 4))
+            .unwrap();
+        let position = &portfolio.positions().unwrap()[0];
+        assert_eq!(position.quantity, 0.0);
+        assert_eq!(position.cost, 0.0);
+        assert_eq!(position.average_cost(), 0.0);
+        assert_close(position.realized, -101.0);
+    }
+
+    #[test]
+    fn oversell_is_rejected() {
+        let mut portfolio = Portfolio::default();
+        portfolio
+            .record(trade(Side::Buy, 100.0, 1.0, 0.0, 4))
+            .unwrap();
+        // 按日期排序后先卖后买，卖出超过持仓
+        assert!(portfolio
+            .record(trade(Side::Sell, 100.0, 1.0, 0.0, 3))
+            .is_err());
+        assert_eq!(portfolio.transactions.len(), 1);
+        // 手动写入的记录有误时返回错误而不是空持仓
+        portfolio
+            .transactions
+            .insert(0, trade(Side::Sell, 100.0, 1.0, 0.0, 3));
+        assert!(portfolio.positions().is_err());
+    }
+
+    #[test]
+    fn parse_transaction() {
+        let transaction =
+            Transaction::parse("SH512690", Side::Buy, "1000 0.905 5 2023-04-07", date(1)).unwrap();
+        assert_eq!(transaction, trade(Side::Buy, 1000.0, 0.905, 5.0, 7));
+        let transaction = Transaction::parse("SH512690", Side::Sell, "10 1", date(1)).unwrap();
+        assert_eq!(transaction.fee, 0.0);
+        assert_eq!(transaction.date, date(1));
+        assert!(Transaction::parse("SH512690", Side::Buy, "0 1", date(1)).is_err());
+        assert!(Transaction::parse("SH512690", Side::Buy, "10 -1", date(1)).is_err());
+        assert!(Transaction::parse("SH512690", Side::Buy, "10", date(1)).is_err());
+        assert!(Transaction::parse("SH512690", Side::Buy, "10 1 0 04-07", date(1)).is_err());
+    }
+}
@@ -0,0 +1,106 @@
+use crate::market::MarketStatus;
+use std::time::{Duration, Instant};
+
+/// 定时刷新：交易时段按 `interval` 刷新，其余时间退避到 `closed_interval`
+#[derive(Debug, Clone)]
+pub struct RefreshScheduler {
+    pub interval: Duration,
+    pub closed_interval: Duration,
+    pub paused: bool,
+    last_poll: Option<Instant>,
+}
+
+impl RefreshScheduler {
+    pub fn new(interval: Duration, closed_interval: Duration) -> RefreshScheduler {
+        RefreshScheduler {
+            interval,
+            closed_interval,
+            paused: false,
+            last_poll: None,
+        }
+    }
+
+    pub fn current_interval(&self, status: MarketStatus) -> Duration {
+        match status {
+            MarketStatus::Open => self.interval,
+            MarketStatus::Break | MarketStatus::Closed => self.closed_interval,
+        }
+    }
+
+    /// `now` 时是否到了刷新的时间，返回 `true` 时记为已刷新
+    pub fn poll(&mut self, status: MarketStatus, now: Instant) -> bool {
+        if self.paused {
+            return false;
+        }
+        let due = match self.last_poll {
+            Some(last_poll) => now.duration_since(last_poll) >= self.current_interval(status),
+            None => true,
+        };
+        if due {
+            self.last_poll = Some(now);
+        }
+        due
+    }
+
+    /// 交易时段内超过两个刷新间隔没有更新即认为数据已过期
+    pub fn is_stale(&self, status: MarketStatus, since_update: Duration) -> bool {
+        status == MarketStatus::Open && since_update > self.interval * 2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    fn scheduler() -> RefreshScheduler {
+        RefreshScheduler::new(SECOND * 10, SECOND * 1800)
+    }
+
+    #[test]
+    fn backoff_outside_trading_hours() {
+        let scheduler = scheduler();
+        assert_eq!(scheduler.current_interval(MarketStatus::Open), SECOND * 10);
+        assert_eq!(
+            scheduler.current_interval(MarketStatus::Break),
+            SECOND * 1800
+        );
+        assert_eq!(
+            scheduler.current_interval(MarketStatus::Closed),
+            SECOND * 1800
+        );
+    }
+
+    #[test]
+    fn poll_after_interval() {
+        let mut scheduler = scheduler();
+        let start = Instant::now();
+        // 第一次总是刷新
+        assert!(scheduler.poll(MarketStatus::Open, start));
+        assert!(!scheduler.poll(MarketStatus::Open, start + SECOND * 9));
+        assert!(scheduler.poll(MarketStatus::Open, start + SECOND * 10));
+        // 收盘后按退避间隔刷新
+        let last = start + SECOND * 10;
+        assert!(!scheduler.poll(MarketStatus::Closed, last + SECOND * 60));
+        assert!(scheduler.poll(MarketStatus::Closed, last + SECOND * 1800));
+    }
+
+    #[test]
+    fn paused_never_polls() {
+        let mut scheduler = scheduler();
+        scheduler.paused = true;
+        assert!(!scheduler.poll(MarketStatus::Open, Instant::now()));
+        scheduler.paused = false;
+        assert!(scheduler.poll(MarketStatus::Open, Instant::now()));
+    }
+
+    #[test]
+    fn stale_only_while_open() {
+        let scheduler = scheduler();
+        assert!(!scheduler.is_stale(MarketStatus::Open, SECOND * 20));
+        assert!(scheduler.is_stale(MarketStatus::Open, SECOND * 21));
+        assert!(!scheduler.is_stale(MarketStatus::Break, SECOND * 3600));
+        assert!(!scheduler.is_stale(MarketStatus::Closed, SECOND * 3600));
+    }
+}
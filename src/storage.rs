@@ -0,0 +1,39 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+/// 指定数据目录的环境变量，优先于系统的数据目录
+pub const DATA_DIR_ENV: &str = "STOCK_RS_DATA_DIR";
+
+/// 本地数据目录，如 `~/.local/share/stock_rs`，设置了 `STOCK_RS_DATA_DIR` 时使用它
+pub fn data_dir() -> PathBuf {
+    if let Some(dir) = env::var_os(DATA_DIR_ENV).filter(|dir| !dir.is_empty()) {
+        return PathBuf::from(dir);
+    }
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("stock_rs")
+}
+
+/// 读取 json 文件，文件不存在时返回 `None`
+pub fn load_json<T: DeserializeOwned>(path: &Path) -> io::Result<Option<T>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    let value = serde_json::from_str(&content)?;
+    Ok(Some(value))
+}
+
+/// 写入 json 文件，先写临时文件再重命名，避免写到一半时损坏原文件
+pub fn save_json<T: Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_string_pretty(value)?)?;
+    fs::rename(tmp, path)
+}
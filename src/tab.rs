@@ -0,0 +1,49 @@
+/// 顶部标签页对应的全屏视图
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Tab {
+    /// 大盘与自选列表
+    Overview,
+    /// 单只股票的 K 线图
+    #[default]
+    Chart,
+    /// 资金流向
+    Flows,
+    Portfolio,
+    /// 提醒记录
+    Log,
+}
+
+impl Tab {
+    pub const ALL: [Tab; 5] = [
+        Tab::Overview,
+        Tab::Chart,
+        Tab::Flows,
+        Tab::Portfolio,
+        Tab::Log,
+    ];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Tab::Overview => "概览",
+            Tab::Chart => "图表",
+            Tab::Flows => "资金",
+            Tab::Portfolio => "持仓",
+            Tab::Log => "日志",
+        }
+    }
+
+    pub fn index(&self) -> usize {
+        Tab::ALL
+            .iter()
+            .position(|tab| tab == self)
+            .unwrap_or_default()
+    }
+
+    pub fn next(self) -> Tab {
+        Tab::ALL[(self.index() + 1) % Tab::ALL.len()]
+    }
+
+    pub fn previous(self) -> Tab {
+        Tab::ALL[(self.index() + Tab::ALL.len() - 1) % Tab::ALL.len()]
+    }
+}
@@ -0,0 +1,486 @@
+use crate::storage;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{env, io, path::PathBuf};
+use tui::style::Color;
+
+/// 内置的配色
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Preset {
+    #[default]
+    Dark,
+    Light,
+    /// 红绿色弱友好：以橙色表示上涨、蓝色表示下跌
+    ColorBlind,
+}
+
+/// 终端支持的颜色数量
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorMode {
+    /// 根据 `COLORTERM`、`TERM` 环境变量判断
+    #[default]
+    Auto,
+    /// 24 位真彩色
+    Truecolor,
+    /// 256 色，RGB 颜色换成最接近的 256 色
+    Ansi256,
+    /// 只有 16 种基本颜色，其余颜色换成最接近的基本颜色
+    Ansi16,
+}
+
+impl ColorMode {
+    /// 由 `COLORTERM` 与 `TERM` 的值判断终端支持的颜色
+    fn detect(colorterm: Option<&str>, term: Option<&str>) -> ColorMode {
+        if matches!(colorterm, Some("truecolor" | "24bit")) {
+            ColorMode::Truecolor
+        } else if term.is_some_and(|term| term.contains("256color")) {
+            ColorMode::Ansi256
+        } else {
+            ColorMode::Ansi16
+        }
+    }
+
+    /// `Auto` 时按环境变量得到实际的颜色模式
+    fn resolve(self) -> ColorMode {
+        match self {
+            ColorMode::Auto => ColorMode::detect(
+                env::var("COLORTERM").ok().as_deref(),
+                env::var("TERM").ok().as_deref(),
+            ),
+            mode => mode,
+        }
+    }
+}
+
+/// 界面各部分使用的颜色
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Theme {
+    /// 自选表格的文字与背景
+    #[serde(with = "color")]
+    pub list_fg: Color,
+    #[serde(with = "color")]
+    pub list_bg: Color,
+    /// 自选表格选中行的背景
+    #[serde(with = "color")]
+    pub list_highlight_bg: Color,
+    /// 简要信息面板的文字与背景
+    #[serde(with = "color")]
+    pub header_fg: Color,
+    #[serde(with = "color")]
+    pub header_bg: Color,
+    /// 图表标题
+    #[serde(with = "color")]
+    pub title: Color,
+    /// 坐标轴
+    #[serde(with = "color")]
+    pub axis: Color,
+    /// 时间等次要文字
+    #[serde(with = "color")]
+    pub muted: Color,
+    /// 上涨与下跌：K 线、涨跌幅、盈亏
+    #[serde(with = "color")]
+    pub up: Color,
+    #[serde(with = "color")]
+    pub down: Color,
+    /// 收盘线与面积图
+    #[serde(with = "color")]
+    pub line: Color,
+    /// 光标十字线
+    #[serde(with = "color")]
+    pub crosshair: Color,
+    /// 画线，选中的画线
+    #[serde(with = "color")]
+    pub drawing: Color,
+    #[serde(with = "color")]
+    pub drawing_selected: Color,
+    /// 对比线依次使用的颜色
+    #[serde(with = "color_list")]
+    pub compare: Vec<Color>,
+    /// 暂停、默认值等提示
+    #[serde(with = "color")]
+    pub warning: Color,
+    /// 过期、请求失败等错误
+    #[serde(with = "color")]
+    pub error: Color,
+    /// 提醒横幅的文字与背景
+    #[serde(with = "color")]
+    pub alert_fg: Color,
+    #[serde(with = "color")]
+    pub alert_bg: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::preset(Preset::Dark)
+    }
+}
+
+/// 数据目录下 `theme.json` 的内容：选择的配色、颜色模式，以及覆盖配色的各个颜色
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct ThemeConfig {
+    preset: Preset,
+    color_mode: ColorMode,
+    #[serde(flatten)]
+    colors: Map<String, Value>,
+}
+
+impl Theme {
+    pub fn preset(preset: Preset) -> Theme {
+        match preset {
+            Preset::Dark => Theme {
+                list_fg: Color::Black,
+                list_bg: Color::White,
+                list_highlight_bg: Color::LightGreen,
+                header_fg: Color::White,
+                header_bg: Color::Black,
+                title: Color::Cyan,
+                axis: Color::Gray,
+                muted: Color::Gray,
+                up: Color::Red,
+                down: Color::Green,
+                line: Color::Yellow,
+                crosshair: Color::DarkGray,
+                drawing: Color::Magenta,
+                drawing_selected: Color::Yellow,
+                compare: vec![
+                    Color::Yellow,
+                    Color::Cyan,
+                    Color::Magenta,
+                    Color::LightBlue,
+                    Color::LightRed,
+                    Color::LightGreen,
+                ],
+                warning: Color::Yellow,
+                error: Color::Red,
+                alert_fg: Color::White,
+                alert_bg: Color::Red,
+            },
+            Preset::Light => Theme {
+                list_fg: Color::Black,
+                list_bg: Color::Reset,
+                list_highlight_bg: Color::LightCyan,
+                header_fg: Color::Black,
+                header_bg: Color::Reset,
+                title: Color::Blue,
+                axis: Color::DarkGray,
+                muted: Color::DarkGray,
+                up: Color::Red,
+                down: Color::Green,
+                line: Color::Blue,
+                crosshair: Color::Gray,
+                drawing: Color::Magenta,
+                drawing_selected: Color::Blue,
+                compare: vec![
+                    Color::Blue,
+                    Color::Magenta,
+                    Color::Red,
+                    Color::Green,
+                    Color::Cyan,
+                    Color::Black,
+                ],
+                warning: Color::Magenta,
+                error: Color::Red,
+                alert_fg: Color::White,
+                alert_bg: Color::Red,
+            },
+            Preset::ColorBlind => {
+                // Okabe-Ito 配色
+                let orange = Color::Rgb(230, 159, 0);
+                let blue = Color::Rgb(0, 114, 178);
+                let sky_blue = Color::Rgb(86, 180, 233);
+                let vermillion = Color::Rgb(213, 94, 0);
+                let reddish_purple = Color::Rgb(204, 121, 167);
+                let yellow = Color::Rgb(240, 228, 66);
+                Theme {
+                    list_fg: Color::Black,
+                    list_bg: Color::White,
+                    list_highlight_bg: sky_blue,
+                    header_fg: Color::White,
+                    header_bg: Color::Black,
+                    title: sky_blue,
+                    axis: Color::Gray,
+                    muted: Color::Gray,
+                    up: orange,
+                    down: blue,
+                    line: yellow,
+                    crosshair: Color::DarkGray,
+                    drawing: reddish_purple,
+                    drawing_selected: yellow,
+                    compare: vec![orange, sky_blue, yellow, blue, vermillion, reddish_purple],
+                    warning: yellow,
+                    error: vermillion,
+                    alert_fg: Color::Black,
+                    alert_bg: orange,
+                }
+            }
+        }
+    }
+
+    fn path() -> PathBuf {
+        storage::data_dir().join("theme.json")
+    }
+
+    /// 读取数据目录下的 `theme.json`，如
+    /// `{"preset": "light", "color_mode": "ansi16", "up": "#ff5f00", "compare": ["blue", "208"]}`，
+    /// 文件不存在时使用深色配色
+    pub fn load() -> io::Result<Theme> {
+        let config: ThemeConfig = storage::load_json(&Self::path())?.unwrap_or_default();
+        let mut theme = Theme::preset(config.preset);
+        if !config.colors.is_empty() {
+            let mut value = serde_json::to_value(&theme)?;
+            if let Value::Object(fields) = &mut value {
+                fields.extend(config.colors);
+            }
+            theme = serde_json::from_value(value)?;
+        }
+        Ok(theme.with_color_mode(config.color_mode))
+    }
+
+    /// 按终端支持的颜色转换全部颜色
+    pub fn with_color_mode(self, color_mode: ColorMode) -> Theme {
+        match color_mode.resolve() {
+            ColorMode::Ansi16 => self.map_colors(to_ansi16),
+            ColorMode::Ansi256 => self.map_colors(to_ansi256),
+            _ => self,
+        }
+    }
+
+    fn map_colors(&self, c: fn(Color) -> Color) -> Theme {
+        Theme {
+            list_fg: c(self.list_fg),
+            list_bg: c(self.list_bg),
+            list_highlight_bg: c(self.list_highlight_bg),
+            header_fg: c(self.header_fg),
+            header_bg: c(self.header_bg),
+            title: c(self.title),
+            axis: c(self.axis),
+            muted: c(self.muted),
+            up: c(self.up),
+            down: c(self.down),
+            line: c(self.line),
+            crosshair: c(self.crosshair),
+            drawing: c(self.drawing),
+            drawing_selected: c(self.drawing_selected),
+            compare: self.compare.iter().map(|color| c(*color)).collect(),
+            warning: c(self.warning),
+            error: c(self.error),
+            alert_fg: c(self.alert_fg),
+            alert_bg: c(self.alert_bg),
+        }
+    }
+}
+
+/// 16 种基本颜色及其常见的 RGB 值
+const ANSI16: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+/// 256 色中编号为 `index` 的颜色的 RGB 值
+fn indexed_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => ANSI16[index as usize].1,
+        16..=231 => {
+            let level = |value: u8| if value == 0 { 0 } else { 55 + value * 40 };
+            let index = index - 16;
+            (level(index / 36), level(index / 6 % 6), level(index % 6))
+        }
+        _ => {
+            let gray = 8 + (index - 232) * 10;
+            (gray, gray, gray)
+        }
+    }
+}
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> i32 {
+    let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+    d(r1, r2) + d(g1, g2) + d(b1, b2)
+}
+
+/// 把 RGB 颜色换成最接近的 256 色，跳过前 16 种随终端配色变化的颜色
+fn to_ansi256(color: Color) -> Color {
+    match color {
+        Color::Rgb(r, g, b) => {
+            let index = (16..=255)
+                .min_by_key(|index| distance(indexed_rgb(*index), (r, g, b)))
+                .unwrap_or(16);
+            Color::Indexed(index)
+        }
+        color => color,
+    }
+}
+
+fn to_ansi16(color: Color) -> Color {
+    let (r, g, b) = match color {
+        Color::Rgb(r, g, b) => (r, g, b),
+        Color::Indexed(index) => indexed_rgb(index),
+        color => return color,
+    };
+    ANSI16
+        .iter()
+        .min_by_key(|(_, rgb)| distance(*rgb, (r, g, b)))
+        .map(|(color, _)| *color)
+        .unwrap_or(Color::Reset)
+}
+
+/// 颜色的文字形式：`red`、`light_blue` 等名称，`#rrggbb`，或 256 色的编号
+mod color {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use tui::style::Color;
+
+    const NAMES: [(&str, Color); 17] = [
+        ("reset", Color::Reset),
+        ("black", Color::Black),
+        ("red", Color::Red),
+        ("green", Color::Green),
+        ("yellow", Color::Yellow),
+        ("blue", Color::Blue),
+        ("magenta", Color::Magenta),
+        ("cyan", Color::Cyan),
+        ("gray", Color::Gray),
+        ("dark_gray", Color::DarkGray),
+        ("light_red", Color::LightRed),
+        ("light_green", Color::LightGreen),
+        ("light_yellow", Color::LightYellow),
+        ("light_blue", Color::LightBlue),
+        ("light_magenta", Color::LightMagenta),
+        ("light_cyan", Color::LightCyan),
+        ("white", Color::White),
+    ];
+
+    pub fn parse(text: &str) -> Option<Color> {
+        let text = text.trim().to_lowercase();
+        if let Some(hex) = text.strip_prefix('#') {
+            let value = u32::from_str_radix(hex, 16)
+                .ok()
+                .filter(|_| hex.len() == 6)?;
+            return Some(Color::Rgb(
+                (value >> 16) as u8,
+                (value >> 8) as u8,
+                value as u8,
+            ));
+        }
+        if let Ok(index) = text.parse::<u8>() {
+            return Some(Color::Indexed(index));
+        }
+        NAMES
+            .iter()
+            .find(|(name, _)| *name == text)
+            .map(|(_, color)| *color)
+    }
+
+    pub fn format(color: &Color) -> String {
+        match color {
+            Color::Rgb(r, g, b) => format!("#{:02x}{:02x}{:02x}", r, g, b),
+            Color::Indexed(index) => index.to_string(),
+            color => NAMES
+                .iter()
+                .find(|(_, named)| named == color)
+                .map(|(name, _)| name.to_string())
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn serialize<S: Serializer>(color: &Color, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format(color))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        parse(&text).ok_or_else(|| D::Error::custom(format!("invalid color {:?}", text)))
+    }
+}
+
+mod color_list {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use tui::style::Color;
+
+    pub fn serialize<S: Serializer>(colors: &[Color], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(colors.iter().map(super::color::format))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Color>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|text| {
+                super::color::parse(text)
+                    .ok_or_else(|| D::Error::custom(format!("invalid color {:?}", text)))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_color_mode() {
+        let detect = ColorMode::detect;
+        assert_eq!(
+            detect(Some("truecolor"), Some("xterm-256color")),
+            ColorMode::Truecolor
+        );
+        assert_eq!(detect(Some("24bit"), None), ColorMode::Truecolor);
+        assert_eq!(detect(None, Some("xterm-256color")), ColorMode::Ansi256);
+        assert_eq!(
+            detect(Some(""), Some("screen-256color")),
+            ColorMode::Ansi256
+        );
+        assert_eq!(detect(None, Some("xterm")), ColorMode::Ansi16);
+        assert_eq!(detect(None, None), ColorMode::Ansi16);
+        // 明确指定时不读取环境变量
+        assert_eq!(ColorMode::Ansi256.resolve(), ColorMode::Ansi256);
+        assert_eq!(ColorMode::Truecolor.resolve(), ColorMode::Truecolor);
+    }
+
+    #[test]
+    fn convert_colors() {
+        assert_eq!(to_ansi256(Color::Rgb(255, 0, 0)), Color::Indexed(196));
+        assert_eq!(to_ansi256(Color::Rgb(230, 159, 0)), Color::Indexed(178));
+        assert_eq!(to_ansi256(Color::Rgb(128, 128, 128)), Color::Indexed(244));
+        assert_eq!(to_ansi256(Color::Red), Color::Red);
+        assert_eq!(to_ansi16(Color::Rgb(250, 10, 10)), Color::LightRed);
+        assert_eq!(to_ansi16(Color::Indexed(21)), Color::Blue);
+        assert_eq!(to_ansi16(Color::Reset), Color::Reset);
+    }
+
+    #[test]
+    fn color_mode_applies_to_theme() {
+        let theme = Theme::preset(Preset::ColorBlind);
+        assert_eq!(theme.clone().with_color_mode(ColorMode::Truecolor), theme);
+        let ansi256 = theme.clone().with_color_mode(ColorMode::Ansi256);
+        assert!(matches!(ansi256.up, Color::Indexed(_)));
+        assert_eq!(ansi256.list_fg, Color::Black);
+        let ansi16 = theme.with_color_mode(ColorMode::Ansi16);
+        assert!(!matches!(ansi16.up, Color::Rgb(..) | Color::Indexed(_)));
+    }
+
+    #[test]
+    fn parse_and_format_colors() {
+        for text in ["red", "light_blue", "#ff5f00", "208", "reset"] {
+            assert_eq!(color::format(&color::parse(text).unwrap()), text);
+        }
+        assert_eq!(color::parse(" Dark_Gray "), Some(Color::DarkGray));
+        assert_eq!(color::parse("#fff"), None);
+        assert_eq!(color::parse("256"), None);
+        assert_eq!(color::parse("purple"), None);
+    }
+}
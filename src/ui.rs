@@ -1,158 +1,1123 @@
-use crate::app::App;
+use crate::app::{App, Control, Input};
+use crate::decode::FindingKind;
+use crate::keymap::Key;
+use crate::logging;
+use crate::tab::Tab;
+use crate::theme::Theme;
+use crate::watchlist::{format_amount, Column};
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    cursor,
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers, MouseButton,
+        MouseEventKind,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use custom_widget::kline::kline::{Axis, Dataset, KLine};
+use custom_widget::kline::{
+    annotation::Annotation,
+    kline::{Axis, Dataset, KLine},
+    klinedata::ChartType,
+};
+use log::Level;
 use std::{
     error::Error,
-    io,
+    io, panic,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
     time::{Duration, Instant},
 };
 use tui::{
     backend::{Backend, CrosstermBackend},
-    layout::{Constraint, Direction, Layout, Rect},
-    style::Color,
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Modifier, Style},
     symbols,
     text::{Span, Spans},
-    widgets::{Block, Borders, List, ListItem, Paragraph, Wrap},
+    widgets::{
+        Block, Borders, Cell, Clear, List, ListItem, ListState, Paragraph, Row, Sparkline, Table,
+        TableState, Tabs, Wrap,
+    },
     Frame, Terminal,
 };
+use unicode_width::UnicodeWidthStr;
+
+/// 退出时等待进行中请求的最长时间
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// 恢复终端：退出原始模式、备用屏幕和鼠标捕获，出错时也尽量执行完每一步
+fn restore_terminal() {
+    let _ = disable_raw_mode();
+    let _ = execute!(
+        io::stdout(),
+        LeaveAlternateScreen,
+        DisableMouseCapture,
+        cursor::Show
+    );
+}
+
+/// 离开作用域时恢复终端，`run_app` 提前返回错误时也不会留下错乱的终端
+struct TerminalGuard;
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore_terminal();
+    }
+}
+
+/// 在后台监听 Ctrl-C 和 SIGTERM，收到后设置返回的标志
+fn listen_for_signals() -> Arc<AtomicBool> {
+    let quit = Arc::new(AtomicBool::new(false));
+    let flag = quit.clone();
+    tokio::spawn(async move {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            match signal(SignalKind::terminate()) {
+                Ok(mut terminate) => {
+                    tokio::select! {
+                        _ = tokio::signal::ctrl_c() => {}
+                        _ = terminate.recv() => {}
+                    }
+                }
+                Err(err) => {
+                    log::error!("listen for SIGTERM failed: {}", err);
+                    let _ = tokio::signal::ctrl_c().await;
+                }
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = tokio::signal::ctrl_c().await;
+        flag.store(true, Ordering::SeqCst);
+    });
+    quit
+}
+
+pub fn run<'a>(mut app: App<'a>, tick_rate: Duration) -> Result<(), Box<dyn Error>> {
+    // panic 时先恢复终端，错误信息才能正常显示
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        log::error!("{}", info);
+        restore_terminal();
+        default_hook(info);
+    }));
+    let quit = listen_for_signals();
 
-pub fn run<'a>(app: App<'a>, tick_rate: Duration) -> Result<(), Box<dyn Error>> {
     enable_raw_mode()?;
+    let guard = TerminalGuard;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let res = run_app(&mut terminal, app, tick_rate);
+    let res = run_app(&mut terminal, &mut app, tick_rate, &quit);
 
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    terminal.show_cursor()?;
-    if let Err(err) = res {
-        println!("{:?}", err)
+    // 先恢复终端再返回错误，错误信息由 main 打印到正常的屏幕上
+    drop(guard);
+    app.shutdown(SHUTDOWN_TIMEOUT);
+    if let Err(ref err) = res {
+        log::error!("run app failed: {}", err);
     }
-    Ok(())
+    res
 }
 
 fn run_app<'a, B: Backend>(
     terminal: &mut Terminal<B>,
-    mut app: App<'a>,
+    app: &mut App<'a>,
     tick_rate: Duration,
+    quit: &AtomicBool,
 ) -> Result<(), Box<dyn Error>> {
     let mut last_tick = Instant::now();
-    loop {
-        terminal.draw(|f| draw(f, &mut app))?;
+    while !quit.load(Ordering::SeqCst) {
+        terminal.draw(|f| draw(f, app))?;
 
         let timeout = tick_rate
             .checked_sub(last_tick.elapsed())
             .unwrap_or_else(|| Duration::from_secs(0));
 
         if crossterm::event::poll(timeout)? {
-            if let Event::Key(key) = event::read()? {
-                match key.code {
-                    KeyCode::Char(i) => app.on_key(i),
-                    KeyCode::Left => {}
-                    KeyCode::Up => app.on_up(),
-                    KeyCode::Right => {}
-                    KeyCode::Down => app.on_bottom(),
-                    _ => {}
+            match event::read()? {
+                // 原始模式下 Ctrl-C 不会产生信号，作为按键处理
+                Event::Key(key)
+                    if key.code == KeyCode::Char('c')
+                        && key.modifiers.contains(KeyModifiers::CONTROL) =>
+                {
+                    return Ok(());
+                }
+                Event::Key(key) => match app.on_key(Key::from(key)) {
+                    Control::Quit => return Ok(()),
+                    Control::Continue => {}
+                },
+                Event::Resize(width, height) => {
+                    // 清空缓冲区，整屏重绘，避免尺寸变化后残留旧内容
+                    terminal.resize(Rect::new(0, 0, width, height))?;
+                    app.on_resize();
                 }
+                Event::Mouse(mouse) => match mouse.kind {
+                    MouseEventKind::Down(MouseButton::Left) => {
+                        app.on_list_click(mouse.column, mouse.row);
+                        app.on_chart_click(mouse.column, mouse.row);
+                    }
+                    MouseEventKind::Down(MouseButton::Right) => {
+                        app.on_chart_right_click(mouse.column, mouse.row)
+                    }
+                    MouseEventKind::Drag(MouseButton::Left) => {
+                        app.on_chart_drag(mouse.column, mouse.row)
+                    }
+                    MouseEventKind::Up(MouseButton::Left) => app.on_mouse_up(),
+                    MouseEventKind::Moved => app.on_chart_hover(mouse.column, mouse.row),
+                    MouseEventKind::ScrollDown => app.on_scroll(mouse.column, mouse.row, true),
+                    MouseEventKind::ScrollUp => app.on_scroll(mouse.column, mouse.row, false),
+                    _ => {}
+                },
+                _ => {}
             }
         }
         if last_tick.elapsed() >= tick_rate {
+            app.on_tick();
             last_tick = Instant::now();
         }
     }
+    Ok(())
 }
 
+/// 能正常显示的最小终端尺寸
+const MIN_WIDTH: u16 = 60;
+const MIN_HEIGHT: u16 = 15;
+
+/// 显示侧边自选列表时 K 线图至少保留的宽度，不足时收起自选列表
+const MIN_CHART_WIDTH: u16 = 50;
+
+/// 股票区域低于该高度时简要信息只显示一行
+const COMPACT_HEADER_HEIGHT: u16 = 24;
+
+/// 股票区域低于该高度时不显示简要信息
+const MIN_HEADER_HEIGHT: u16 = 12;
+
+/// 概览中大盘面板的高度
+const OVERVIEW_INDEX_HEIGHT: u16 = 7;
+
+/// 概览中的列表低于该高度时不显示大盘面板
+const MIN_OVERVIEW_LIST_HEIGHT: u16 = 8;
+
 pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    // 没有绘制的区域不响应鼠标
+    app.chart_area = Rect::default();
+    app.list_area = Rect::default();
+    let mut area = f.size();
+    if area.width < MIN_WIDTH || area.height < MIN_HEIGHT {
+        draw_too_small(f, area);
+        return;
+    }
+    if let Some(event) = app.alerts.banner() {
+        let banner = Paragraph::new(format!(
+            " ⚠ {} {} {}",
+            event.time.format("%H:%M:%S"),
+            event.stock_code,
+            event.message
+        ))
+        .style(
+            Style::default()
+                .fg(app.theme.alert_fg)
+                .bg(app.theme.alert_bg)
+                .add_modifier(Modifier::BOLD),
+        );
+        f.render_widget(banner, Rect { height: 1, ..area });
+        area.y += 1;
+        area.height = area.height.saturating_sub(1);
+    }
+    if let Some(ref input) = app.input {
+        area.height = area.height.saturating_sub(1);
+        draw_input(
+            f,
+            input,
+            &app.theme,
+            Rect {
+                y: area.bottom(),
+                height: 1,
+                ..area
+            },
+        );
+    }
     let chunks = Layout::default()
-        .direction(Direction::Horizontal)
+        .direction(Direction::Vertical)
         .margin(1)
-        .constraints([Constraint::Percentage(10), Constraint::Percentage(90)].as_ref())
-        .split(f.size());
+        .constraints([Constraint::Length(1), Constraint::Min(0)].as_ref())
+        .split(area);
+    draw_tabs(f, app, chunks[0]);
+    match app.tab {
+        Tab::Overview => draw_overview(f, app, chunks[1]),
+        Tab::Chart => draw_chart_tab(f, app, chunks[1]),
+        Tab::Flows => draw_flows(f, app, chunks[1]),
+        Tab::Portfolio => draw_portfolio(f, app, chunks[1]),
+        Tab::Log => draw_log(f, app, chunks[1]),
+    }
+    if app.show_alert_log {
+        draw_alert_log(f, app, centered_rect(60, 60, f.size()));
+    }
+    if app.show_debug {
+        draw_debug(f, app, centered_rect(80, 70, f.size()));
+    }
+    if let Some(cursor) = app.column_cursor {
+        draw_columns(f, app, cursor, centered_rect(40, 50, f.size()));
+    }
+    if app.show_help {
+        draw_help(f, app, centered_rect(60, 80, f.size()));
+    }
+}
+
+/// 选择自选列表展示的列，上下移动，回车打开或关闭
+fn draw_columns<B: Backend>(f: &mut Frame<B>, app: &App, cursor: usize, area: Rect) {
+    let items: Vec<ListItem> = Column::ALL
+        .iter()
+        .map(|column| {
+            let mark = if app.watchlist.columns.contains(column) {
+                "[x]"
+            } else {
+                "[ ]"
+            };
+            ListItem::new(format!("{} {}", mark, column.title()))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("展示的列（回车切换，Esc 关闭）"),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut state = ListState::default();
+    state.select(Some(cursor));
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+/// 按当前键位映射生成的按键帮助
+fn draw_help<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let help = app.keymap.help();
+    let key_width = help
+        .iter()
+        .map(|(keys, _)| keys.width() as u16)
+        .max()
+        .unwrap_or_default();
+    let rows: Vec<Row> = help
+        .into_iter()
+        .map(|(keys, description)| {
+            Row::new(vec![
+                Cell::from(keys).style(Style::default().add_modifier(Modifier::BOLD)),
+                Cell::from(description),
+            ])
+        })
+        .collect();
+    let widths = [
+        Constraint::Length(key_width),
+        Constraint::Length(area.width.saturating_sub(key_width + 4)),
+    ];
+    let table = Table::new(rows)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("按键（? 或 Esc 关闭）"),
+        )
+        .widths(&widths)
+        .column_spacing(2);
+    f.render_widget(Clear, area);
+    f.render_widget(table, area);
+}
+
+fn draw_tabs<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let titles = Tab::ALL
+        .iter()
+        .enumerate()
+        .map(|(i, tab)| Spans::from(format!("{} {}", i + 1, tab.title())))
+        .collect();
+    let tabs = Tabs::new(titles)
+        .select(app.tab.index())
+        .style(Style::default().fg(app.theme.muted))
+        .highlight_style(
+            Style::default()
+                .fg(app.theme.title)
+                .add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+        );
+    f.render_widget(tabs, area);
+}
+
+/// 终端小于最小尺寸时只显示提示
+fn draw_too_small<B: Backend>(f: &mut Frame<B>, area: Rect) {
+    let text = vec![
+        Spans::from("终端太小"),
+        Spans::from(format!(
+            "需要至少 {}×{}，当前 {}×{}",
+            MIN_WIDTH, MIN_HEIGHT, area.width, area.height
+        )),
+    ];
+    let paragraph = Paragraph::new(text)
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: true });
+    let height = area.height.min(2);
+    let y = area.y + (area.height - height) / 2;
+    f.render_widget(paragraph, Rect { y, height, ..area });
+}
+
+/// 自选列表与当前股票的图表，宽度不足或手动收起时只显示图表
+fn draw_chart_tab<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
+    let list_width = watchlist_width(app);
+    if !app.show_sidebar || area.width < list_width + MIN_CHART_WIDTH {
+        draw_stock_block(f, app, area);
+        return;
+    }
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Length(list_width), Constraint::Min(0)].as_ref())
+        .split(area);
     draw_list(f, app, chunks[0]);
     draw_stock_block(f, app, chunks[1]);
 }
 
-fn draw_list<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
+/// 大盘指数的分时走势与自选列表的涨跌统计，下方为完整的自选列表
+fn draw_overview<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
+    if area.height < OVERVIEW_INDEX_HEIGHT + MIN_OVERVIEW_LIST_HEIGHT {
+        draw_list(f, app, area);
+        return;
+    }
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Length(OVERVIEW_INDEX_HEIGHT),
+                Constraint::Min(0),
+            ]
+            .as_ref(),
+        )
+        .split(area);
+    let top = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Length(32), Constraint::Min(0)].as_ref())
+        .split(chunks[0]);
+
+    let directions: Vec<i8> = app
+        .stock_ids
+        .items
+        .iter()
+        .filter_map(|item| Some(item.quote()?.direction()))
+        .collect();
+    let count = |direction: i8| directions.iter().filter(|&&d| d == direction).count();
+    let mut text = Vec::new();
+    let mut title = String::from("大盘");
+    let mut trend: Vec<u64> = Vec::new();
+    if let Some(szzs) = app.market_index() {
+        let ends: Vec<f32> = szzs
+            .get_trends()
+            .iter()
+            .map(|trend| trend.get_end())
+            .collect();
+        let last = ends.last().copied().unwrap_or_default();
+        let change = last - szzs.get_pre_close();
+        let percent = match szzs.get_pre_close() {
+            pre_close if pre_close > 0.0 => change / pre_close * 100.0,
+            _ => 0.0,
+        };
+        let color = if change > 0.0 {
+            app.theme.up
+        } else if change < 0.0 {
+            app.theme.down
+        } else {
+            app.theme.header_fg
+        };
+        title = szzs.get_name().to_string();
+        text.push(Spans::from(Span::styled(
+            format!("{:.2}", last),
+            Style::default().fg(color).add_modifier(Modifier::BOLD),
+        )));
+        text.push(Spans::from(Span::styled(
+            format!("{:+.2}  {:+.2}%", change, percent),
+            Style::default().fg(color),
+        )));
+        // 以最低点为 0，保留两位小数的精度
+        let min = ends.iter().cloned().fold(f32::MAX, f32::min);
+        trend = ends
+            .iter()
+            .map(|end| ((end - min) * 100.0).round() as u64)
+            .collect();
+    } else {
+        text.push(Spans::from("尚未加载数据"));
+    }
+    text.push(Spans::from(""));
+    text.push(Spans::from(vec![
+        Span::raw("自选 "),
+        Span::styled(
+            format!("涨 {}", count(1)),
+            Style::default().fg(app.theme.up),
+        ),
+        Span::raw("  "),
+        Span::styled(
+            format!("跌 {}", count(-1)),
+            Style::default().fg(app.theme.down),
+        ),
+        Span::raw(format!("  平 {}", count(0))),
+    ]));
+    let summary = Paragraph::new(text)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .style(
+            Style::default()
+                .fg(app.theme.header_fg)
+                .bg(app.theme.header_bg),
+        );
+    f.render_widget(summary, top[0]);
+    let sparkline = Sparkline::default()
+        .block(Block::default().borders(Borders::ALL).title("分时"))
+        .style(Style::default().fg(app.theme.line))
+        .data(&trend);
+    f.render_widget(sparkline, top[1]);
+    draw_list(f, app, chunks[1]);
+}
+
+/// 当前股票每日的主力、超大单、大单净流入，最新的在前
+fn draw_flows<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
+    let theme = &app.theme;
+    let stock_name = app
+        .selected_item_brief_info()
+        .map_or("", |item| item.stock_name.as_ref());
+    let mut main_ins: Vec<_> = app
+        .selected_day_data()
+        .map(|stock_data| stock_data.get_past_main_ins().iter().collect())
+        .unwrap_or_default();
+    main_ins.sort_by(|a, b| b.get_date().cmp(a.get_date()));
+    let signed = |value: f64| {
+        let color = if value > 0.0 {
+            theme.up
+        } else if value < 0.0 {
+            theme.down
+        } else {
+            theme.list_fg
+        };
+        Style::default().fg(color)
+    };
+    let rows: Vec<Row> = main_ins
+        .iter()
+        .map(|main_in| {
+            let amount =
+                |value: i32| Cell::from(format_amount(value as f64)).style(signed(value as f64));
+            Row::new(vec![
+                Cell::from(main_in.get_date().to_string()),
+                amount(main_in.get_main()),
+                amount(main_in.get_bigger()),
+                amount(main_in.get_big()),
+                Cell::from(format!("{:+.2}%", main_in.get_up_rate()))
+                    .style(signed(main_in.get_up_rate() as f64)),
+                Cell::from(format!("{:.3}", main_in.get_price())),
+            ])
+        })
+        .collect();
+    let total = |days: usize| {
+        main_ins
+            .iter()
+            .take(days)
+            .fold(0.0, |sum, main_in| sum + main_in.get_main() as f64)
+    };
+    let title = Spans::from(vec![
+        Span::raw(format!("资金流向 {}  ", stock_name)),
+        Span::raw("5日主力 "),
+        Span::styled(format_amount(total(5)), signed(total(5))),
+        Span::raw("  10日主力 "),
+        Span::styled(format_amount(total(10)), signed(total(10))),
+    ]);
+    let header = Row::new(vec!["日期", "主力", "超大单", "大单", "涨幅", "价格"])
+        .style(Style::default().add_modifier(Modifier::BOLD));
+    let widths = [
+        Constraint::Length(12),
+        Constraint::Length(10),
+        Constraint::Length(10),
+        Constraint::Length(10),
+        Constraint::Length(8),
+        Constraint::Length(8),
+    ];
+    let table = Table::new(rows)
+        .header(header)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .widths(&widths)
+        .highlight_style(Style::default().bg(theme.list_highlight_bg));
+    f.render_stateful_widget(table, area, &mut app.flows_state);
+}
+
+/// 上方为全部提醒记录，最新的在前；下方为最近的运行日志，最新的在后
+fn draw_log<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(40), Constraint::Percentage(60)].as_ref())
+        .split(area);
+    draw_alert_history(f, app, chunks[0]);
+    draw_log_entries(f, app, chunks[1]);
+}
+
+/// 最近的运行日志，只显示放得下的最新几条
+fn draw_log_entries<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let entries = logging::recent();
+    let visible = area.height.saturating_sub(2) as usize;
+    let items: Vec<ListItem> = entries
+        .iter()
+        .skip(entries.len().saturating_sub(visible))
+        .map(|entry| {
+            let color = match entry.level {
+                Level::Error => app.theme.error,
+                Level::Warn => app.theme.warning,
+                _ => app.theme.muted,
+            };
+            ListItem::new(Spans::from(vec![
+                Span::styled(
+                    entry.time.format("%H:%M:%S ").to_string(),
+                    Style::default().fg(app.theme.muted),
+                ),
+                Span::styled(format!("{:<5} ", entry.level), Style::default().fg(color)),
+                Span::raw(entry.message.as_str()),
+            ]))
+        })
+        .collect();
+    let title = format!("运行日志 {}", logging::path().display());
+    let list = List::new(items).block(Block::default().borders(Borders::ALL).title(title));
+    f.render_widget(list, area);
+}
+
+fn draw_alert_history<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
+    let rows: Vec<Row> = app
+        .alerts
+        .log
+        .iter()
+        .rev()
+        .map(|event| {
+            Row::new(vec![
+                Cell::from(event.time.format("%m-%d %H:%M:%S").to_string())
+                    .style(Style::default().fg(app.theme.muted)),
+                Cell::from(event.stock_code.clone())
+                    .style(Style::default().add_modifier(Modifier::BOLD)),
+                Cell::from(event.message.clone()),
+            ])
+        })
+        .collect();
+    let title = format!("提醒记录（{} 条规则）", app.alerts.rules.len());
+    let widths = [
+        Constraint::Length(14),
+        Constraint::Length(10),
+        Constraint::Length(area.width.saturating_sub(28)),
+    ];
+    let table = Table::new(rows)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .widths(&widths)
+        .highlight_style(Style::default().bg(app.theme.list_highlight_bg));
+    f.render_stateful_widget(table, area, &mut app.log_state);
+}
+
+fn draw_input<B: Backend>(f: &mut Frame<B>, input: &Input, theme: &Theme, area: Rect) {
+    let mut spans = vec![
+        Span::styled(
+            format!(" {}: ", input.purpose.prompt()),
+            Style::default().add_modifier(Modifier::BOLD),
+        ),
+        Span::raw(format!("{}_", input.text)),
+    ];
+    if let Some(ref error) = input.error {
+        spans.push(Span::styled(
+            format!("  {}", error),
+            Style::default().fg(theme.error),
+        ));
+    }
+    f.render_widget(Paragraph::new(Spans::from(spans)), area);
+}
+
+fn draw_portfolio<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("持仓（b 买入 e 卖出）");
+    let positions = match app.portfolio.positions() {
+        Ok(positions) => positions,
+        Err(err) => {
+            let text = vec![
+                Spans::from(Span::styled(
+                    format!("成交记录有误：{}", err),
+                    Style::default().fg(app.theme.error),
+                )),
+                Spans::from(Span::styled(
+                    "请检查数据目录下的 portfolio.json",
+                    Style::default().fg(app.theme.muted),
+                )),
+            ];
+            let paragraph = Paragraph::new(text).block(block).wrap(Wrap { trim: true });
+            f.render_widget(paragraph, area);
+            return;
+        }
+    };
+    let price_of = |stock_code: &str| {
+        app.stock_ids
+            .items
+            .iter()
+            .find(|item| item.stock_code == stock_code)
+            .and_then(|item| item.quote())
+            .map(|quote| quote.price)
+    };
+    let total_value: f64 = positions
+        .iter()
+        .filter_map(|position| Some(position.market_value(price_of(&position.stock_code)?)))
+        .fold(0.0, |sum, value| sum + value);
+    let pnl_style = |value: f64| {
+        if value > 0.0 {
+            Style::default().fg(app.theme.up)
+        } else if value < 0.0 {
+            Style::default().fg(app.theme.down)
+        } else {
+            Style::default()
+        }
+    };
+    let (mut total_unrealized, mut total_realized) = (0.0, 0.0);
+    let mut rows: Vec<Row> = positions
+        .iter()
+        .map(|position| {
+            let price = price_of(&position.stock_code);
+            let unrealized = price.map(|price| position.unrealized(price));
+            total_unrealized += unrealized.unwrap_or_default();
+            total_realized += position.realized;
+            let optional = |value: Option<f64>, precision: usize| {
+                value
+                    .map(|value| format!("{:.*}", precision, value))
+                    .unwrap_or_else(|| String::from("-"))
+            };
+            let weight = price
+                .filter(|_| total_value > 0.0)
+                .map(|price| position.market_value(price) / total_value * 100.0);
+            Row::new(vec![
+                Cell::from(position.stock_code.clone()),
+                Cell::from(format!("{}", position.quantity)),
+                Cell::from(format!("{:.3}", position.average_cost())),
+                Cell::from(optional(price, 3)),
+                Cell::from(optional(price.map(|price| position.market_value(price)), 2)),
+                Cell::from(optional(unrealized, 2))
+                    .style(pnl_style(unrealized.unwrap_or_default())),
+                Cell::from(format!("{:.2}", position.realized)).style(pnl_style(position.realized)),
+                Cell::from(optional(weight, 1)),
+            ])
+        })
+        .collect();
+    rows.push(
+        Row::new(vec![
+            Cell::from("合计"),
+            Cell::from(""),
+            Cell::from(""),
+            Cell::from(""),
+            Cell::from(format!("{:.2}", total_value)),
+            Cell::from(format!("{:.2}", total_unrealized)).style(pnl_style(total_unrealized)),
+            Cell::from(format!("{:.2}", total_realized)).style(pnl_style(total_realized)),
+            Cell::from(""),
+        ])
+        .style(Style::default().add_modifier(Modifier::BOLD)),
+    );
+    let header = Row::new(vec![
+        "代码",
+        "持仓",
+        "成本",
+        "现价",
+        "市值",
+        "浮动盈亏",
+        "已实现",
+        "权重%",
+    ])
+    .style(Style::default().add_modifier(Modifier::BOLD));
+    let widths = [
+        Constraint::Length(9),
+        Constraint::Length(10),
+        Constraint::Length(8),
+        Constraint::Length(8),
+        Constraint::Length(12),
+        Constraint::Length(10),
+        Constraint::Length(10),
+        Constraint::Length(6),
+    ];
+    let table = Table::new(rows)
+        .header(header)
+        .block(block)
+        .widths(&widths)
+        .highlight_style(Style::default().bg(app.theme.list_highlight_bg));
+    f.render_stateful_widget(table, area, &mut app.portfolio_state);
+}
+
+/// 在 `area` 中居中、按百分比计算的矩形
+fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
+    let width = area.width * percent_x / 100;
+    let height = area.height * percent_y / 100;
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+fn draw_alert_log<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
     let items: Vec<ListItem> = app
+        .alerts
+        .log
+        .iter()
+        .rev()
+        .map(|event| {
+            ListItem::new(Spans::from(vec![
+                Span::styled(
+                    event.time.format("%m-%d %H:%M:%S ").to_string(),
+                    Style::default().fg(app.theme.muted),
+                ),
+                Span::styled(
+                    format!("{} ", event.stock_code),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(event.message.as_str()),
+            ]))
+        })
+        .collect();
+    let title = format!("提醒日志（{} 条规则）", app.alerts.rules.len());
+    let list = List::new(items).block(Block::default().borders(Borders::ALL).title(title));
+    f.render_widget(Clear, area);
+    f.render_widget(list, area);
+}
+
+/// 当前股票最近一次数据的解析与校验报告
+fn draw_debug<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let item = app.selected_item_brief_info();
+    let report = item
+        .and_then(|item| item.stock_data.as_ref())
+        .map(|stock_data| stock_data.get_report());
+    let items: Vec<ListItem> = match report {
+        None => vec![ListItem::new("尚未加载数据")],
+        Some(report) if report.is_empty() => vec![ListItem::new("没有发现问题")],
+        Some(report) => report
+            .findings
+            .iter()
+            .map(|finding| {
+                let color = match finding.kind {
+                    FindingKind::Invalid | FindingKind::Dropped => app.theme.error,
+                    FindingKind::Defaulted | FindingKind::Version => app.theme.warning,
+                };
+                ListItem::new(Spans::from(vec![
+                    Span::styled(
+                        format!("[{}] ", finding.kind.name()),
+                        Style::default().fg(color),
+                    ),
+                    Span::styled(
+                        format!("{} ", finding.path),
+                        Style::default().add_modifier(Modifier::BOLD),
+                    ),
+                    Span::raw(finding.message.as_str()),
+                    Span::styled(
+                        match finding.count {
+                            1 => String::new(),
+                            count => format!(" ×{}", count),
+                        },
+                        Style::default().fg(app.theme.muted),
+                    ),
+                ]))
+            })
+            .collect(),
+    };
+    let mut title = format!(
+        "数据检查 {}",
+        item.map_or("", |item| item.stock_code.as_ref())
+    );
+    if let Some(report) = report {
+        title.push_str(&format!(
+            "（默认值 {}，已丢弃 {}，不一致 {}）",
+            report.count(FindingKind::Defaulted),
+            report.count(FindingKind::Dropped),
+            report.count(FindingKind::Invalid)
+        ));
+    }
+    let list = List::new(items).block(Block::default().borders(Borders::ALL).title(title));
+    f.render_widget(Clear, area);
+    f.render_widget(list, area);
+}
+
+/// 自选表格需要的宽度：各列宽度、列间距、对比标记与边框
+fn watchlist_width(app: &App) -> u16 {
+    let columns = &app.watchlist.columns;
+    columns.iter().map(|column| column.width() + 1).sum::<u16>() + 3
+}
+
+fn draw_list<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
+    let columns = &app.watchlist.columns;
+    let header = Row::new(
+        std::iter::once(Cell::from(" ")).chain(columns.iter().map(|column| {
+            let mut title = column.title().to_string();
+            if app.watchlist.sort_by == Some(*column) {
+                title.push(if app.watchlist.descending {
+                    '▼'
+                } else {
+                    '▲'
+                });
+            }
+            Cell::from(title)
+        })),
+    )
+    .style(Style::default().add_modifier(Modifier::BOLD));
+    let rows: Vec<Row> = app
         .stock_ids
         .items
         .iter()
         .map(|i| {
-            let lines = vec![Spans::from(Span::styled(
-                i.stock_code,
-                Style::default().add_modifier(Modifier::ITALIC),
-            ))];
-            ListItem::new(lines).style(Style::default().fg(Color::Black).bg(Color::White))
+            let mark = if app.compare.contains(&i.stock_code_num) {
+                "*"
+            } else {
+                " "
+            };
+            let quote = i.quote();
+            let direction_color = match quote.as_ref().map(|quote| quote.direction()) {
+                Some(1) => app.theme.up,
+                Some(-1) => app.theme.down,
+                _ => app.theme.list_fg,
+            };
+            let cells = std::iter::once(Cell::from(mark)).chain(columns.iter().map(|column| {
+                let cell = Cell::from(column.text(i, quote.as_ref()));
+                if column.directional() {
+                    cell.style(Style::default().fg(direction_color))
+                } else {
+                    cell
+                }
+            }));
+            Row::new(cells).style(
+                Style::default()
+                    .fg(app.theme.list_fg)
+                    .bg(app.theme.list_bg)
+                    .add_modifier(Modifier::ITALIC),
+            )
         })
         .collect();
-    let widget_items = List::new(items)
+    let widths: Vec<Constraint> = std::iter::once(Constraint::Length(1))
+        .chain(
+            columns
+                .iter()
+                .map(|column| Constraint::Length(column.width())),
+        )
+        .collect();
+    let table = Table::new(rows)
+        .header(header)
         .block(Block::default().borders(Borders::ALL).title("Stock List"))
+        .widths(&widths)
         .highlight_style(
             Style::default()
-                .bg(Color::LightGreen)
+                .bg(app.theme.list_highlight_bg)
                 .add_modifier(Modifier::BOLD),
         );
-    f.render_stateful_widget(widget_items, area, &mut app.stock_ids.state);
+    let mut state = TableState::default();
+    state.select(app.stock_ids.state.selected());
+    f.render_stateful_widget(table, area, &mut state);
+    // 与表格的滚动方式一致：从第一行开始，选中行超出可见行数时向下滚动
+    let visible_rows = area.height.saturating_sub(3).max(1) as usize;
+    app.list_offset = app
+        .stock_ids
+        .state
+        .selected()
+        .map_or(0, |selected| (selected + 1).saturating_sub(visible_rows));
+    app.list_area = area;
+}
+
+/// 股票名称与刷新状态：最近更新时间、市场状态、过期与暂停提示
+fn stock_block_title<'a>(app: &'a App) -> Spans<'a> {
+    let item = match app.selected_item_brief_info() {
+        Some(item) => item,
+        None => return Spans::from("自选列表为空"),
+    };
+    let mut spans = vec![Span::raw(item.stock_name.as_ref())];
+    if let Some(updated_at) = item.updated_at {
+        spans.push(Span::raw(format!(
+            "  更新于 {}",
+            updated_at.format("%H:%M:%S")
+        )));
+    }
+    spans.push(Span::raw(format!("  {}", app.market_status().name())));
+    if app.is_stale() {
+        spans.push(Span::styled(
+            "  数据过期",
+            Style::default()
+                .fg(app.theme.error)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+    if app.scheduler.paused {
+        spans.push(Span::styled(
+            "  自动刷新已暂停",
+            Style::default().fg(app.theme.warning),
+        ));
+    }
+    if item.loading {
+        spans.push(Span::raw("  加载中"));
+    } else if let Some(ref err) = item.fetch_error {
+        spans.push(Span::styled(
+            format!("  {}（R 重试）", err.name()),
+            Style::default().fg(app.theme.error),
+        ));
+    }
+    if let Some(ref status) = app.status {
+        spans.push(Span::raw(format!("  {}", status)));
+    }
+    Spans::from(spans)
 }
 
-fn draw_stock_block<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+fn draw_stock_block<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
     let block = Block::default()
         .borders(Borders::ALL)
-        .title(app.selected_item_brief_info().stock_name);
+        .title(stock_block_title(app));
     f.render_widget(block, area);
 
+    // 高度充足时简要信息占五行，较矮时压缩为一行，再矮或没有数据时不显示
+    let header_height = match area.height {
+        _ if app.k_line_datas.is_empty() => 0,
+        height if height < MIN_HEADER_HEIGHT => 0,
+        height if height < COMPACT_HEADER_HEIGHT => 1,
+        _ => 5,
+    };
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .margin(1)
-        .constraints([Constraint::Percentage(10), Constraint::Percentage(90)])
+        .constraints([Constraint::Length(header_height), Constraint::Min(0)])
         .split(area);
-    draw_stock_header(f, app, chunks[0]);
-    draw_k_line_chart(f, app, chunks[1]);
+    if header_height > 0 {
+        draw_stock_header(f, app, chunks[0]);
+    }
+    if app.compare_mode {
+        draw_compare_chart(f, app, chunks[1]);
+    } else {
+        draw_k_line_chart(f, app, chunks[1]);
+    }
 }
 
+/// 简要信息，取当前股票最新一天的日 k 线，尚未加载时显示 `--`；
+/// 高度只有一行时去掉边框并把各项排成一行
 fn draw_stock_header<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
-    let text = vec![
-        Spans::from(Span::styled(
-            "最新: 0.900",
+    let theme = &app.theme;
+    let latest = app
+        .selected_item_brief_info()
+        .and_then(|item| item.day_k_line_datas())
+        .and_then(|datas| datas.into_iter().max_by(|a, b| a.date.cmp(&b.date)));
+    // 价格与昨收比较，高于昨收为涨的颜色，低于为跌的颜色
+    let price_style = |value: f64, pre_close: f64| {
+        if value > pre_close {
+            Style::default().fg(theme.up)
+        } else if value < pre_close {
+            Style::default().fg(theme.down)
+        } else {
             Style::default()
-                .fg(Color::Green)
-                .add_modifier(Modifier::BOLD),
-        )),
+        }
+    };
+    let price = |label: &str, value: Option<(f64, f64)>| match value {
+        Some((value, pre_close)) => Span::styled(
+            format!("{}: {:.3}  ", label, value),
+            price_style(value, pre_close),
+        ),
+        None => Span::raw(format!("{}: --  ", label)),
+    };
+    let amount = |label: &str, value: Option<f64>| {
+        Span::raw(format!(
+            "{}: {}",
+            label,
+            value.map_or_else(|| String::from("--"), format_amount)
+        ))
+    };
+    let text = vec![
+        Spans::from(match &latest {
+            Some(k_line_data) => Span::styled(
+                format!("最新: {:.3}  {:+.2}%", k_line_data.end, k_line_data.up_rate),
+                price_style(k_line_data.end, k_line_data.pre_close).add_modifier(Modifier::BOLD),
+            ),
+            None => Span::styled("最新: --", Style::default().add_modifier(Modifier::BOLD)),
+        }),
         Spans::from(vec![
-            Span::styled("今开: 0.902  ", Style::default().fg(Color::Green)),
-            Span::styled("最高: 0.912  ", Style::default().fg(Color::Red)),
-            Span::from("量: 770.7 万"),
+            price(
+                "今开",
+                latest.as_ref().map(|data| (data.start, data.pre_close)),
+            ),
+            price(
+                "最高",
+                latest.as_ref().map(|data| (data.max, data.pre_close)),
+            ),
+            amount("量", latest.as_ref().map(|data| data.quantity as f64)),
         ]),
         Spans::from(vec![
-            Span::from("昨收: 0.904  "),
-            Span::styled("最低: 0.894  ", Style::default().fg(Color::Green)),
-            Span::from("额: 6.95 亿"),
+            price(
+                "昨收",
+                latest.as_ref().map(|data| (data.pre_close, data.pre_close)),
+            ),
+            price(
+                "最低",
+                latest.as_ref().map(|data| (data.min, data.pre_close)),
+            ),
+            amount("额", latest.as_ref().map(|data| data.amount as f64)),
         ]),
     ];
+    let style = Style::default().fg(theme.header_fg).bg(theme.header_bg);
+    if area.height < 3 {
+        let mut spans = Vec::new();
+        for line in text {
+            spans.extend(line.0);
+            spans.push(Span::raw("  "));
+        }
+        f.render_widget(Paragraph::new(Spans::from(spans)).style(style), area);
+        return;
+    }
     let block = Block::default().borders(Borders::ALL).title(Span::styled(
         "简要信息",
         Style::default().add_modifier(Modifier::BOLD),
     ));
     let paragraph = Paragraph::new(text)
-        .style(Style::default().fg(Color::White).bg(Color::Black))
+        .style(style)
         .block(block)
         .wrap(Wrap { trim: true });
     f.render_widget(paragraph, area);
 }
 
-fn draw_k_line_chart<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+/// 图表标题：光标所在 K 线、画线工具的状态
+fn k_line_chart_title(app: &App) -> String {
+    let mut title = String::from("Kline");
+    if let Some(k_line_data) = app.chart_view.cursor.and_then(|i| app.k_line_datas.get(i)) {
+        title.push_str(&format!(
+            "  {} 开 {:.3} 高 {:.3} 低 {:.3} 收 {:.3}",
+            k_line_data.date, k_line_data.start, k_line_data.max, k_line_data.min, k_line_data.end
+        ));
+    }
+    if let Some(ref tool) = app.drawing_tool {
+        title.push_str(&format!(
+            "  [{}: 回车或点击放置锚点，Esc 取消]",
+            tool.name()
+        ));
+    }
+    title
+}
+
+fn k_line_annotations(app: &App) -> Vec<Annotation<'static>> {
+    let range = app.chart_view.range(app.k_line_datas.len());
+    let mut annotations: Vec<Annotation> = app
+        .drawings
+        .items
+        .iter()
+        .enumerate()
+        .filter_map(|(i, drawing)| {
+            let color = if app.drawings.selected == Some(i) {
+                app.theme.drawing_selected
+            } else {
+                app.theme.drawing
+            };
+            drawing.to_annotation(&app.k_line_datas, range.start, color)
+        })
+        .collect();
+    if let Some(cursor) = app
+        .chart_view
+        .cursor
+        .filter(|cursor| range.contains(cursor))
+    {
+        annotations.push(Annotation::Crosshair {
+            x: (cursor - range.start) as f64,
+            y: app.k_line_datas[cursor].end,
+            color: app.theme.crosshair,
+        });
+    }
+    annotations
+}
+
+fn draw_k_line_chart<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
     // let x_labels = vec![
     //     Span::styled(
     //         format!("{}", app.window[0]),
@@ -164,17 +1129,28 @@ fn draw_k_line_chart<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
     //         Style::default().add_modifier(Modifier::BOLD),
     //     ),
     // ];
+    // 平均 K 线已按全部数据算好，按普通 K 线绘制
+    let chart_type = match app.chart_type {
+        ChartType::HeikinAshi => ChartType::Candlestick,
+        chart_type => chart_type,
+    };
+    let k_line_datas = app.drawn_k_line_datas();
     let datasets = vec![Dataset::default()
-        .name("K 线图")
+        .name(format!("K 线图 ({})", app.chart_type.name()))
         .marker(symbols::Marker::Braille)
-        .data(&app.k_line_datas)];
+        .chart_type(chart_type)
+        .style(Style::default().fg(app.theme.line))
+        .candle_colors(app.theme.up, app.theme.down)
+        .data(&k_line_datas)];
     let kline = KLine::new(datasets)
+        .renderer(app.renderer)
+        .annotations(k_line_annotations(app))
         .block(
             Block::default()
                 .title(Span::styled(
-                    "Kline",
+                    k_line_chart_title(app),
                     Style::default()
-                        .fg(Color::Cyan)
+                        .fg(app.theme.title)
                         .add_modifier(Modifier::BOLD),
                 ))
                 .borders(Borders::ALL),
@@ -182,24 +1158,435 @@ fn draw_k_line_chart<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
         .x_axis(
             Axis::default()
                 .title("X Axis")
-                .style(Style::default().fg(Color::Gray))
+                .style(Style::default().fg(app.theme.axis))
                 .bounds(app.get_x_bounds())
                 .labels(vec![
-                    Span::styled(app.get_start_x_label(), Style::default().add_modifier(Modifier::BOLD)),
-                    Span::styled(app.get_end_x_label(), Style::default().add_modifier(Modifier::BOLD))
-                ])
-
+                    Span::styled(
+                        app.get_start_x_label(),
+                        Style::default().add_modifier(Modifier::BOLD),
+                    ),
+                    Span::raw(app.get_center_x_label()),
+                    Span::styled(
+                        app.get_end_x_label(),
+                        Style::default().add_modifier(Modifier::BOLD),
+                    ),
+                ]),
         )
         .y_axis(
             Axis::default()
                 .title("Y Axis")
-                .style(Style::default().fg(Color::Gray))
+                .style(Style::default().fg(app.theme.axis))
                 .bounds(app.get_y_bounds())
                 .labels(vec![
-                    Span::styled(app.get_y_bounds()[0].to_string(), Style::default().add_modifier(Modifier::BOLD)),
-                    Span::styled(app.get_center_y_label().to_string(), Style::default().add_modifier(Modifier::BOLD)),
-                    Span::styled(app.get_y_bounds()[1].to_string(), Style::default().add_modifier(Modifier::BOLD))
-                ])
+                    Span::styled(
+                        app.get_y_bounds()[0].to_string(),
+                        Style::default().add_modifier(Modifier::BOLD),
+                    ),
+                    Span::styled(
+                        app.get_center_y_label().to_string(),
+                        Style::default().add_modifier(Modifier::BOLD),
+                    ),
+                    Span::styled(
+                        app.get_y_bounds()[1].to_string(),
+                        Style::default().add_modifier(Modifier::BOLD),
+                    ),
+                ]),
+        );
+    let graph_area = kline.graph_area(area);
+    f.render_widget(kline, area);
+    app.chart_area = graph_area;
+}
+
+fn draw_compare_chart<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let lines = app.compare_lines();
+    let mut title = String::from("对比");
+    for item in app
+        .stock_ids
+        .items
+        .iter()
+        .filter(|item| app.compare.contains(&item.stock_code_num))
+    {
+        if item.loading {
+            title.push_str(&format!("  {} 加载中", item.stock_code));
+        } else if let Some(ref err) = item.fetch_error {
+            title.push_str(&format!("  {} {}", item.stock_code, err.name()));
+        }
+    }
+    let datasets = lines
+        .iter()
+        .map(|line| {
+            Dataset::default()
+                .name(line.name.as_str())
+                .marker(symbols::Marker::Braille)
+                .chart_type(ChartType::Line)
+                .style(Style::default().fg(line.color))
+                .data(&line.datas)
+        })
+        .collect();
+
+    let mut min = 0.0_f64;
+    let mut max = 0.0_f64;
+    for k_line_data in lines.iter().flat_map(|line| &line.datas) {
+        min = min.min(k_line_data.end);
+        max = max.max(k_line_data.end);
+    }
+    let len = lines
+        .first()
+        .map(|line| line.datas.len())
+        .unwrap_or_default();
+    let x_labels = match lines.first() {
+        Some(line) if len > 0 => vec![
+            Span::raw(line.datas[0].date.to_string()),
+            Span::raw(line.datas[len - 1].date.to_string()),
+        ],
+        _ => vec![],
+    };
+    let kline = KLine::new(datasets)
+        .block(
+            Block::default()
+                .title(Span::styled(
+                    title,
+                    Style::default()
+                        .fg(app.theme.title)
+                        .add_modifier(Modifier::BOLD),
+                ))
+                .borders(Borders::ALL),
+        )
+        .x_axis(
+            Axis::default()
+                .style(Style::default().fg(app.theme.axis))
+                .bounds([0.0, len.saturating_sub(1).max(1) as f64])
+                .labels(x_labels),
+        )
+        .y_axis(
+            Axis::default()
+                .style(Style::default().fg(app.theme.axis))
+                .bounds([min, max])
+                .labels(vec![
+                    Span::raw(format!("{:+.2}%", min)),
+                    Span::raw(format!("{:+.2}%", (min + max) / 2.0)),
+                    Span::raw(format!("{:+.2}%", max)),
+                ]),
         );
     f.render_widget(kline, area);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::{App, StockBriefInfo};
+    use crate::decode::decode;
+    use crate::drawing::{Anchor, Drawing, Drawings};
+    use crate::keymap::Action;
+    use crate::market::{self, MarketCalendar};
+    use crate::net::FetchResult;
+    use crate::portfolio::{Side, Transaction};
+    use crate::storage;
+    use chrono::NaiveDate;
+    use std::{env, sync::Once};
+    use tui::backend::TestBackend;
+
+    /// 数据目录指向一个不存在的目录，配置、自选、主题等都使用默认值
+    fn app() -> App<'static> {
+        static INIT: Once = Once::new();
+        INIT.call_once(|| {
+            env::set_var(
+                storage::DATA_DIR_ENV,
+                env::temp_dir().join("stock_rs_snapshots"),
+            );
+        });
+        let mut app = App::new();
+        // 不按 `COLORTERM`、`TERM` 转换颜色，与运行测试的终端无关
+        app.theme = Theme::default();
+        // 固定为非交易日，标题中的市场状态不随运行时间变化
+        app.calendar = MarketCalendar::new(&[market::shanghai_now().date_naive()]);
+        app
+    }
+
+    /// 载入 `assets/resp.json` 作为第一只股票的数据
+    fn app_with_sample() -> App<'static> {
+        let mut app = app();
+        app.on_fetched(FetchResult {
+            gp_id: 512690,
+            cursor: None,
+            result: Ok(decode(include_bytes!("../assets/resp.json")).unwrap()),
+        });
+        for item in app.stock_ids.items.iter_mut() {
+            item.updated_at = None;
+        }
+        app
+    }
+
+    /// 绘制整个界面，返回每行的字符，宽字符后被占用的单元格跳过
+    fn render(app: &mut App, width: u16, height: u16) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal.draw(|f| draw(f, app)).unwrap();
+        let buffer = terminal.backend().buffer();
+        let mut lines = Vec::new();
+        for y in 0..height {
+            let mut line = String::new();
+            let mut x = 0;
+            while x < width {
+                let symbol = &buffer.get(x, y).symbol;
+                line.push_str(symbol);
+                x += symbol.width().max(1) as u16;
+            }
+            lines.push(line);
+        }
+        lines.join("\n")
+    }
+
+    #[test]
+    fn chart_with_builtin_data() {
+        let mut app = app();
+        insta::assert_snapshot!(render(&mut app, 120, 36));
+    }
+
+    #[test]
+    fn chart_with_sample() {
+        let mut app = app_with_sample();
+        insta::assert_snapshot!(render(&mut app, 120, 36));
+    }
+
+    #[test]
+    fn overview_with_sample() {
+        let mut app = app_with_sample();
+        app.tab = Tab::Overview;
+        insta::assert_snapshot!(render(&mut app, 120, 36));
+    }
+
+    #[test]
+    fn flows_with_sample() {
+        let mut app = app_with_sample();
+        app.tab = Tab::Flows;
+        insta::assert_snapshot!(render(&mut app, 100, 24));
+    }
+
+    #[test]
+    fn empty_watchlist() {
+        let mut app = app();
+        app.stock_ids.items.clear();
+        app.on_bottom();
+        for action in [
+            Action::SelectNext,
+            Action::SelectPrevious,
+            Action::SelectFirst,
+            Action::SelectLast,
+            Action::CursorLeft,
+            Action::CursorRight,
+            Action::ZoomIn,
+            Action::ZoomOut,
+            Action::PanLeft,
+            Action::PanRight,
+            Action::ToggleCompare,
+            Action::CompareView,
+            Action::CompareView,
+            Action::Export,
+            Action::Retry,
+        ] {
+            app.on_action(action);
+        }
+        let mut snapshot = render(&mut app, 100, 24);
+        for tab in [Tab::Overview, Tab::Flows, Tab::Portfolio] {
+            app.tab = tab;
+            snapshot.push('\n');
+            snapshot.push_str(&render(&mut app, 100, 24));
+        }
+        insta::assert_snapshot!(snapshot);
+    }
+
+    #[test]
+    fn no_bars() {
+        let mut app = app();
+        app.k_line_datas.clear();
+        app.on_action(Action::ZoomIn);
+        app.on_action(Action::CursorRight);
+        assert_eq!(app.get_start_x_label(), "");
+        insta::assert_snapshot!(render(&mut app, 100, 30));
+    }
+
+    #[test]
+    fn single_bar() {
+        let mut app = app();
+        app.k_line_datas.truncate(1);
+        app.on_action(Action::CursorLeft);
+        insta::assert_snapshot!(render(&mut app, 100, 30));
+    }
+
+    /// 价格不变时 y 轴范围向两侧扩展，K 线画在中间
+    #[test]
+    fn flat_prices() {
+        let mut app = app();
+        for k_line_data in app.k_line_datas.iter_mut() {
+            k_line_data.start = 0.9;
+            k_line_data.end = 0.9;
+            k_line_data.min = 0.9;
+            k_line_data.max = 0.9;
+        }
+        let [min, max] = app.get_y_bounds();
+        assert!(min < 0.9 && max > 0.9);
+        insta::assert_snapshot!(render(&mut app, 100, 30));
+    }
+
+    /// 平移后趋势线的一个锚点移出可见范围，线段裁剪后仍然绘制
+    #[test]
+    fn trend_line_with_anchor_off_screen() {
+        let mut app = app();
+        let first = &app.k_line_datas[0];
+        let last = &app.k_line_datas[app.k_line_datas.len() - 1];
+        let trend_line = Drawing::TrendLine {
+            from: Anchor {
+                date: first.date.to_string(),
+                price: first.min,
+            },
+            to: Anchor {
+                date: last.date.to_string(),
+                price: last.max,
+            },
+        };
+        app.chart_view.start = 5;
+        app.chart_view.len = 20;
+        let without = render(&mut app, 100, 30);
+        app.drawings.add(trend_line);
+        let with = render(&mut app, 100, 30);
+        assert_ne!(without, with);
+        insta::assert_snapshot!(with);
+    }
+
+    /// 鼠标点中画线选中它，拖动锚点移动，右键删除；点击空白处取消选择
+    #[test]
+    fn edit_drawing_with_mouse() {
+        let mut app = app();
+        app.chart_view.start = 5;
+        app.chart_view.len = 20;
+        render(&mut app, 100, 30);
+        let visible: Vec<String> = app
+            .visible_k_line_datas()
+            .iter()
+            .map(|k_line_data| k_line_data.date.to_string())
+            .collect();
+        let [y_min, y_max] = app.get_y_bounds();
+        let price = |ratio: f64| y_min + (y_max - y_min) * ratio;
+        // 图表坐标对应的单元格
+        let screen = |app: &App, x: usize, price: f64| {
+            let area = app.chart_area;
+            let [x_min, x_max] = app.get_x_bounds();
+            let column = (x as f64 - x_min) / (x_max - x_min) * area.width as f64;
+            let row = (y_max - price) / (y_max - y_min) * area.height as f64;
+            (area.left() + column as u16, area.top() + row as u16)
+        };
+        app.drawings = Drawings {
+            // 保存到其它测试不会读取的文件
+            stock_code: String::from("edit_drawing_with_mouse"),
+            items: vec![Drawing::TrendLine {
+                from: Anchor {
+                    date: visible[2].clone(),
+                    price: price(0.2),
+                },
+                to: Anchor {
+                    date: visible[10].clone(),
+                    price: price(0.6),
+                },
+            }],
+            selected: None,
+        };
+
+        let (column, row) = screen(&app, 10, price(0.6));
+        app.on_chart_click(column, row);
+        assert_eq!(app.drawings.selected, Some(0));
+        let (column, row) = screen(&app, 15, price(0.8));
+        app.on_chart_drag(column, row);
+        app.on_mouse_up();
+        match &app.drawings.items[0] {
+            Drawing::TrendLine { from, to } => {
+                assert_eq!(from.date, visible[2]);
+                assert_eq!(to.date, visible[15]);
+                assert!((to.price - price(0.8)).abs() < (y_max - y_min) / 10.0);
+            }
+            drawing => panic!("unexpected drawing: {:?}", drawing),
+        }
+
+        let (column, row) = screen(&app, 18, price(0.1));
+        app.on_chart_click(column, row);
+        assert_eq!(app.drawings.selected, None);
+
+        let (column, row) = screen(&app, 2, price(0.2));
+        app.on_chart_right_click(column, row);
+        assert!(app.drawings.items.is_empty());
+    }
+
+    /// 只刷新列表中显示的行与选中的股票，滚动后新出现的行补充加载
+    #[test]
+    fn refresh_only_visible_rows() {
+        let mut app = app();
+        for i in 0..7 {
+            app.stock_ids
+                .items
+                .push(StockBriefInfo::new("ETF", "SH000000", i));
+        }
+        // 最小高度下概览只显示列表，共 9 行
+        app.tab = Tab::Overview;
+        render(&mut app, 100, MIN_HEIGHT);
+        assert_eq!(app.refresh_targets(true), (0..9).collect::<Vec<_>>());
+        app.on_action(Action::SelectLast);
+        render(&mut app, 100, MIN_HEIGHT);
+        assert_eq!(app.refresh_targets(true), (3..12).collect::<Vec<_>>());
+        app.stock_ids.items[3].loading = true;
+        app.stock_ids.items[4].stock_data =
+            Some(decode(include_bytes!("../assets/resp.json")).unwrap());
+        assert_eq!(app.refresh_targets(false), (5..12).collect::<Vec<_>>());
+        // 收起列表时只刷新选中的股票
+        app.tab = Tab::Chart;
+        app.show_sidebar = false;
+        render(&mut app, 100, 30);
+        assert_eq!(app.refresh_targets(true), [11]);
+    }
+
+    /// 平均 K 线按全部数据计算，缩放、平移后同一根 K 线的数值不变，y 轴范围包含绘制的影线
+    #[test]
+    fn heikin_ashi_independent_of_window() {
+        let mut app = app_with_sample();
+        app.chart_type = ChartType::HeikinAshi;
+        let full = app.drawn_k_line_datas().into_owned();
+        app.chart_view.start = 30;
+        app.chart_view.len = 20;
+        let visible = app.drawn_k_line_datas();
+        assert_eq!(visible.len(), 20);
+        for (drawn, expected) in visible.iter().zip(&full[30..50]) {
+            assert_eq!(drawn.date, expected.date);
+            assert_eq!((drawn.start, drawn.end), (expected.start, expected.end));
+        }
+        let [min, max] = app.get_y_bounds();
+        for k_line_data in visible.iter() {
+            assert!(min <= k_line_data.min && k_line_data.max <= max);
+        }
+    }
+
+    /// 成交记录中卖出超过持仓时显示错误而不是空表
+    #[test]
+    fn portfolio_with_invalid_transactions() {
+        let mut app = app();
+        app.tab = Tab::Portfolio;
+        app.portfolio.transactions = vec![Transaction {
+            stock_code: String::from("SH512690"),
+            side: Side::Sell,
+            quantity: 100.0,
+            price: 1.0,
+            fee: 0.0,
+            date: NaiveDate::from_ymd_opt(2023, 4, 7).unwrap(),
+        }];
+        insta::assert_snapshot!(render(&mut app, 100, 16));
+    }
+
+    #[test]
+    fn compact_layout() {
+        let mut app = app_with_sample();
+        insta::assert_snapshot!(render(&mut app, MIN_WIDTH, MIN_HEIGHT));
+    }
+
+    #[test]
+    fn too_small() {
+        let mut app = app();
+        insta::assert_snapshot!(render(&mut app, MIN_WIDTH - 1, MIN_HEIGHT));
+    }
+}
@@ -0,0 +1,430 @@
+use crate::{app::StockBriefInfo, model::StockData, storage};
+use custom_widget::kline::klinedata::KlineData;
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, io, path::PathBuf};
+
+/// 迷你走势图使用的字符，由低到高
+const SPARK_SYMBOLS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// 迷你走势图展示的天数
+const SPARK_DAYS: usize = 5;
+
+/// 自选列表可以展示的列
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Column {
+    Name,
+    Code,
+    Price,
+    Change,
+    Volume,
+    Amount,
+    MainInflow,
+    Trend,
+}
+
+impl Column {
+    /// 列选择弹窗中列出的顺序
+    pub const ALL: [Column; 8] = [
+        Column::Name,
+        Column::Code,
+        Column::Price,
+        Column::Change,
+        Column::Volume,
+        Column::Amount,
+        Column::MainInflow,
+        Column::Trend,
+    ];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Column::Name => "名称",
+            Column::Code => "代码",
+            Column::Price => "最新",
+            Column::Change => "涨幅",
+            Column::Volume => "量",
+            Column::Amount => "额",
+            Column::MainInflow => "主力",
+            Column::Trend => "5日",
+        }
+    }
+
+    pub fn width(&self) -> u16 {
+        match self {
+            Column::Name => 12,
+            Column::Code => 9,
+            Column::Price => 7,
+            Column::Change => 7,
+            Column::Volume | Column::Amount | Column::MainInflow => 9,
+            Column::Trend => SPARK_DAYS as u16,
+        }
+    }
+
+    /// 是否按涨跌着色
+    pub fn directional(&self) -> bool {
+        matches!(
+            self,
+            Column::Price | Column::Change | Column::MainInflow | Column::Trend
+        )
+    }
+
+    pub fn text(&self, item: &StockBriefInfo, quote: Option<&Quote>) -> String {
+        let quote = match (self, quote) {
+            (Column::Name, _) => return item.stock_name.to_string(),
+            (Column::Code, _) => return item.stock_code.to_string(),
+            (_, Some(quote)) => quote,
+            (_, None) => return String::from("-"),
+        };
+        match self {
+            Column::Price => format!("{:.3}", quote.price),
+            Column::Change => format!("{:+.2}%", quote.change),
+            Column::Volume => format_amount(quote.volume),
+            Column::Amount => format_amount(quote.amount),
+            Column::MainInflow => quote
+                .main_inflow
+                .map(format_amount)
+                .unwrap_or_else(|| String::from("-")),
+            Column::Trend => sparkline(&quote.closes),
+            Column::Name | Column::Code => unreachable!(),
+        }
+    }
+
+    /// 排序使用的数值，名称、代码列和没有数据时返回 `None`
+    fn value(&self, quote: Option<&Quote>) -> Option<f64> {
+        let quote = quote?;
+        match self {
+            Column::Price => Some(quote.price),
+            Column::Change | Column::Trend => Some(quote.change),
+            Column::Volume => Some(quote.volume),
+            Column::Amount => Some(quote.amount),
+            Column::MainInflow => quote.main_inflow,
+            Column::Name | Column::Code => None,
+        }
+    }
+
+    /// 比较两只股票在该列上的值，`value` 为预先计算的排序数值，没有数据的始终排在最后
+    fn compare(
+        &self,
+        (a, a_value): (&StockBriefInfo, Option<f64>),
+        (b, b_value): (&StockBriefInfo, Option<f64>),
+        descending: bool,
+    ) -> Ordering {
+        let order = |ordering: Ordering| {
+            if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        };
+        match self {
+            Column::Name => order(a.stock_name.cmp(&b.stock_name)),
+            Column::Code => order(a.stock_code.cmp(&b.stock_code)),
+            _ => match (a_value, b_value) {
+                (Some(a), Some(b)) => order(a.partial_cmp(&b).unwrap_or(Ordering::Equal)),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            },
+        }
+    }
+}
+
+/// 由 `StockData` 计算出的最新行情
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Quote {
+    pub price: f64,
+    pub change: f64,
+    pub volume: f64,
+    pub amount: f64,
+    pub main_inflow: Option<f64>,
+    /// 最近几天的收盘价，按日期升序
+    pub closes: Vec<f64>,
+}
+
+impl Quote {
+    pub fn from_stock_data(stock_data: &StockData) -> Option<Quote> {
+        let main_inflow = stock_data.get_latest_main_in().map(|main| main as f64);
+        Quote::from_k_line_datas(stock_data.get_k_line_datas(), main_inflow)
+    }
+
+    pub fn from_k_line_datas(
+        mut k_line_datas: Vec<KlineData>,
+        main_inflow: Option<f64>,
+    ) -> Option<Quote> {
+        k_line_datas.sort_by(|a, b| a.date.cmp(&b.date));
+        let latest = k_line_datas.last()?;
+        let closes = k_line_datas
+            .iter()
+            .rev()
+            .take(SPARK_DAYS)
+            .rev()
+            .map(|k_line_data| k_line_data.end)
+            .collect();
+        Some(Quote {
+            price: latest.end,
+            change: latest.up_rate,
+            volume: latest.quantity as f64,
+            amount: latest.amount as f64,
+            main_inflow,
+            closes,
+        })
+    }
+
+    /// 涨跌方向：1 上涨，-1 下跌，0 平
+    pub fn direction(&self) -> i8 {
+        if self.change > 0.0 {
+            1
+        } else if self.change < 0.0 {
+            -1
+        } else {
+            0
+        }
+    }
+}
+
+/// 以万、亿为单位格式化数量
+pub fn format_amount(value: f64) -> String {
+    if value.abs() >= 1e8 {
+        format!("{:.2}亿", value / 1e8)
+    } else if value.abs() >= 1e4 {
+        format!("{:.1}万", value / 1e4)
+    } else {
+        format!("{:.0}", value)
+    }
+}
+
+/// 用方块字符绘制迷你走势图
+pub fn sparkline(values: &[f64]) -> String {
+    let min = values.iter().cloned().fold(f64::MAX, f64::min);
+    let max = values.iter().cloned().fold(f64::MIN, f64::max);
+    values
+        .iter()
+        .map(|value| {
+            let level = if max > min {
+                ((value - min) / (max - min) * (SPARK_SYMBOLS.len() - 1) as f64).round() as usize
+            } else {
+                SPARK_SYMBOLS.len() / 2
+            };
+            SPARK_SYMBOLS[level]
+        })
+        .collect()
+}
+
+/// 自选列表的展示设置，保存在数据目录下的 `watchlist.json`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct WatchlistConfig {
+    pub columns: Vec<Column>,
+    pub sort_by: Option<Column>,
+    pub descending: bool,
+}
+
+impl Default for WatchlistConfig {
+    fn default() -> Self {
+        WatchlistConfig {
+            columns: vec![Column::Code, Column::Price, Column::Change, Column::Trend],
+            sort_by: None,
+            descending: true,
+        }
+    }
+}
+
+impl WatchlistConfig {
+    fn path() -> PathBuf {
+        storage::data_dir().join("watchlist.json")
+    }
+
+    pub fn load() -> io::Result<WatchlistConfig> {
+        Ok(storage::load_json(&Self::path())?.unwrap_or_default())
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        storage::save_json(&Self::path(), self)
+    }
+
+    /// 在展示的列之间循环切换排序列，最后回到不排序
+    pub fn next_sort_column(&mut self) {
+        let columns = &self.columns;
+        self.sort_by = match self.sort_by {
+            None => columns.first().copied(),
+            Some(column) => columns.iter().skip_while(|c| **c != column).nth(1).copied(),
+        };
+    }
+
+    /// 打开或关闭某一列，按 `Column::ALL` 的顺序插入，至少保留一列
+    pub fn toggle_column(&mut self, column: Column) {
+        match self.columns.iter().position(|c| *c == column) {
+            Some(_) if self.columns.len() == 1 => {}
+            Some(i) => {
+                self.columns.remove(i);
+                if self.sort_by == Some(column) {
+                    self.sort_by = None;
+                }
+            }
+            None => {
+                let rank = |column: &Column| Column::ALL.iter().position(|c| c == column);
+                let i = self
+                    .columns
+                    .iter()
+                    .position(|c| rank(c) > rank(&column))
+                    .unwrap_or(self.columns.len());
+                self.columns.insert(i, column);
+            }
+        }
+    }
+
+    /// 按排序列排序，每只股票的行情只计算一次
+    pub fn sort(&self, items: &mut Vec<StockBriefInfo>) {
+        let column = match self.sort_by {
+            Some(column) => column,
+            None => return,
+        };
+        let mut keyed: Vec<(Option<f64>, StockBriefInfo)> = items
+            .drain(..)
+            .map(|item| (column.value(item.quote().as_ref()), item))
+            .collect();
+        keyed.sort_by(|(a_value, a), (b_value, b)| {
+            column.compare((a, *a_value), (b, *b_value), self.descending)
+        });
+        items.extend(keyed.into_iter().map(|(_, item)| item));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::borrow::Cow;
+
+    fn item(code: &'static str, closes: &[f64]) -> StockBriefInfo<'static> {
+        let mut item = StockBriefInfo::new(code, code, 0);
+        if !closes.is_empty() {
+            let datas = closes
+                .iter()
+                .enumerate()
+                .map(|(i, end)| KlineData {
+                    date: Cow::Owned(format!("2023-04-{:02}", i + 1)),
+                    end: *end,
+                    up_rate: *end,
+                    ..KlineData::default()
+                })
+                .collect();
+            item.imported = Some(datas);
+        }
+        item
+    }
+
+    fn codes(items: &[StockBriefInfo]) -> Vec<String> {
+        items
+            .iter()
+            .map(|item| item.stock_code.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn sort_by_price() {
+        let mut items = vec![
+            item("b", &[2.0]),
+            item("none", &[]),
+            item("c", &[3.0]),
+            item("a", &[1.0]),
+        ];
+        let mut config = WatchlistConfig {
+            sort_by: Some(Column::Price),
+            ..WatchlistConfig::default()
+        };
+        config.sort(&mut items);
+        assert_eq!(codes(&items), ["c", "b", "a", "none"]);
+        // 升序时没有数据的仍然排在最后
+        config.descending = false;
+        config.sort(&mut items);
+        assert_eq!(codes(&items), ["a", "b", "c", "none"]);
+    }
+
+    #[test]
+    fn sort_by_code_and_unsorted() {
+        let mut items = vec![item("b", &[]), item("c", &[]), item("a", &[])];
+        let mut config = WatchlistConfig {
+            sort_by: None,
+            ..WatchlistConfig::default()
+        };
+        config.sort(&mut items);
+        assert_eq!(codes(&items), ["b", "c", "a"]);
+        config.sort_by = Some(Column::Code);
+        config.descending = false;
+        config.sort(&mut items);
+        assert_eq!(codes(&items), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn next_sort_column_cycles() {
+        let mut config = WatchlistConfig::default();
+        let mut seen = Vec::new();
+        for _ in 0..=config.columns.len() {
+            config.next_sort_column();
+            seen.push(config.sort_by);
+        }
+        assert_eq!(
+            seen,
+            [
+                Some(Column::Code),
+                Some(Column::Price),
+                Some(Column::Change),
+                Some(Column::Trend),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn toggle_column_keeps_order() {
+        let mut config = WatchlistConfig {
+            sort_by: Some(Column::Price),
+            ..WatchlistConfig::default()
+        };
+        config.toggle_column(Column::Name);
+        config.toggle_column(Column::Volume);
+        assert_eq!(
+            config.columns,
+            [
+                Column::Name,
+                Column::Code,
+                Column::Price,
+                Column::Change,
+                Column::Volume,
+                Column::Trend
+            ]
+        );
+        // 关闭排序列时取消排序
+        config.toggle_column(Column::Price);
+        assert_eq!(config.sort_by, None);
+        assert!(!config.columns.contains(&Column::Price));
+    }
+
+    #[test]
+    fn toggle_keeps_last_column() {
+        let mut config = WatchlistConfig {
+            columns: vec![Column::Code],
+            ..WatchlistConfig::default()
+        };
+        config.toggle_column(Column::Code);
+        assert_eq!(config.columns, [Column::Code]);
+    }
+
+    #[test]
+    fn sparkline_levels() {
+        assert_eq!(sparkline(&[1.0, 2.0, 3.0, 4.0, 8.0]), "▁▂▃▄█");
+        assert_eq!(sparkline(&[3.0, 1.0]), "█▁");
+        // 价格不变时画在中间
+        assert_eq!(sparkline(&[1.0, 1.0, 1.0]), "▅▅▅");
+        assert_eq!(sparkline(&[]), "");
+    }
+
+    #[test]
+    fn quote_from_k_line_datas() {
+        let quote = item("a", &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]).quote().unwrap();
+        assert_eq!(quote.price, 6.0);
+        assert_eq!(quote.closes, [2.0, 3.0, 4.0, 5.0, 6.0]);
+        assert_eq!(quote.direction(), 1);
+        assert!(item("none", &[]).quote().is_none());
+    }
+}